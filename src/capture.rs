@@ -1,3 +1,5 @@
+use std::fmt;
+use std::path::PathBuf;
 use std::thread;
 use std::time::Duration;
 
@@ -5,33 +7,313 @@ use nokhwa::pixel_format::RgbFormat;
 use nokhwa::utils::{CameraFormat, CameraIndex, FrameFormat, RequestedFormat, RequestedFormatType, Resolution};
 use nokhwa::Camera;
 
-pub fn requested_format(resolution: Option<(u32, u32)>, fps: u32) -> RequestedFormat<'static> {
+use crate::detect::{self, CameraId};
+use crate::framerate::FrameRate;
+#[cfg(feature = "images")]
+use crate::image_input::ImageSource;
+use crate::pattern::{Pattern, PatternSource};
+use crate::pixfmt::PixelFormat;
+use crate::rawvideo::RawVideoSource;
+use crate::y4m::Y4mSource;
+
+/// Capture size used when no resolution is requested
+pub const DEFAULT_RESOLUTION: (u32, u32) = (1920, 1080);
+
+/// Minimum decoded pixels per ASCII cell along each axis when decoding at reduced scale
+const MIN_PIXELS_PER_CELL: u32 = 2;
+
+/// Pixel format requested from a webcam
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum CaptureFormat {
+    /// Compressed frames, decoded to RGB24 on capture
+    #[default]
+    Mjpeg,
+    /// Uncompressed YUV 4:2:2, handed to the renderer without decoding
+    Yuyv,
+    /// Uncompressed YUV 4:2:0, handed to the renderer without decoding
+    Nv12,
+}
+
+impl CaptureFormat {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "mjpeg" | "mjpg" => Some(Self::Mjpeg),
+            "yuyv" | "yuyv422" => Some(Self::Yuyv),
+            "nv12" => Some(Self::Nv12),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Mjpeg => "mjpeg",
+            Self::Yuyv => "yuyv",
+            Self::Nv12 => "nv12",
+        }
+    }
+
+    fn frame_format(self) -> FrameFormat {
+        match self {
+            Self::Mjpeg => FrameFormat::MJPEG,
+            Self::Yuyv => FrameFormat::YUYV,
+            Self::Nv12 => FrameFormat::NV12,
+        }
+    }
+
+    /// Layout of the frames a webcam opened in this format produces
+    pub fn pixel_format(self) -> PixelFormat {
+        match self {
+            Self::Mjpeg => PixelFormat::Rgb24,
+            Self::Yuyv => PixelFormat::Yuyv,
+            Self::Nv12 => PixelFormat::Nv12,
+        }
+    }
+}
+
+pub fn requested_format(
+    resolution: Option<(u32, u32)>,
+    fps: FrameRate,
+    format: CaptureFormat,
+) -> RequestedFormat<'static> {
     // Default to 1920x1080 (16:9). AbsoluteHighestFrameRate picks by pixel count
     // on tie, which selects 4:3 (e.g. 1920x1440) over 16:9 on many cameras.
-    let (w, h) = resolution.unwrap_or((1920, 1080));
+    let (w, h) = resolution.unwrap_or(DEFAULT_RESOLUTION);
     let fmt_type = RequestedFormatType::Closest(CameraFormat::new(
         Resolution::new(w, h),
-        FrameFormat::MJPEG,
-        fps,
+        format.frame_format(),
+        // nokhwa only takes whole rates, so 29.97 is requested as 30 and the
+        // Closest match settles on the camera's nearest interval
+        fps.round(),
     ));
     RequestedFormat::new::<RgbFormat>(fmt_type)
 }
 
+/// A source of frames for the capture thread.
+///
+/// Sources are opened inside the capture thread (see [`SourceSpec::open`]) so
+/// implementations don't need to be `Send`.
+pub trait FrameSource {
+    /// Capture a single frame in the layout reported by `pixel_format`
+    fn capture_frame(&mut self) -> anyhow::Result<Vec<u8>>;
+
+    /// Native frame dimensions of the source
+    fn resolution(&self) -> (u32, u32);
+
+    /// Dimensions of the last frame returned by `capture_frame`. Differs from
+    /// `resolution` for sources that decode at reduced scale.
+    fn frame_dimensions(&self) -> (u32, u32) {
+        self.resolution()
+    }
+
+    /// Tell the source how much detail the renderer needs. Sources that can
+    /// decode at reduced scale use it to skip work; others ignore it.
+    fn set_detail(&mut self, _detail: DecodeDetail) {}
+
+    /// Set a device control (exposure, gain, ...) by key. Returns the value the
+    /// device applied.
+    fn set_control(&mut self, key: &str, _value: &str) -> anyhow::Result<i32> {
+        Err(anyhow::anyhow!("this input has no camera control '{}'", key))
+    }
+
+    /// Pixel layout produced by `capture_frame`. Most sources decode to RGB24.
+    fn pixel_format(&self) -> PixelFormat {
+        PixelFormat::Rgb24
+    }
+
+    /// Release the underlying device. Called before the source is dropped.
+    fn stop_stream(&mut self);
+}
+
+/// How much of each frame the renderer uses, published by the render thread
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DecodeDetail {
+    /// ASCII grid size (columns, rows)
+    pub grid: (u32, u32),
+    /// Full-resolution RGB is needed (color theme or GUI raw preview)
+    pub full_size: bool,
+}
+
+impl Default for DecodeDetail {
+    fn default() -> Self {
+        DecodeDetail {
+            grid: (0, 0),
+            full_size: true,
+        }
+    }
+}
+
+/// Pick the JPEG DCT scaling denominator (1, 2, 4 or 8) for a frame of `size`
+/// that still leaves MIN_PIXELS_PER_CELL decoded pixels per grid cell.
+pub fn jpeg_scale_denom(size: (u32, u32), detail: DecodeDetail) -> u32 {
+    let (cols, rows) = detail.grid;
+    if detail.full_size || cols == 0 || rows == 0 {
+        return 1;
+    }
+    [8, 4, 2]
+        .into_iter()
+        .find(|&d| {
+            size.0 / d >= cols * MIN_PIXELS_PER_CELL && size.1 / d >= rows * MIN_PIXELS_PER_CELL
+        })
+        .unwrap_or(1)
+}
+
+/// Returned by sources that have run out of frames (end of file, closed pipe).
+/// The capture thread shuts the pipeline down instead of trying to reconnect.
+#[derive(Debug)]
+pub struct EndOfStream;
+
+impl fmt::Display for EndOfStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "end of input")
+    }
+}
+
+impl std::error::Error for EndOfStream {}
+
+/// Substitute `index` into a printf-style `%d` / `%0Nd` placeholder.
+/// Returns None if `pattern` has no placeholder (i.e. it names a single image).
+pub fn sequence_path(pattern: &str, index: u32) -> Option<String> {
+    let start = pattern.find('%')?;
+    let spec = &pattern[start + 1..];
+    let digits = spec.find(|c: char| !c.is_ascii_digit())?;
+    if !spec[digits..].starts_with('d') {
+        return None;
+    }
+    let width: usize = spec[..digits].parse().unwrap_or(0);
+    Some(format!(
+        "{}{:0width$}{}",
+        &pattern[..start],
+        index,
+        &spec[digits + 1..],
+        width = width
+    ))
+}
+
+/// Describes which frame source to open. Cheap to clone and `Send`, so it can be
+/// handed to the capture thread and reopened on reconnect or camera change.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SourceSpec {
+    /// V4L2 webcam. `index` is where `id` last resolved to
+    Webcam {
+        id: CameraId,
+        index: u32,
+        format: CaptureFormat,
+    },
+    /// YUV4MPEG2 file, played at its declared frame rate
+    Y4m { path: PathBuf, looping: bool },
+    /// Still image, or an image sequence when the path contains `%d` / `%0Nd`
+    Image { path: PathBuf, looping: bool },
+    /// Generated test pattern at the requested resolution and fps
+    Pattern(Pattern),
+    /// Raw frames of a fixed format and size read from stdin
+    Stdin {
+        format: PixelFormat,
+        size: (u32, u32),
+    },
+}
+
+impl SourceSpec {
+    /// Open the source with the requested resolution and frame rate.
+    pub fn open(
+        &self,
+        resolution: Option<(u32, u32)>,
+        fps: FrameRate,
+    ) -> anyhow::Result<Box<dyn FrameSource>> {
+        match self {
+            SourceSpec::Webcam { index, format, .. } => Ok(Box::new(WebcamCapture::new(
+                *index, resolution, fps, *format,
+            )?)),
+            SourceSpec::Y4m { path, looping } => Ok(Box::new(Y4mSource::open(path, *looping)?)),
+            #[cfg(feature = "images")]
+            SourceSpec::Image { path, looping } => {
+                Ok(Box::new(ImageSource::open(path, *looping, resolution)?))
+            }
+            #[cfg(not(feature = "images"))]
+            SourceSpec::Image { .. } => Err(anyhow::anyhow!(
+                "Image input is not available in this build.\n\
+                 Hint: Rebuild with: cargo build --release --features images"
+            )),
+            SourceSpec::Pattern(pattern) => {
+                Ok(Box::new(PatternSource::new(*pattern, resolution, fps)))
+            }
+            SourceSpec::Stdin { format, size } => Ok(Box::new(RawVideoSource::stdin(*format, *size))),
+        }
+    }
+
+    /// Look a webcam's identity up again, as its /dev/videoN may have changed
+    /// since it was last opened (replug, module reload). No-op for other sources.
+    pub fn resolve_camera(&mut self) -> anyhow::Result<()> {
+        if let SourceSpec::Webcam { id, index, .. } = self {
+            *index = detect::resolve_camera(id)
+                .ok_or_else(|| anyhow::anyhow!("No camera matching '{}' is connected", id))?;
+        }
+        Ok(())
+    }
+
+    /// The `key=value` form used by the control socket to report this source.
+    pub fn as_setting(&self) -> String {
+        match self {
+            SourceSpec::Webcam { index, .. } => format!("camera_index={}", index),
+            SourceSpec::Y4m { path, .. } | SourceSpec::Image { path, .. } => {
+                format!("input={}", path.display())
+            }
+            SourceSpec::Pattern(pattern) => format!("input=pattern:{}", pattern.name()),
+            SourceSpec::Stdin { .. } => "input=-".to_string(),
+        }
+    }
+}
+
+impl fmt::Display for SourceSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SourceSpec::Webcam { id, index, format } => {
+                write!(f, "/dev/video{}", index)?;
+                if !matches!(id, CameraId::Index(_)) {
+                    write!(f, " [{}]", id)?;
+                }
+                match format {
+                    CaptureFormat::Mjpeg => Ok(()),
+                    _ => write!(f, " ({})", format.name()),
+                }
+            }
+            SourceSpec::Y4m { path, looping } | SourceSpec::Image { path, looping } => {
+                write!(f, "{}{}", path.display(), if *looping { " (looping)" } else { "" })
+            }
+            SourceSpec::Pattern(pattern) => write!(f, "pattern:{}", pattern.name()),
+            SourceSpec::Stdin { format, size } => {
+                write!(f, "stdin ({} {}x{})", format.name(), size.0, size.1)
+            }
+        }
+    }
+}
+
 pub struct WebcamCapture {
     camera: Camera,
+    index: u32,
     width: u32,
     height: u32,
+    format: CaptureFormat,
+    detail: DecodeDetail,
+    /// Size and layout of the last captured frame
+    frame_dims: (u32, u32),
+    frame_format: PixelFormat,
 }
 
 impl WebcamCapture {
-    pub fn new(device_index: u32, resolution: Option<(u32, u32)>, fps: u32) -> anyhow::Result<Self> {
-        Self::open_with_retries(device_index, resolution, fps, 3)
+    pub fn new(
+        device_index: u32,
+        resolution: Option<(u32, u32)>,
+        fps: FrameRate,
+        format: CaptureFormat,
+    ) -> anyhow::Result<Self> {
+        Self::open_with_retries(device_index, resolution, fps, format, 3)
     }
 
     fn open_with_retries(
         device_index: u32,
         resolution: Option<(u32, u32)>,
-        fps: u32,
+        fps: FrameRate,
+        format: CaptureFormat,
         max_attempts: u32,
     ) -> anyhow::Result<Self> {
         let index = CameraIndex::Index(device_index);
@@ -44,8 +326,8 @@ impl WebcamCapture {
                 thread::sleep(Duration::from_millis(delay));
             }
 
-            let format = requested_format(resolution, fps);
-            match Camera::new(index.clone(), format) {
+            let requested = requested_format(resolution, fps, format);
+            match Camera::new(index.clone(), requested) {
                 Ok(mut camera) => {
                     match camera.open_stream() {
                         Ok(()) => {
@@ -54,8 +336,13 @@ impl WebcamCapture {
                             let height = cam_format.resolution().height_y;
                             return Ok(WebcamCapture {
                                 camera,
+                                index: device_index,
                                 width,
                                 height,
+                                format,
+                                detail: DecodeDetail::default(),
+                                frame_dims: (width, height),
+                                frame_format: format.pixel_format(),
                             });
                         }
                         Err(e) => {
@@ -79,9 +366,13 @@ impl WebcamCapture {
                             if let Some((w, h)) = resolution {
                                 format!(
                                     "{}\n\
-                                     Hint: Camera may not support {}x{}.\n\
+                                     Hint: Camera may not support {}x{} {}.\n\
                                      Try: v4l2-ctl --list-formats-ext -d /dev/video{}",
-                                    base, w, h, device_index
+                                    base,
+                                    w,
+                                    h,
+                                    format.name().to_uppercase(),
+                                    device_index
                                 )
                             } else {
                                 base
@@ -96,9 +387,34 @@ impl WebcamCapture {
             "Failed to open camera".to_string()
         })))
     }
+}
+
+impl FrameSource for WebcamCapture {
+    fn capture_frame(&mut self) -> anyhow::Result<Vec<u8>> {
+        // Raw formats go to the renderer as-is; it reads the luma plane directly
+        if self.format != CaptureFormat::Mjpeg {
+            let raw = self
+                .camera
+                .frame_raw()
+                .map_err(|e| anyhow::anyhow!("Frame capture failed: {}", e))?;
+            return Ok(raw.into_owned());
+        }
+
+        // When the grid is much coarser than the frame, let the JPEG decoder
+        // downscale in the DCT domain and skip colour conversion entirely
+        let denom = jpeg_scale_denom((self.width, self.height), self.detail);
+        if denom > 1 {
+            let raw = self
+                .camera
+                .frame_raw()
+                .map_err(|e| anyhow::anyhow!("Frame capture failed: {}", e))?;
+            let (luma, w, h) = decode_jpeg_luma(&raw, denom)
+                .map_err(|e| anyhow::anyhow!("Frame decode failed: {}", e))?;
+            self.frame_dims = (w, h);
+            self.frame_format = PixelFormat::Gray;
+            return Ok(luma);
+        }
 
-    /// Capture a single frame, decoded to RGB24
-    pub fn capture_frame(&mut self) -> anyhow::Result<Vec<u8>> {
         let buffer = self
             .camera
             .frame()
@@ -108,14 +424,238 @@ impl WebcamCapture {
             .decode_image::<RgbFormat>()
             .map_err(|e| anyhow::anyhow!("Frame decode failed: {}", e))?;
 
+        self.frame_dims = (image.width(), image.height());
+        self.frame_format = PixelFormat::Rgb24;
         Ok(image.into_raw())
     }
 
-    pub fn stop_stream(&mut self) {
+    fn resolution(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    fn frame_dimensions(&self) -> (u32, u32) {
+        self.frame_dims
+    }
+
+    fn pixel_format(&self) -> PixelFormat {
+        self.frame_format
+    }
+
+    fn set_detail(&mut self, detail: DecodeDetail) {
+        self.detail = detail;
+    }
+
+    fn set_control(&mut self, key: &str, value: &str) -> anyhow::Result<i32> {
+        detect::set_control(self.index, key, value).map_err(|e| anyhow::anyhow!(e))
+    }
+
+    fn stop_stream(&mut self) {
         let _ = self.camera.stop_stream();
     }
+}
 
-    pub fn resolution(&self) -> (u32, u32) {
-        (self.width, self.height)
+/// Decode only the luma of a JPEG frame, scaled down by `denom` (2, 4 or 8).
+/// libjpeg reports a bad header through mozjpeg's unwinding error handler,
+/// which aborts the release build, so frames that fail `jpeg_frame_valid` are
+/// turned into an error for the capture loop before they reach the decoder.
+/// Damage inside the entropy-coded data only raises warnings, which mozjpeg
+/// silences.
+fn decode_jpeg_luma(data: &[u8], denom: u32) -> std::io::Result<(Vec<u8>, u32, u32)> {
+    if !jpeg_frame_valid(data) {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "corrupt JPEG frame",
+        ));
+    }
+    let mut decompress = mozjpeg::Decompress::new_mem(data)?;
+    decompress.scale((8 / denom) as u8);
+    let mut started = decompress.grayscale()?;
+    let (w, h) = (started.width() as u32, started.height() as u32);
+    let luma = started.read_scanlines::<u8>()?;
+    started.finish()?;
+    Ok((luma, w, h))
+}
+
+/// Walk the marker segments up to the start of scan and check everything
+/// libjpeg would otherwise fail on: SOI first, every segment length inside
+/// the buffer, sane quantization and Huffman tables, an 8-bit baseline,
+/// extended or progressive frame header, scan components that exist in the
+/// frame, and an EOI at the end (cameras pad frames with zeros).
+fn jpeg_frame_valid(data: &[u8]) -> bool {
+    if !data.starts_with(&[0xff, 0xd8]) {
+        return false;
+    }
+    let end = data.len() - data.iter().rev().take_while(|&&b| b == 0).count();
+    if end < 4 || data[end - 2..end] != [0xff, 0xd9] {
+        return false;
+    }
+
+    let mut components: Vec<u8> = Vec::new();
+    let mut pos = 2;
+    loop {
+        if data.get(pos) != Some(&0xff) {
+            return false;
+        }
+        while data.get(pos) == Some(&0xff) {
+            pos += 1;
+        }
+        let Some(&marker) = data.get(pos) else {
+            return false;
+        };
+        pos += 1;
+        // Markers without a length: TEM, RST0-7, and a stray SOI
+        if marker == 0x01 || (0xd0..=0xd8).contains(&marker) {
+            continue;
+        }
+        if marker == 0xd9 || pos + 2 > end {
+            return false;
+        }
+        let len = u16::from_be_bytes([data[pos], data[pos + 1]]) as usize;
+        if len < 2 || pos + len > end {
+            return false;
+        }
+        let body = &data[pos + 2..pos + len];
+        match marker {
+            // Only 8-bit baseline, extended and progressive Huffman frames
+            0xc0..=0xc2 => {
+                if body.len() < 6 || body[0] != 8 {
+                    return false;
+                }
+                let height = u16::from_be_bytes([body[1], body[2]]);
+                let width = u16::from_be_bytes([body[3], body[4]]);
+                let count = body[5] as usize;
+                if height == 0 || width == 0 || count == 0 || count > 4 {
+                    return false;
+                }
+                if body.len() != 6 + 3 * count {
+                    return false;
+                }
+                components.clear();
+                for c in body[6..].chunks(3) {
+                    let (h, v) = (c[1] >> 4, c[1] & 0x0f);
+                    if !(1..=4).contains(&h) || !(1..=4).contains(&v) || c[2] > 3 {
+                        return false;
+                    }
+                    components.push(c[0]);
+                }
+            }
+            0xc3 | 0xc5..=0xc7 | 0xc9..=0xcb | 0xcd..=0xcf => return false,
+            0xc4 => {
+                let mut table = body;
+                while !table.is_empty() {
+                    if table.len() < 17 || table[0] >> 4 > 1 || table[0] & 0x0f > 3 {
+                        return false;
+                    }
+                    let symbols: usize = table[1..17].iter().map(|&n| n as usize).sum();
+                    if symbols > 256 || table.len() < 17 + symbols {
+                        return false;
+                    }
+                    table = &table[17 + symbols..];
+                }
+            }
+            0xdb => {
+                let mut table = body;
+                while !table.is_empty() {
+                    let precision = table[0] >> 4;
+                    let size = 1 + 64 * (precision as usize + 1);
+                    if precision > 1 || table[0] & 0x0f > 3 || table.len() < size {
+                        return false;
+                    }
+                    table = &table[size..];
+                }
+            }
+            0xda => {
+                let count = body.first().copied().unwrap_or(0) as usize;
+                if components.is_empty() || count == 0 || count > components.len() {
+                    return false;
+                }
+                if body.len() != 4 + 2 * count {
+                    return false;
+                }
+                return body[1..1 + 2 * count]
+                    .chunks(2)
+                    .all(|c| components.contains(&c[0]) && c[1] >> 4 <= 3 && c[1] & 0x0f <= 3);
+            }
+            _ => {}
+        }
+        pos += len;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_jpeg_scale_denom() {
+        let detail = DecodeDetail {
+            grid: (80, 40),
+            full_size: false,
+        };
+        // 1920x1080 over 80x40 cells leaves 3x3 pixels per cell at 1/8
+        assert_eq!(jpeg_scale_denom((1920, 1080), detail), 8);
+        assert_eq!(jpeg_scale_denom((640, 480), detail), 4);
+        assert_eq!(jpeg_scale_denom((200, 100), detail), 1);
+
+        let fine = DecodeDetail {
+            grid: (200, 100),
+            full_size: false,
+        };
+        assert_eq!(jpeg_scale_denom((1920, 1080), fine), 4);
+
+        let color = DecodeDetail {
+            full_size: true,
+            ..detail
+        };
+        assert_eq!(jpeg_scale_denom((1920, 1080), color), 1);
+        assert_eq!(jpeg_scale_denom((1920, 1080), DecodeDetail::default()), 1);
+    }
+
+    fn gray_jpeg(width: usize, height: usize) -> Vec<u8> {
+        let mut compress = mozjpeg::Compress::new(mozjpeg::ColorSpace::JCS_GRAYSCALE);
+        compress.set_size(width, height);
+        let mut started = compress.start_compress(Vec::new()).unwrap();
+        let pixels: Vec<u8> = (0..width * height).map(|i| (i % 251) as u8).collect();
+        started.write_scanlines(&pixels).unwrap();
+        started.finish().unwrap()
+    }
+
+    #[test]
+    fn test_decode_jpeg_luma_scales() {
+        let mut jpeg = gray_jpeg(64, 48);
+        assert!(jpeg_frame_valid(&jpeg));
+        let (luma, w, h) = decode_jpeg_luma(&jpeg, 2).unwrap();
+        assert_eq!((w, h), (32, 24));
+        assert_eq!(luma.len(), 32 * 24);
+
+        // Zero padding after EOI is how some cameras fill the buffer
+        jpeg.extend_from_slice(&[0; 16]);
+        assert!(decode_jpeg_luma(&jpeg, 4).is_ok());
+    }
+
+    #[test]
+    fn test_truncated_jpeg_is_rejected() {
+        let jpeg = gray_jpeg(64, 48);
+        for cut in [jpeg.len() / 2, 20, 2] {
+            let err = decode_jpeg_luma(&jpeg[..cut], 2).unwrap_err();
+            assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        }
+
+        // A frame header that points past the end of the buffer
+        let mut broken = jpeg.clone();
+        let sof = broken
+            .windows(2)
+            .position(|w| w[0] == 0xff && (0xc0..=0xc2).contains(&w[1]))
+            .unwrap();
+        broken[sof + 2] = 0xff;
+        assert!(!jpeg_frame_valid(&broken));
+    }
+
+    #[test]
+    fn test_corrupt_jpeg_is_an_error() {
+        // A JPEG start-of-image marker and nothing after it
+        let err = decode_jpeg_luma(&[0xff, 0xd8, 0xff, 0xe0, 0x00], 4).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert!(decode_jpeg_luma(b"not a jpeg", 2).is_err());
     }
 }
@@ -0,0 +1,169 @@
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use crate::framerate::FrameRate;
+use crate::sink::{OutputSink, RenderedFrame};
+use crate::terminal::{self, ColorDepth};
+
+/// An asciinema v2 recording of the character grid: a JSON header line, then
+/// a `[seconds, "o", text]` event for every frame that changed. The color
+/// theme and rain are written with 24-bit colours, other themes as plain text.
+pub struct CastSink {
+    path: PathBuf,
+    writer: BufWriter<File>,
+    /// Terminal size in the header, from the first frame's grid. Later grids
+    /// are cut to it.
+    size: Option<(u32, u32)>,
+    started: Instant,
+    /// Set when frames are timestamped by count rather than by the clock
+    frame_interval: Option<f64>,
+    frames: u64,
+    text: String,
+    /// Text of the last event, so a still picture isn't written again
+    last: String,
+}
+
+impl CastSink {
+    pub fn create(path: &Path) -> anyhow::Result<Self> {
+        let file = File::create(path)
+            .map_err(|e| anyhow::anyhow!("Cannot create '{}': {}", path.display(), e))?;
+        Ok(CastSink {
+            path: path.to_path_buf(),
+            writer: BufWriter::new(file),
+            size: None,
+            started: Instant::now(),
+            frame_interval: None,
+            frames: 0,
+            text: String::new(),
+            last: String::new(),
+        })
+    }
+
+    /// Timestamp frame N at N / `fps` instead of when it arrived, for frames
+    /// produced faster than real time
+    pub fn timed_by(mut self, fps: FrameRate) -> Self {
+        self.frame_interval = Some(fps.interval().as_secs_f64());
+        self
+    }
+}
+
+impl OutputSink for CastSink {
+    fn name(&self) -> String {
+        self.path.display().to_string()
+    }
+
+    fn write_frame(&mut self, frame: &RenderedFrame) -> anyhow::Result<()> {
+        let grid = &frame.grid;
+        let size = match self.size {
+            Some(size) => size,
+            None => {
+                let timestamp = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|d| d.as_secs())
+                    .unwrap_or(0);
+                writeln!(
+                    self.writer,
+                    "{{\"version\": 2, \"width\": {}, \"height\": {}, \"timestamp\": {}, \"env\": {{\"TERM\": \"xterm-256color\"}}}}",
+                    grid.columns, grid.rows, timestamp
+                )?;
+                self.started = Instant::now();
+                self.size = Some((grid.columns, grid.rows));
+                (grid.columns, grid.rows)
+            }
+        };
+
+        let depth = grid.colored.then_some(ColorDepth::TrueColor);
+        terminal::encode_grid(grid, depth, size, &mut self.text);
+        let time = match self.frame_interval {
+            Some(interval) => self.frames as f64 * interval,
+            None => self.started.elapsed().as_secs_f64(),
+        };
+        self.frames += 1;
+        if self.text != self.last {
+            let mut event = format!("[{:.6}, \"o\", ", time);
+            // The first event also hides the cursor for the whole playback
+            let hide_cursor = if self.last.is_empty() { "\x1b[?25l" } else { "" };
+            push_json_string(&format!("{}{}", hide_cursor, self.text), &mut event);
+            event.push_str("]\n");
+            self.writer
+                .write_all(event.as_bytes())
+                .and_then(|()| self.writer.flush())
+                .map_err(|e| anyhow::anyhow!("Writing to {} failed: {}", self.path.display(), e))?;
+            std::mem::swap(&mut self.text, &mut self.last);
+        }
+        self.text.clear();
+        Ok(())
+    }
+}
+
+/// Append `s` as a quoted JSON string
+fn push_json_string(s: &str, out: &mut String) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Rgb;
+    use crate::rain::CellRender;
+    use crate::renderer::CellGrid;
+
+    #[test]
+    fn test_json_string() {
+        let mut out = String::new();
+        push_json_string("a\"b\\c\r\n\x1b[Hﾊ", &mut out);
+        assert_eq!(out, r#""a\"b\\c\r\n\u001b[Hﾊ""#);
+    }
+
+    #[test]
+    fn test_cast_recording() {
+        let path = std::env::temp_dir().join(format!("virtual-ascii-{}.cast", std::process::id()));
+        let mut sink = CastSink::create(&path).unwrap();
+        let mut grid = CellGrid::blank(2, 1, Rgb { r: 0, g: 0, b: 0 });
+        let frame = |grid: &CellGrid| RenderedFrame {
+            rgb: Vec::new(),
+            width: 0,
+            height: 0,
+            grid: grid.clone(),
+        };
+
+        sink.write_frame(&frame(&grid)).unwrap();
+        // An unchanged frame adds no event
+        sink.write_frame(&frame(&grid)).unwrap();
+        grid.cells[0] = CellRender {
+            ch: '#',
+            color: Rgb { r: 255, g: 0, b: 0 },
+            intensity: 1.0,
+        };
+        grid.colored = true;
+        sink.write_frame(&frame(&grid)).unwrap();
+        drop(sink);
+
+        let data = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let lines: Vec<&str> = data.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with(r#"{"version": 2, "width": 2, "height": 1, "timestamp": "#));
+        assert!(lines[1].starts_with("[0.0"));
+        assert!(lines[1].ends_with(r#", "o", "\u001b[?25l\u001b[H\u001b[0m  \u001b[J"]"#));
+        assert!(lines[2].ends_with(
+            r#", "o", "\u001b[H\u001b[0m\u001b[48;2;0;0;0m\u001b[38;2;255;0;0m# \u001b[J"]"#
+        ));
+    }
+}
@@ -1,6 +1,16 @@
+use std::path::{Path, PathBuf};
+
 use clap::{Args, Parser, Subcommand};
 
-use crate::detect;
+use crate::capture::{CaptureFormat, SourceSpec};
+use crate::detect::{self, CameraId};
+use crate::framerate::{FrameRate, MAX_FPS};
+use crate::pattern::Pattern;
+use crate::output::{OutputIo, V4l2Options};
+use crate::pixfmt::{OutputEncoding, OutputFormat, PixelFormat, YuvMatrix};
+use crate::sink::{self, SinkSpec};
+use crate::snapshot::SnapshotFormat;
+use crate::terminal::ColorDepth;
 
 pub fn parse_resolution(s: &str) -> Result<(u32, u32), String> {
     let (w_str, h_str) = s
@@ -22,6 +32,56 @@ pub fn parse_resolution(s: &str) -> Result<(u32, u32), String> {
     Ok((w, h))
 }
 
+/// Parse a frame rate such as `30`, `29.97` or `30000/1001`, up to MAX_FPS.
+pub fn parse_fps(s: &str) -> Result<FrameRate, String> {
+    match FrameRate::parse(s) {
+        Some(fps) if fps <= MAX_FPS => Ok(fps),
+        Some(_) => Err(format!("fps '{}' is above the maximum of {}", s, MAX_FPS)),
+        None => Err(format!("invalid fps '{}': expected e.g. 30, 29.97 or 30000/1001", s)),
+    }
+}
+
+/// A recording target, which must be a `.y4m` file.
+pub fn parse_record_path(s: &str) -> Result<PathBuf, String> {
+    let path = PathBuf::from(s);
+    if sink::is_y4m_path(&path) {
+        Ok(path)
+    } else {
+        Err(format!("recordings are Y4M: '{}' needs a .y4m extension (or use --record-cast)", s))
+    }
+}
+
+/// Interpret an `--input` argument as a frame source.
+pub fn parse_input(input: &str, looping: bool) -> Result<SourceSpec, String> {
+    if let Some(name) = input.strip_prefix("pattern:") {
+        return Pattern::from_name(name).map(SourceSpec::Pattern).ok_or_else(|| {
+            format!(
+                "unknown pattern '{}': expected bars, gradient or bouncing-box",
+                name
+            )
+        });
+    }
+    let path = Path::new(input);
+    let ext = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase());
+    match ext.as_deref() {
+        Some("y4m") => Ok(SourceSpec::Y4m {
+            path: path.to_path_buf(),
+            looping,
+        }),
+        Some("png") => Ok(SourceSpec::Image {
+            path: path.to_path_buf(),
+            looping,
+        }),
+        _ => Err(format!(
+            "unsupported input '{}': expected a .y4m or .png file or pattern:<name>",
+            input
+        )),
+    }
+}
+
 #[derive(Parser, Debug)]
 #[command(
     name = "virtual-ascii",
@@ -39,16 +99,25 @@ pub struct Cli {
 #[derive(Subcommand, Debug)]
 pub enum SubCommand {
     /// Change settings on a running instance
-    Set(SetArgs),
+    Set(Box<SetArgs>),
     /// Query current settings from a running instance
     Status,
+    /// List a camera's V4L2 controls (brightness, exposure, focus, ...)
+    Controls(ControlsArgs),
+    /// Save the current frame of a running instance as text, ANSI, HTML,
+    /// SVG or PNG
+    Snapshot(SnapshotArgs),
+    /// Convert a video file, image or image sequence to ASCII art, without a
+    /// camera or loopback device
+    Convert(Box<ConvertArgs>),
     #[cfg(feature = "gui")]
     /// Launch the graphical interface
     Gui,
 }
 
+/// How the ASCII art looks, shared by the live camera and `convert`
 #[derive(Args, Debug)]
-pub struct RunArgs {
+pub struct AppearanceArgs {
     /// Definition level (1=blocky, 10=ultra-fine)
     #[arg(short, long, default_value_t = 5, value_parser = clap::value_parser!(u8).range(1..=10))]
     pub definition: u8,
@@ -57,22 +126,6 @@ pub struct RunArgs {
     #[arg(short, long, default_value = "matrix")]
     pub theme: String,
 
-    /// Target FPS
-    #[arg(short, long, default_value_t = 30, value_parser = clap::value_parser!(u32).range(1..=240))]
-    pub fps: u32,
-
-    /// Resolution WxH (e.g. 1920x1080). Auto-selects highest if omitted
-    #[arg(short = 'r', long, value_parser = parse_resolution)]
-    pub resolution: Option<(u32, u32)>,
-
-    /// Webcam device index (auto-detected if not specified)
-    #[arg(short = 'i', long)]
-    pub camera_index: Option<u32>,
-
-    /// V4L2 loopback device path
-    #[arg(short = 'o', long, default_value = "/dev/video20")]
-    pub output_device: String,
-
     /// Override foreground color (hex, e.g. ff00ff)
     #[arg(long)]
     pub fg_color: Option<String>,
@@ -90,6 +143,103 @@ pub struct RunArgs {
     pub invert: bool,
 }
 
+#[derive(Args, Debug)]
+pub struct RunArgs {
+    #[command(flatten)]
+    pub appearance: AppearanceArgs,
+
+    /// Target FPS (30, 29.97 or 30000/1001)
+    #[arg(short, long, default_value = "30", value_parser = parse_fps)]
+    pub fps: FrameRate,
+
+    /// Resolution WxH (e.g. 1920x1080). Auto-selects highest if omitted
+    #[arg(short = 'r', long, value_parser = parse_resolution)]
+    pub resolution: Option<(u32, u32)>,
+
+    /// Webcam device index (auto-detected if not specified)
+    #[arg(short = 'i', long)]
+    pub camera_index: Option<u32>,
+
+    /// Webcam by name substring, bus_info or /dev/v4l/by-id path. Found again
+    /// after a replug even if its /dev/videoN changes
+    #[arg(long, conflicts_with = "camera_index")]
+    pub camera: Option<String>,
+
+    /// Webcam pixel format (mjpeg, yuyv, nv12). Raw formats skip JPEG decoding
+    #[arg(long, default_value = "mjpeg", conflicts_with = "input")]
+    pub capture_format: String,
+
+    /// Read frames from a file, test pattern or stdin instead of a webcam
+    /// (.y4m, .png, frames/%05d.png, pattern:bars, pattern:gradient, pattern:bouncing-box, - for raw video on stdin)
+    #[arg(long, conflicts_with_all = ["camera_index", "camera"])]
+    pub input: Option<String>,
+
+    /// Pixel format of raw video on stdin (rgb24, yuyv, nv12, gray)
+    #[arg(long, default_value = "rgb24")]
+    pub input_format: String,
+
+    /// Frame size WxH of raw video on stdin
+    #[arg(long, value_parser = parse_resolution)]
+    pub input_size: Option<(u32, u32)>,
+
+    /// Restart file inputs and image sequences from the beginning when they end
+    #[arg(long = "loop", default_value_t = false)]
+    pub loop_input: bool,
+
+    /// V4L2 loopback device path, or - to write frames to stdout
+    #[arg(short = 'o', long, visible_alias = "output", default_value = "/dev/video20")]
+    pub output_device: String,
+
+    /// Output frame size WxH (e.g. 1280x720). Defaults to the camera resolution
+    #[arg(long, value_parser = parse_resolution)]
+    pub output_size: Option<(u32, u32)>,
+
+    /// How the camera image fills an output of a different shape (fit, fill, stretch)
+    #[arg(long, default_value = "fill")]
+    pub scale_mode: String,
+
+    /// Pixel format written to loopback devices and stdout (rgb24, bgr24, yuyv,
+    /// i420/yuv420p, nv12), or y4m for a YUV4MPEG2 stream on stdout
+    #[arg(long, default_value = "rgb24")]
+    pub output_format: String,
+
+    /// YUV matrix for YUV output formats (bt601, bt709)
+    #[arg(long, default_value = "bt601")]
+    pub output_matrix: String,
+
+    /// Sample range for YUV output formats (limited, full)
+    #[arg(long, default_value = "limited")]
+    pub output_range: String,
+
+    /// How frames are handed to loopback devices (write, mmap). mmap falls
+    /// back to write if the driver rejects streaming I/O
+    #[arg(long, default_value = "write")]
+    pub output_io: String,
+
+    /// Also send frames to another loopback device, a file of raw RGB24 frames,
+    /// a command's stdin with pipe:<command>, or stdout with - (repeatable)
+    #[arg(long, value_name = "OUTPUT", value_parser = SinkSpec::parse)]
+    pub add_output: Vec<SinkSpec>,
+
+    /// Record the rendered output to a .y4m file, alongside the virtual camera
+    #[arg(long, value_name = "PATH", value_parser = parse_record_path)]
+    pub record: Option<PathBuf>,
+
+    /// Record the character grid to an asciinema v2 file, with colours for
+    /// the color and rain themes
+    #[arg(long, value_name = "PATH", conflicts_with = "record")]
+    pub record_cast: Option<PathBuf>,
+
+    /// Draw the ASCII art in this terminal instead of a loopback device,
+    /// with as many columns and rows as the window has
+    #[arg(long, default_value_t = false, conflicts_with = "output_size")]
+    pub terminal: bool,
+
+    /// Colours for --terminal (auto, truecolor, 256, 16). auto goes by $COLORTERM and $TERM
+    #[arg(long, default_value = "auto")]
+    pub terminal_colors: String,
+}
+
 #[derive(Args, Debug)]
 pub struct SetArgs {
     /// Definition level (1=blocky, 10=ultra-fine)
@@ -100,9 +250,9 @@ pub struct SetArgs {
     #[arg(short, long)]
     pub theme: Option<String>,
 
-    /// Target FPS
-    #[arg(short, long, value_parser = clap::value_parser!(u32).range(1..=240))]
-    pub fps: Option<u32>,
+    /// Target FPS (30, 29.97 or 30000/1001)
+    #[arg(short, long, value_parser = parse_fps)]
+    pub fps: Option<FrameRate>,
 
     /// Resolution WxH (e.g. 1920x1080)
     #[arg(short = 'r', long, value_parser = parse_resolution)]
@@ -112,6 +262,18 @@ pub struct SetArgs {
     #[arg(short = 'i', long)]
     pub camera_index: Option<u32>,
 
+    /// Webcam by name substring, bus_info or /dev/v4l/by-id path
+    #[arg(long, conflicts_with = "camera_index")]
+    pub camera: Option<String>,
+
+    /// Webcam pixel format (mjpeg, yuyv, nv12)
+    #[arg(long, conflicts_with = "input")]
+    pub capture_format: Option<String>,
+
+    /// Switch to a file or test pattern input (.y4m, .png, frames/%05d.png, pattern:<name>)
+    #[arg(long, conflicts_with_all = ["camera_index", "camera"])]
+    pub input: Option<String>,
+
     /// Override foreground color (hex, e.g. ff00ff)
     #[arg(long)]
     pub fg_color: Option<String>,
@@ -127,6 +289,99 @@ pub struct SetArgs {
     /// Invert brightness mapping
     #[arg(long)]
     pub invert: Option<bool>,
+
+    /// How the camera image fills the output (fit, fill, stretch)
+    #[arg(long)]
+    pub scale_mode: Option<String>,
+
+    /// Set a camera control, e.g. --ctrl exposure_time_absolute=200 (repeatable)
+    #[arg(long = "ctrl", value_name = "NAME=VALUE")]
+    pub ctrl: Vec<String>,
+
+    /// Attach another output: /dev/videoN, a file, or pipe:<command> (repeatable)
+    #[arg(long, value_name = "OUTPUT")]
+    pub add_output: Vec<String>,
+
+    /// Detach an output by the name shown in `status` (repeatable)
+    #[arg(long, value_name = "NAME")]
+    pub remove_output: Vec<String>,
+
+    /// Start recording the rendered output to a .y4m file
+    #[arg(long, value_name = "PATH", value_parser = parse_record_path)]
+    pub record: Option<PathBuf>,
+
+    /// Start recording the character grid to an asciinema v2 file
+    #[arg(long, value_name = "PATH", conflicts_with = "record")]
+    pub record_cast: Option<PathBuf>,
+
+    /// Stop the running recording
+    #[arg(long, default_value_t = false)]
+    pub stop_recording: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct SnapshotArgs {
+    /// File to write
+    pub path: PathBuf,
+
+    /// txt, ansi, html, svg or png (default: from the file extension)
+    #[arg(short, long, value_parser = parse_snapshot_format)]
+    pub format: Option<SnapshotFormat>,
+}
+
+fn parse_snapshot_format(s: &str) -> Result<SnapshotFormat, String> {
+    SnapshotFormat::from_name(s)
+        .ok_or_else(|| format!("unknown snapshot format '{}': expected txt, ansi, html, svg or png", s))
+}
+
+#[derive(Args, Debug)]
+pub struct ConvertArgs {
+    /// .y4m file, .png image, image sequence (frames/%05d.png), raw video
+    /// file (.rgb, .raw) or - for raw video on stdin
+    pub input: String,
+
+    /// .y4m or raw RGB24 file, .cast, pipe:<command>, - for stdout, or a
+    /// .png/.txt/.ans/.html/.svg image (frames/%05d.png for one per frame)
+    #[arg(short, long)]
+    pub output: String,
+
+    #[command(flatten)]
+    pub appearance: AppearanceArgs,
+
+    /// Frame rate of image sequences and raw video. Y4M files use their own
+    #[arg(short, long, default_value = "30", value_parser = parse_fps)]
+    pub fps: FrameRate,
+
+    /// Pixel format of raw video (rgb24, yuyv, nv12, gray)
+    #[arg(long, default_value = "rgb24")]
+    pub input_format: String,
+
+    /// Frame size WxH of raw video
+    #[arg(long, value_parser = parse_resolution)]
+    pub input_size: Option<(u32, u32)>,
+
+    /// Output frame size WxH. Defaults to the input's
+    #[arg(long, value_parser = parse_resolution)]
+    pub output_size: Option<(u32, u32)>,
+
+    /// How the input fills an output of a different shape (fit, fill, stretch)
+    #[arg(long, default_value = "fill")]
+    pub scale_mode: String,
+
+    /// Seed for the rain themes, so a file converts the same way every time
+    #[arg(long, default_value_t = 1)]
+    pub seed: u64,
+}
+
+#[derive(Args, Debug)]
+pub struct ControlsArgs {
+    /// Webcam device index (auto-detected if not specified)
+    #[arg(short = 'i', long)]
+    pub camera_index: Option<u32>,
+
+    /// Webcam by name substring, bus_info or /dev/v4l/by-id path
+    #[arg(long, conflicts_with = "camera_index")]
+    pub camera: Option<String>,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -264,6 +519,37 @@ impl BrightnessCurve {
     }
 }
 
+/// How the source frame is mapped onto the output when their aspect ratios differ
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ScaleMode {
+    /// Show the whole source, leaving blank bars on two sides
+    Fit,
+    /// Cover the whole output, cropping the source
+    #[default]
+    Fill,
+    /// Cover the whole output with the whole source, distorting it
+    Stretch,
+}
+
+impl ScaleMode {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "fit" => Some(Self::Fit),
+            "fill" | "crop" => Some(Self::Fill),
+            "stretch" => Some(Self::Stretch),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Fit => "fit",
+            Self::Fill => "fill",
+            Self::Stretch => "stretch",
+        }
+    }
+}
+
 /// Movie-authentic matrix character set: half-width katakana + numerals + symbols
 pub fn matrix_charset() -> Vec<char> {
     let mut chars = Vec::new();
@@ -326,22 +612,19 @@ pub fn definition_to_params(level: u8, theme_name: &str) -> (u32, Vec<char>) {
     (columns, charset_str.chars().collect())
 }
 
+/// Theme, charset and brightness mapping picked by `AppearanceArgs`
 #[derive(Debug)]
-pub struct AppConfig {
+pub struct Appearance {
     pub theme: ColorTheme,
     pub definition: u8,
     pub ascii_columns: u32,
     pub charset: Vec<char>,
     pub brightness_curve: BrightnessCurve,
     pub invert: bool,
-    pub fps: u32,
-    pub camera_index: u32,
-    pub resolution: Option<(u32, u32)>,
-    pub output_device: String,
 }
 
-impl AppConfig {
-    pub fn from_cli(args: RunArgs) -> anyhow::Result<Self> {
+impl Appearance {
+    pub fn from_args(args: &AppearanceArgs) -> anyhow::Result<Self> {
         let mut theme = ColorTheme::from_name(&args.theme).ok_or_else(|| {
             anyhow::anyhow!(
                 "Unknown theme '{}'. Available: mono, green, amber, blue, matrix, vaporwave, fire, color",
@@ -376,31 +659,186 @@ impl AppConfig {
 
         let (ascii_columns, charset) = definition_to_params(args.definition, &args.theme);
 
-        let camera_index = match args.camera_index {
-            Some(i) => i,
-            None => {
-                if let Some(i) = detect::detect_camera(&args.output_device) {
-                    let name = detect::device_name(i).unwrap_or_default();
-                    eprintln!("Auto-detected camera: /dev/video{} ({})", i, name);
-                    i
-                } else {
-                    eprintln!("Warning: no camera auto-detected, falling back to index 0");
-                    0
-                }
-            }
-        };
-
-        Ok(AppConfig {
+        Ok(Appearance {
             theme,
             definition: args.definition,
             ascii_columns,
             charset,
             brightness_curve,
             invert: args.invert,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct AppConfig {
+    pub theme: ColorTheme,
+    pub definition: u8,
+    pub ascii_columns: u32,
+    pub charset: Vec<char>,
+    pub brightness_curve: BrightnessCurve,
+    pub invert: bool,
+    pub fps: FrameRate,
+    pub source: SourceSpec,
+    pub resolution: Option<(u32, u32)>,
+    pub output_device: String,
+    /// Write a YUV4MPEG2 stream rather than bare frames to stdout
+    pub output_y4m: bool,
+    pub output_size: Option<(u32, u32)>,
+    pub scale_mode: ScaleMode,
+    pub output_options: V4l2Options,
+    pub extra_outputs: Vec<SinkSpec>,
+    /// A Y4M file or an asciicast
+    pub record: Option<SinkSpec>,
+    /// Draw in the terminal, with this many colours, instead of opening `output_device`
+    pub terminal: Option<ColorDepth>,
+}
+
+impl AppConfig {
+    pub fn from_cli(args: RunArgs) -> anyhow::Result<Self> {
+        let appearance = Appearance::from_args(&args.appearance)?;
+
+        let scale_mode = ScaleMode::from_name(&args.scale_mode).ok_or_else(|| {
+            anyhow::anyhow!(
+                "Unknown scale mode '{}'. Available: fit, fill, stretch",
+                args.scale_mode
+            )
+        })?;
+
+        // Y4M is I420 frames behind a stream header, so only stdout can take it
+        let output_y4m = args.output_format == "y4m";
+        let writes_stdout =
+            args.output_device == "-" || args.add_output.iter().any(|o| matches!(o, SinkSpec::Stdout { .. }));
+        if output_y4m && !writes_stdout {
+            return Err(anyhow::anyhow!(
+                "--output-format y4m is a stream format for stdout. Use it with --output -"
+            ));
+        }
+        let output_format = if output_y4m { "i420" } else { args.output_format.as_str() };
+
+        let terminal = if args.terminal {
+            if writes_stdout {
+                return Err(anyhow::anyhow!(
+                    "--terminal draws on stdout, so frames can't go to stdout as well"
+                ));
+            }
+            Some(match args.terminal_colors.as_str() {
+                "auto" => ColorDepth::detect(),
+                name => ColorDepth::from_name(name).ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "Unknown terminal colors '{}'. Available: auto, truecolor, 256, 16",
+                        name
+                    )
+                })?,
+            })
+        } else {
+            None
+        };
+
+        let output_encoding = OutputEncoding {
+            format: OutputFormat::from_name(output_format).ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Unknown output format '{}'. Available: rgb24, bgr24, yuyv, i420, nv12, y4m (stdout only)",
+                    args.output_format
+                )
+            })?,
+            matrix: YuvMatrix::from_name(&args.output_matrix).ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Unknown YUV matrix '{}'. Available: bt601, bt709",
+                    args.output_matrix
+                )
+            })?,
+            full_range: match args.output_range.as_str() {
+                "limited" | "tv" => false,
+                "full" | "pc" => true,
+                other => {
+                    return Err(anyhow::anyhow!(
+                        "Unknown output range '{}'. Available: limited, full",
+                        other
+                    ))
+                }
+            },
+        };
+
+        let source = if let Some("-" | "pipe:") = args.input.as_deref() {
+            let format = PixelFormat::from_name(&args.input_format).ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Unknown input format '{}'. Available: rgb24, yuyv, nv12, gray",
+                    args.input_format
+                )
+            })?;
+            let size = args.input_size.ok_or_else(|| {
+                anyhow::anyhow!("Raw video on stdin needs its frame size: --input-size WxH")
+            })?;
+            SourceSpec::Stdin { format, size }
+        } else if let Some(ref input) = args.input {
+            parse_input(input, args.loop_input).map_err(|e| anyhow::anyhow!(e))?
+        } else {
+            let format = CaptureFormat::from_name(&args.capture_format).ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Unknown capture format '{}'. Available: mjpeg, yuyv, nv12",
+                    args.capture_format
+                )
+            })?;
+            let (id, index) = match (args.camera_index, &args.camera) {
+                (Some(i), _) => (CameraId::Index(i), i),
+                (None, Some(camera)) => {
+                    let id = CameraId::parse(camera);
+                    let index = detect::resolve_camera(&id)
+                        .ok_or_else(|| anyhow::anyhow!("No camera matching '{}' found", camera))?;
+                    (id, index)
+                }
+                (None, None) => {
+                    let i = if let Some(i) = detect::detect_camera(&args.output_device) {
+                        let name = detect::device_name(i).unwrap_or_default();
+                        eprintln!("Auto-detected camera: /dev/video{} ({})", i, name);
+                        i
+                    } else {
+                        eprintln!("Warning: no camera auto-detected, falling back to index 0");
+                        0
+                    };
+                    (CameraId::Index(i), i)
+                }
+            };
+            SourceSpec::Webcam { id, index, format }
+        };
+
+        Ok(AppConfig {
+            theme: appearance.theme,
+            definition: appearance.definition,
+            ascii_columns: appearance.ascii_columns,
+            charset: appearance.charset,
+            brightness_curve: appearance.brightness_curve,
+            invert: appearance.invert,
             fps: args.fps,
-            camera_index,
+            source,
             resolution: args.resolution,
             output_device: args.output_device,
+            output_y4m,
+            output_size: args.output_size,
+            scale_mode,
+            output_options: V4l2Options {
+                encoding: output_encoding,
+                io: OutputIo::from_name(&args.output_io).ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "Unknown output I/O method '{}'. Available: write, mmap",
+                        args.output_io
+                    )
+                })?,
+            },
+            extra_outputs: args
+                .add_output
+                .into_iter()
+                .map(|spec| match spec {
+                    SinkSpec::Stdout { .. } => SinkSpec::Stdout { y4m: output_y4m },
+                    spec => spec,
+                })
+                .collect(),
+            record: args
+                .record
+                .map(SinkSpec::File)
+                .or_else(|| args.record_cast.map(SinkSpec::Cast)),
+            terminal,
         })
     }
 }
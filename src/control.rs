@@ -1,16 +1,23 @@
 use std::io::{BufRead, BufReader, Write};
 use std::os::unix::io::{AsRawFd, FromRawFd};
 use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
 use crossbeam_channel::Sender;
 
+use crate::capture::{CaptureFormat, SourceSpec};
 use crate::config::{
-    definition_to_params, parse_hex_color, parse_resolution, BrightnessCurve, ColorTheme, Rgb,
+    definition_to_params, parse_hex_color, parse_input, parse_resolution, BrightnessCurve,
+    ColorTheme, Rgb, ScaleMode,
 };
-use crate::detect;
+use crate::detect::{self, CameraId};
+use crate::framerate::{FrameRate, MAX_FPS};
+use crate::output::V4l2Options;
+use crate::sink::{self, SinkSet, SinkSpec};
+use crate::snapshot::SnapshotFormat;
 
 // --- Command types ---
 
@@ -21,11 +28,15 @@ pub struct CaptureCommand {
 
 pub enum CaptureAction {
     ChangeCamera {
-        index: u32,
+        source: SourceSpec,
         resolution: Option<(u32, u32)>,
     },
     ChangeFps {
-        fps: u32,
+        fps: FrameRate,
+    },
+    /// Set V4L2 controls on the open camera, as (key, value) pairs
+    SetControls {
+        controls: Vec<(String, String)>,
     },
 }
 
@@ -42,29 +53,69 @@ pub enum RenderAction {
         bg: Rgb,
         brightness_curve: BrightnessCurve,
         invert: bool,
+        scale_mode: ScaleMode,
         theme_name: String,
     },
+    /// Open an output and start feeding it frames
+    AttachSink {
+        spec: SinkSpec,
+    },
+    /// Stop the output with this name
+    DetachSink {
+        name: String,
+    },
+    /// Resize a renderer whose grid was fixed with `AsciiRenderer::with_grid`,
+    /// e.g. to follow a terminal window
+    FitGrid {
+        columns: u32,
+        rows: u32,
+    },
+    /// Write the last rendered frame to a file
+    Snapshot {
+        format: SnapshotFormat,
+        path: PathBuf,
+    },
 }
 
 // --- Runtime state ---
 
 pub struct RuntimeState {
-    pub camera_index: u32,
+    pub source: SourceSpec,
     pub resolution: Option<(u32, u32)>,
-    pub fps: u32,
-    pub max_fps: u32,
+    pub fps: FrameRate,
+    pub max_fps: FrameRate,
     pub theme_name: String,
     pub fg: Rgb,
     pub bg: Rgb,
     pub definition: u8,
     pub brightness_curve: BrightnessCurve,
     pub invert: bool,
+    pub scale_mode: ScaleMode,
+    /// Camera controls changed over the socket, with the values the device applied
+    pub controls: Vec<(String, i32)>,
+    /// Names of the attached outputs
+    pub outputs: Vec<String>,
+    /// Pixel format, colorimetry and I/O method of the main loopback device
+    pub output_options: V4l2Options,
+    /// Output name of the recording started with `--record`, `--record-cast`
+    /// or `RECORD START`/`RECORD CAST`
+    pub recording: Option<String>,
+    /// The file that recording writes to
+    pub recording_path: Option<PathBuf>,
 }
 
 impl RuntimeState {
-    pub fn format_status(&self) -> String {
+    /// `sinks` supplies what the outputs report: the rate the loopback device
+    /// settled on, the current frame size, and whether the recording is running.
+    pub fn format_status(&self, sinks: &SinkSet) -> String {
         let mut out = String::new();
-        out.push_str(&format!("camera_index={}\n", self.camera_index));
+        out.push_str(&format!("{}\n", self.source.as_setting()));
+        if let SourceSpec::Webcam { ref id, format, .. } = self.source {
+            if !matches!(id, CameraId::Index(_)) {
+                out.push_str(&format!("camera={}\n", id));
+            }
+            out.push_str(&format!("capture_format={}\n", format.name()));
+        }
         if let Some((w, h)) = self.resolution {
             out.push_str(&format!("resolution={}x{}\n", w, h));
         } else {
@@ -77,6 +128,34 @@ impl RuntimeState {
         out.push_str(&format!("definition={}\n", self.definition));
         out.push_str(&format!("brightness_curve={}\n", self.brightness_curve.name()));
         out.push_str(&format!("invert={}\n", self.invert));
+        out.push_str(&format!("scale_mode={}\n", self.scale_mode.name()));
+        for (key, value) in &self.controls {
+            out.push_str(&format!("ctrl.{}={}\n", key, value));
+        }
+        let (out_w, out_h) = sinks.frame_size();
+        out.push_str(&format!("output_size={}x{}\n", out_w, out_h));
+        let encoding = self.output_options.encoding;
+        out.push_str(&format!("output_format={}\n", encoding.format.name()));
+        if encoding.format.is_yuv() {
+            out.push_str(&format!("output_matrix={}\n", encoding.matrix.name()));
+            let range = if encoding.full_range { "full" } else { "limited" };
+            out.push_str(&format!("output_range={}\n", range));
+        }
+        out.push_str(&format!("output_io={}\n", self.output_options.io.name()));
+        if let Some(fps) = sinks.device_frame_rate() {
+            out.push_str(&format!("output_fps={}\n", fps));
+        }
+        for output in &self.outputs {
+            out.push_str(&format!("output={}\n", output));
+        }
+        match (&self.recording, &self.recording_path) {
+            (Some(name), Some(path)) if sinks.contains(name) => {
+                out.push_str(&format!("recording={}\n", name));
+                let size = std::fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+                out.push_str(&format!("recording_size={}\n", size));
+            }
+            _ => out.push_str("recording=off\n"),
+        }
         out.push_str("END\n");
         out
     }
@@ -164,6 +243,7 @@ pub fn start_listener(
     state: Arc<Mutex<RuntimeState>>,
     capture_cmd_tx: Sender<CaptureCommand>,
     render_cmd_tx: Sender<RenderCommand>,
+    sinks: Arc<Mutex<SinkSet>>,
     shutdown: Arc<AtomicBool>,
 ) -> std::io::Result<std::thread::JoinHandle<()>> {
     let listener = bind_abstract_listener()?;
@@ -203,6 +283,7 @@ pub fn start_listener(
                             &state,
                             &capture_cmd_tx,
                             &render_cmd_tx,
+                            &sinks,
                         );
                     }
                     Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => {
@@ -232,6 +313,7 @@ fn handle_connection(
     state: &Arc<Mutex<RuntimeState>>,
     capture_cmd_tx: &Sender<CaptureCommand>,
     render_cmd_tx: &Sender<RenderCommand>,
+    sinks: &Arc<Mutex<SinkSet>>,
 ) {
     let mut reader = BufReader::new(&stream);
     let mut writer = &stream;
@@ -267,8 +349,9 @@ fn handle_connection(
 
     // Check for STATUS command
     if commands.iter().any(|c| c.eq_ignore_ascii_case("STATUS")) {
+        let sinks = sinks.lock().unwrap_or_else(|e| e.into_inner());
         let st = state.lock().unwrap_or_else(|e| e.into_inner());
-        let status = st.format_status();
+        let status = st.format_status(&sinks);
         let _ = writer.write_all(status.as_bytes());
         return;
     }
@@ -280,6 +363,18 @@ fn handle_connection(
 
     for cmd in &commands {
         let upper = cmd.to_uppercase();
+        if upper.starts_with("OUTPUT ") {
+            responses.push(handle_output_command(&cmd[7..], state, render_cmd_tx));
+            continue;
+        }
+        if upper.starts_with("RECORD ") {
+            responses.push(handle_record_command(&cmd[7..], state, render_cmd_tx, sinks));
+            continue;
+        }
+        if upper.starts_with("SNAPSHOT ") {
+            responses.push(handle_snapshot_command(&cmd[9..], render_cmd_tx));
+            continue;
+        }
         if !upper.starts_with("SET ") {
             responses.push(format!("ERR unknown command: {}\n", cmd));
             continue;
@@ -296,14 +391,51 @@ fn handle_connection(
 
         let current_max_fps = state.lock().unwrap_or_else(|e| e.into_inner()).max_fps;
 
+        if let Some(ctrl) = key.strip_prefix("ctrl.") {
+            if ctrl.is_empty() {
+                responses.push(format!("ERR missing control name: {}\n", key));
+            } else {
+                capture_changes.controls.push((ctrl.to_string(), value));
+            }
+            continue;
+        }
+
         match key.as_str() {
             "camera_index" => match value.parse::<u32>() {
-                Ok(i) => capture_changes.camera_index = Some(i),
+                Ok(i) => capture_changes.camera = Some((CameraId::Index(i), i)),
                 Err(_) => {
                     responses.push(format!("ERR invalid camera_index: {}\n", value));
                     continue;
                 }
             },
+            "camera" => {
+                let id = CameraId::parse(&value);
+                match detect::resolve_camera(&id) {
+                    Some(i) => capture_changes.camera = Some((id, i)),
+                    None => {
+                        responses.push(format!("ERR no camera matching '{}'\n", value));
+                        continue;
+                    }
+                }
+            }
+            "capture_format" => match CaptureFormat::from_name(&value) {
+                Some(f) => capture_changes.capture_format = Some(f),
+                None => {
+                    responses.push(format!(
+                        "ERR unknown capture_format '{}'. Available: mjpeg, yuyv, nv12\n",
+                        value
+                    ));
+                    continue;
+                }
+            },
+            // Sources swapped in live always loop, so the stream isn't torn down when they end
+            "input" => match parse_input(&value, true) {
+                Ok(source) => capture_changes.source = Some(source),
+                Err(e) => {
+                    responses.push(format!("ERR {}\n", e));
+                    continue;
+                }
+            },
             "resolution" => match parse_resolution(&value) {
                 Ok(res) => capture_changes.resolution = Some(Some(res)),
                 Err(e) => {
@@ -311,11 +443,11 @@ fn handle_connection(
                     continue;
                 }
             },
-            "fps" => match value.parse::<u32>() {
-                Ok(f) if (1..=current_max_fps).contains(&f) => capture_changes.fps = Some(f),
+            "fps" => match FrameRate::parse(&value) {
+                Some(f) if f <= current_max_fps => capture_changes.fps = Some(f),
                 _ => {
                     responses.push(format!(
-                        "ERR invalid fps: {} (must be 1-{})\n",
+                        "ERR invalid fps: {} (must be above 0 and at most {})\n",
                         value, current_max_fps
                     ));
                     continue;
@@ -386,6 +518,16 @@ fn handle_connection(
                     continue;
                 }
             },
+            "scale_mode" => match ScaleMode::from_name(&value) {
+                Some(m) => render_changes.scale_mode = Some(m),
+                None => {
+                    responses.push(format!(
+                        "ERR unknown scale_mode '{}'. Available: fit, fill, stretch\n",
+                        value
+                    ));
+                    continue;
+                }
+            },
             _ => {
                 responses.push(format!("ERR unknown key: {}\n", key));
                 continue;
@@ -397,7 +539,7 @@ fn handle_connection(
     let snapshot = {
         let st = state.lock().unwrap_or_else(|e| e.into_inner());
         StateSnapshot {
-            camera_index: st.camera_index,
+            source: st.source.clone(),
             resolution: st.resolution,
             theme_name: st.theme_name.clone(),
             fg: st.fg,
@@ -405,24 +547,25 @@ fn handle_connection(
             definition: st.definition,
             brightness_curve: st.brightness_curve,
             invert: st.invert,
+            scale_mode: st.scale_mode,
         }
     };
 
     // Route capture changes
     if capture_changes.has_changes() {
-        let cam_idx = capture_changes.camera_index.unwrap_or(snapshot.camera_index);
+        let source = capture_changes.resolve_source(snapshot.source);
         let resolution = capture_changes.resolution.unwrap_or(snapshot.resolution);
         let fps = capture_changes.fps;
 
         // Camera/resolution change requires ChangeCamera
-        let needs_camera_change =
-            capture_changes.camera_index.is_some() || capture_changes.resolution.is_some();
+        let needs_camera_change = capture_changes.changes_source()
+            || capture_changes.resolution.is_some();
 
         if needs_camera_change {
             let (resp_tx, resp_rx) = crossbeam_channel::bounded(1);
             let cmd = CaptureCommand {
                 action: CaptureAction::ChangeCamera {
-                    index: cam_idx,
+                    source: source.clone(),
                     resolution,
                 },
                 response_tx: resp_tx,
@@ -432,22 +575,26 @@ fn handle_connection(
                     Ok(Ok(msg)) => {
                         responses.push(format!("OK {}\n", msg));
                         let mut st = state.lock().unwrap_or_else(|e| e.into_inner());
-                        st.camera_index = cam_idx;
-                        st.resolution = resolution;
                         // Refresh max_fps for the new camera/resolution
-                        let new_max = if let Some((w, h)) = resolution {
-                            detect::max_fps_for_resolution(cam_idx, w, h)
-                                .unwrap_or(240)
-                        } else {
+                        let new_max = match (&source, resolution) {
+                            (SourceSpec::Webcam { index, format, .. }, Some((w, h))) => {
+                                detect::max_fps_for_resolution(*index, *format, w, h)
+                                    .unwrap_or(MAX_FPS)
+                            }
                             // Auto mode: max across all resolutions
-                            detect::list_resolutions(cam_idx)
-                                .iter()
-                                .filter_map(|(w, h)| {
-                                    detect::max_fps_for_resolution(cam_idx, *w, *h)
-                                })
-                                .max()
-                                .unwrap_or(240)
+                            (SourceSpec::Webcam { index, format, .. }, None) => {
+                                detect::list_resolutions(*index, *format)
+                                    .iter()
+                                    .filter_map(|(w, h)| {
+                                        detect::max_fps_for_resolution(*index, *format, *w, *h)
+                                    })
+                                    .max()
+                                    .unwrap_or(MAX_FPS)
+                            }
+                            _ => MAX_FPS,
                         };
+                        st.source = source;
+                        st.resolution = resolution;
                         st.max_fps = new_max;
                         if st.fps > st.max_fps {
                             st.fps = st.max_fps;
@@ -481,6 +628,39 @@ fn handle_connection(
                 responses.push("ERR pipeline shutting down\n".to_string());
             }
         }
+
+        // Controls go last so they land on the camera selected by this batch
+        if !capture_changes.controls.is_empty() {
+            let (resp_tx, resp_rx) = crossbeam_channel::bounded(1);
+            let cmd = CaptureCommand {
+                action: CaptureAction::SetControls {
+                    controls: capture_changes.controls.clone(),
+                },
+                response_tx: resp_tx,
+            };
+            if capture_cmd_tx.send(cmd).is_ok() {
+                match resp_rx.recv_timeout(Duration::from_secs(5)) {
+                    Ok(Ok(msg)) => {
+                        responses.push(format!("OK {}\n", msg));
+                        let mut st = state.lock().unwrap_or_else(|e| e.into_inner());
+                        for applied in msg.split_whitespace() {
+                            let parsed = applied
+                                .strip_prefix("ctrl.")
+                                .and_then(|kv| kv.split_once('='))
+                                .and_then(|(k, v)| Some((k.to_string(), v.parse().ok()?)));
+                            if let Some((k, v)) = parsed {
+                                st.controls.retain(|(key, _)| *key != k);
+                                st.controls.push((k, v));
+                            }
+                        }
+                    }
+                    Ok(Err(msg)) => responses.push(format!("ERR {}\n", msg)),
+                    Err(_) => responses.push("ERR control change timed out\n".to_string()),
+                }
+            } else {
+                responses.push("ERR pipeline shutting down\n".to_string());
+            }
+        }
     }
 
     // Route render changes
@@ -495,6 +675,7 @@ fn handle_connection(
             .brightness_curve
             .unwrap_or(snapshot.brightness_curve);
         let invert = render_changes.invert.unwrap_or(snapshot.invert);
+        let scale_mode = render_changes.scale_mode.unwrap_or(snapshot.scale_mode);
 
         let (ascii_columns, charset) = definition_to_params(definition, &theme_name);
 
@@ -507,6 +688,7 @@ fn handle_connection(
                 bg,
                 brightness_curve,
                 invert,
+                scale_mode,
                 theme_name: theme_name.clone(),
             },
             response_tx: resp_tx,
@@ -522,6 +704,7 @@ fn handle_connection(
                     st.definition = definition;
                     st.brightness_curve = brightness_curve;
                     st.invert = invert;
+                    st.scale_mode = scale_mode;
                 }
                 Ok(Err(msg)) => responses.push(format!("ERR {}\n", msg)),
                 Err(_) => responses.push("ERR render rebuild timed out\n".to_string()),
@@ -537,18 +720,217 @@ fn handle_connection(
     }
 }
 
+/// `OUTPUT ADD <output>` / `OUTPUT REMOVE <name>`: attach or detach a sink.
+/// Handled right away rather than batched with SET, since sinks don't depend
+/// on the other settings.
+fn handle_output_command(
+    args: &str,
+    state: &Arc<Mutex<RuntimeState>>,
+    render_cmd_tx: &Sender<RenderCommand>,
+) -> String {
+    let args = args.trim();
+    let (verb, arg) = match args.split_once(' ') {
+        Some((verb, arg)) => (verb, arg.trim()),
+        None => (args, ""),
+    };
+    let action = match verb.to_uppercase().as_str() {
+        "ADD" => match SinkSpec::parse(arg) {
+            Ok(spec) => {
+                if let Some(err) = spec.path().and_then(|path| relative_path_error("output", path)) {
+                    return err;
+                }
+                RenderAction::AttachSink { spec }
+            }
+            Err(e) => return format!("ERR {}\n", e),
+        },
+        "REMOVE" if !arg.is_empty() => RenderAction::DetachSink {
+            name: arg.to_string(),
+        },
+        _ => return "ERR usage: OUTPUT ADD <output> | OUTPUT REMOVE <name>\n".to_string(),
+    };
+
+    match send_sink_action(render_cmd_tx, action) {
+        Ok(msg) => {
+            let mut st = state.lock().unwrap_or_else(|e| e.into_inner());
+            if let Some(name) = msg.strip_prefix("output added ") {
+                st.outputs.push(name.to_string());
+            } else {
+                st.outputs.retain(|o| o != arg);
+            }
+            format!("OK {}\n", msg)
+        }
+        Err(msg) => format!("ERR {}\n", msg),
+    }
+}
+
+/// `RECORD START <path.y4m|path.cast>` / `RECORD CAST <path>` /
+/// `RECORD STOP`: one Y4M or asciicast recording of the rendered output,
+/// attached like any other output so it keeps going while the virtual camera
+/// is in use.
+fn handle_record_command(
+    args: &str,
+    state: &Arc<Mutex<RuntimeState>>,
+    render_cmd_tx: &Sender<RenderCommand>,
+    sinks: &Arc<Mutex<SinkSet>>,
+) -> String {
+    let args = args.trim();
+    let (verb, arg) = match args.split_once(' ') {
+        Some((verb, arg)) => (verb, arg.trim()),
+        None => (args, ""),
+    };
+    let current = {
+        let st = state.lock().unwrap_or_else(|e| e.into_inner());
+        st.recording.clone()
+    }
+    .filter(|name| sinks.lock().unwrap_or_else(|e| e.into_inner()).contains(name));
+
+    let verb = verb.to_uppercase();
+    match verb.as_str() {
+        "START" | "CAST" if !arg.is_empty() => {
+            if let Some(name) = current {
+                return format!("ERR already recording to {}\n", name);
+            }
+            let path = PathBuf::from(arg);
+            if let Some(err) = relative_path_error("recording", &path) {
+                return err;
+            }
+            let spec = match SinkSpec::parse(arg) {
+                _ if verb == "CAST" => SinkSpec::Cast(path.clone()),
+                Ok(SinkSpec::File(path)) if sink::is_y4m_path(&path) => SinkSpec::File(path),
+                Ok(spec @ SinkSpec::Cast(_)) => spec,
+                _ => {
+                    return "ERR recordings are Y4M or asciicast files: use a .y4m or .cast path\n"
+                        .to_string()
+                }
+            };
+            match send_sink_action(render_cmd_tx, RenderAction::AttachSink { spec }) {
+                Ok(msg) => {
+                    let name = msg.strip_prefix("output added ").unwrap_or(arg).to_string();
+                    let mut st = state.lock().unwrap_or_else(|e| e.into_inner());
+                    st.outputs.push(name.clone());
+                    st.recording = Some(name.clone());
+                    st.recording_path = Some(path);
+                    format!("OK recording to {}\n", name)
+                }
+                Err(msg) => format!("ERR {}\n", msg),
+            }
+        }
+        "STOP" => {
+            let Some(name) = current else {
+                return "ERR not recording\n".to_string();
+            };
+            let action = RenderAction::DetachSink { name: name.clone() };
+            match send_sink_action(render_cmd_tx, action) {
+                Ok(_) => {
+                    let mut st = state.lock().unwrap_or_else(|e| e.into_inner());
+                    st.outputs.retain(|o| *o != name);
+                    st.recording = None;
+                    let size = st
+                        .recording_path
+                        .take()
+                        .and_then(|path| std::fs::metadata(path).ok())
+                        .map_or(0, |m| m.len());
+                    format!("OK recording stopped {} ({} bytes)\n", name, size)
+                }
+                Err(msg) => format!("ERR {}\n", msg),
+            }
+        }
+        _ => "ERR usage: RECORD START <path.y4m|path.cast> | RECORD CAST <path> | RECORD STOP\n"
+            .to_string(),
+    }
+}
+
+/// `SNAPSHOT <format> <path>`: write the current frame as txt, ansi, html,
+/// svg or png
+fn handle_snapshot_command(args: &str, render_cmd_tx: &Sender<RenderCommand>) -> String {
+    let parsed = args
+        .trim()
+        .split_once(' ')
+        .and_then(|(format, path)| Some((SnapshotFormat::from_name(format)?, path.trim())))
+        .filter(|(_, path)| !path.is_empty());
+    let Some((format, path)) = parsed else {
+        return "ERR usage: SNAPSHOT <txt|ansi|html|svg|png> <path>\n".to_string();
+    };
+    if let Some(err) = relative_path_error("snapshot", Path::new(path)) {
+        return err;
+    }
+    let action = RenderAction::Snapshot {
+        format,
+        path: PathBuf::from(path),
+    };
+    match send_sink_action(render_cmd_tx, action) {
+        Ok(msg) => format!("OK {}\n", msg),
+        Err(msg) => format!("ERR {}\n", msg),
+    }
+}
+
+/// `set` and `snapshot` send absolute paths; a relative one would land in
+/// whatever directory this instance was started from
+fn relative_path_error(kind: &str, path: &Path) -> Option<String> {
+    path.is_relative().then(|| {
+        format!("ERR {} path '{}' is relative: give an absolute path\n", kind, path.display())
+    })
+}
+
+/// Send an attach/detach or snapshot to the render thread and wait for its answer
+fn send_sink_action(render_cmd_tx: &Sender<RenderCommand>, action: RenderAction) -> Result<String, String> {
+    let (resp_tx, resp_rx) = crossbeam_channel::bounded(1);
+    let cmd = RenderCommand {
+        action,
+        response_tx: resp_tx,
+    };
+    if render_cmd_tx.send(cmd).is_err() {
+        return Err("pipeline shutting down".to_string());
+    }
+    resp_rx
+        .recv_timeout(Duration::from_secs(5))
+        .unwrap_or_else(|_| Err("render thread timed out".to_string()))
+}
+
 // --- Change tracking ---
 
 #[derive(Default)]
 struct CaptureChanges {
-    camera_index: Option<u32>,
+    source: Option<SourceSpec>,
+    /// Camera identity and the index it resolved to
+    camera: Option<(CameraId, u32)>,
+    capture_format: Option<CaptureFormat>,
     resolution: Option<Option<(u32, u32)>>,
-    fps: Option<u32>,
+    fps: Option<FrameRate>,
+    controls: Vec<(String, String)>,
 }
 
 impl CaptureChanges {
     fn has_changes(&self) -> bool {
-        self.camera_index.is_some() || self.resolution.is_some() || self.fps.is_some()
+        self.changes_source()
+            || self.resolution.is_some()
+            || self.fps.is_some()
+            || !self.controls.is_empty()
+    }
+
+    fn changes_source(&self) -> bool {
+        self.source.is_some() || self.camera.is_some() || self.capture_format.is_some()
+    }
+
+    /// The source to switch to. A camera or capture format change keeps
+    /// the other half of the current webcam settings.
+    fn resolve_source(&self, current: SourceSpec) -> SourceSpec {
+        if let Some(ref source) = self.source {
+            return source.clone();
+        }
+        if self.camera.is_none() && self.capture_format.is_none() {
+            return current;
+        }
+        let (cur_id, cur_index, cur_format) = match current {
+            SourceSpec::Webcam { id, index, format } => (id, index, format),
+            _ => (CameraId::Index(0), 0, CaptureFormat::default()),
+        };
+        let (id, index) = self.camera.clone().unwrap_or((cur_id, cur_index));
+        SourceSpec::Webcam {
+            id,
+            index,
+            format: self.capture_format.unwrap_or(cur_format),
+        }
     }
 }
 
@@ -560,6 +942,7 @@ struct RenderChanges {
     definition: Option<u8>,
     brightness_curve: Option<BrightnessCurve>,
     invert: Option<bool>,
+    scale_mode: Option<ScaleMode>,
 }
 
 impl RenderChanges {
@@ -570,11 +953,12 @@ impl RenderChanges {
             || self.definition.is_some()
             || self.brightness_curve.is_some()
             || self.invert.is_some()
+            || self.scale_mode.is_some()
     }
 }
 
 struct StateSnapshot {
-    camera_index: u32,
+    source: SourceSpec,
     resolution: Option<(u32, u32)>,
     theme_name: String,
     fg: Rgb,
@@ -582,4 +966,65 @@ struct StateSnapshot {
     definition: u8,
     brightness_curve: BrightnessCurve,
     invert: bool,
+    scale_mode: ScaleMode,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pattern::Pattern;
+
+    fn test_state() -> Arc<Mutex<RuntimeState>> {
+        Arc::new(Mutex::new(RuntimeState {
+            source: SourceSpec::Pattern(Pattern::Bars),
+            resolution: None,
+            fps: FrameRate::from_int(30),
+            max_fps: MAX_FPS,
+            theme_name: "classic".to_string(),
+            fg: Rgb { r: 255, g: 255, b: 255 },
+            bg: Rgb { r: 0, g: 0, b: 0 },
+            definition: 5,
+            brightness_curve: BrightnessCurve::Linear,
+            invert: false,
+            scale_mode: ScaleMode::default(),
+            controls: Vec::new(),
+            outputs: Vec::new(),
+            output_options: V4l2Options::default(),
+            recording: None,
+            recording_path: None,
+        }))
+    }
+
+    #[test]
+    fn test_output_add_rejects_relative_path() {
+        let (tx, rx) = crossbeam_channel::unbounded();
+        let resp = handle_output_command("ADD out.cast", &test_state(), &tx);
+        assert_eq!(resp, "ERR output path 'out.cast' is relative: give an absolute path\n");
+        let resp = handle_output_command("ADD frames.rgb", &test_state(), &tx);
+        assert!(resp.starts_with("ERR output path 'frames.rgb' is relative"));
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_snapshot_rejects_relative_path() {
+        let (tx, rx) = crossbeam_channel::unbounded();
+        let resp = handle_snapshot_command("png shot.png", &tx);
+        assert_eq!(resp, "ERR snapshot path 'shot.png' is relative: give an absolute path\n");
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_record_rejects_relative_path() {
+        let (tx, rx) = crossbeam_channel::unbounded();
+        let sinks = Arc::new(Mutex::new(SinkSet::new(
+            Arc::new(AtomicBool::new(false)),
+            FrameRate::from_int(30),
+        )));
+        for args in ["START clip.y4m", "CAST ./session.cast"] {
+            let resp = handle_record_command(args, &test_state(), &tx, &sinks);
+            assert!(resp.starts_with("ERR recording path"), "{}", resp);
+            assert!(resp.ends_with("is relative: give an absolute path\n"));
+        }
+        assert!(rx.try_recv().is_err());
+    }
 }
@@ -0,0 +1,281 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::capture::{sequence_path, EndOfStream, FrameSource, SourceSpec};
+use crate::cast::CastSink;
+use crate::config::{self, Appearance, ConvertArgs, ScaleMode};
+use crate::framerate::FrameRate;
+use crate::output::V4l2Options;
+use crate::pixfmt::PixelFormat;
+use crate::rawvideo::RawVideoSource;
+use crate::renderer::AsciiRenderer;
+use crate::sink::{OutputSink, RenderedFrame, SinkSpec};
+use crate::snapshot::{self, SnapshotFormat};
+use crate::y4m::Y4mSource;
+
+/// How long the rain falls before the first frame, long enough to reach the
+/// bottom of the screen
+const RAIN_WARMUP: Duration = Duration::from_secs(3);
+
+/// A file input, read as fast as it decodes
+struct Input {
+    source: Box<dyn FrameSource>,
+    /// The rate the frames were meant to play at, which the rain follows
+    fps: FrameRate,
+    /// A single image is one frame, where the live sources repeat it forever
+    still: bool,
+}
+
+/// Where converted frames go
+enum Destination {
+    /// Every frame, to a Y4M or raw file, a cast, a pipe or stdout
+    Stream(Box<dyn OutputSink>),
+    /// One picture or text file per frame when `pattern` has a `%d`
+    /// placeholder, otherwise the first frame alone
+    Files { format: SnapshotFormat, pattern: String },
+}
+
+/// `virtual-ascii convert`: run every frame of a file through the renderer
+/// and write the result. Frames are timed by the file rather than the clock,
+/// and the rain starts from a fixed seed, so the same input always gives the
+/// same output.
+pub fn run(args: ConvertArgs) -> anyhow::Result<()> {
+    let appearance = Appearance::from_args(&args.appearance)?;
+    let scale_mode = ScaleMode::from_name(&args.scale_mode).ok_or_else(|| {
+        anyhow::anyhow!(
+            "Unknown scale mode '{}'. Available: fit, fill, stretch",
+            args.scale_mode
+        )
+    })?;
+
+    let mut input = open_input(&args)?;
+    let (width, height) = args.output_size.unwrap_or(input.source.resolution());
+    let mut renderer = AsciiRenderer::new(
+        &appearance.charset,
+        appearance.theme.fg,
+        appearance.theme.bg,
+        appearance.brightness_curve,
+        appearance.invert,
+        width,
+        height,
+        appearance.ascii_columns,
+        &appearance.theme.name,
+    )
+    .map_err(|e| anyhow::anyhow!("Renderer init failed: {}", e))?;
+    renderer.set_scale_mode(scale_mode);
+    let interval = input.fps.interval();
+    renderer.seed_rain(args.seed);
+    renderer.run_rain(RAIN_WARMUP, interval);
+
+    let mut destination = open_destination(&args.output, (width, height), input.fps)?;
+    let mut frames = 0u32;
+    loop {
+        let data = match input.source.capture_frame() {
+            Ok(data) => data,
+            Err(e) if e.is::<EndOfStream>() => break,
+            Err(e) => return Err(e),
+        };
+        let (w, h) = input.source.frame_dimensions();
+        let grid = renderer.analyze_timed(&data, input.source.pixel_format(), w, h, interval);
+        let frame = RenderedFrame {
+            rgb: renderer.rasterize(&grid),
+            width: renderer.output_width,
+            height: renderer.output_height,
+            grid,
+        };
+        frames += 1;
+
+        match destination {
+            Destination::Stream(ref mut sink) => sink.write_frame(&frame)?,
+            Destination::Files { format, ref pattern } => {
+                // Numbered from 1, like ffmpeg's image sequences
+                let path = sequence_path(pattern, frames);
+                let single = path.is_none();
+                let path = path.unwrap_or_else(|| pattern.clone());
+                snapshot::write(format, &frame, renderer.font_metrics(), Path::new(&path))?;
+                if single {
+                    break;
+                }
+            }
+        }
+        if input.still {
+            break;
+        }
+    }
+
+    let (columns, rows) = renderer.grid_size();
+    eprintln!(
+        "Converted {} frame{} to {} ({}x{}, {}x{} characters)",
+        frames,
+        if frames == 1 { "" } else { "s" },
+        args.output,
+        renderer.output_width,
+        renderer.output_height,
+        columns,
+        rows
+    );
+    Ok(())
+}
+
+fn open_input(args: &ConvertArgs) -> anyhow::Result<Input> {
+    let input = args.input.as_str();
+    let extension = Path::new(input)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase());
+    let raw = || -> anyhow::Result<(PixelFormat, (u32, u32))> {
+        let format = PixelFormat::from_name(&args.input_format).ok_or_else(|| {
+            anyhow::anyhow!(
+                "Unknown input format '{}'. Available: rgb24, yuyv, nv12, gray",
+                args.input_format
+            )
+        })?;
+        let size = args
+            .input_size
+            .ok_or_else(|| anyhow::anyhow!("Raw video needs its frame size: --input-size WxH"))?;
+        Ok((format, size))
+    };
+
+    let stream = |source: Box<dyn FrameSource>| Input {
+        source,
+        fps: args.fps,
+        still: false,
+    };
+    if input == "-" {
+        let (format, size) = raw()?;
+        return Ok(stream(Box::new(RawVideoSource::stdin(format, size))));
+    }
+    if let Some("rgb" | "raw") = extension.as_deref() {
+        let (format, size) = raw()?;
+        let file = File::open(input).map_err(|e| anyhow::anyhow!("Cannot open '{}': {}", input, e))?;
+        return Ok(stream(Box::new(RawVideoSource::new(BufReader::new(file), format, size))));
+    }
+
+    let unsupported = || {
+        anyhow::anyhow!(
+            "Unsupported input '{}': expected a .y4m, .png, .rgb or .raw file, an image sequence \
+             (frames/%05d.png), or - for raw video on stdin",
+            input
+        )
+    };
+    if input.starts_with("pattern:") {
+        return Err(unsupported());
+    }
+    match config::parse_input(input, false).map_err(|_| unsupported())? {
+        SourceSpec::Y4m { ref path, .. } => {
+            let source = Y4mSource::open(path, false)?.unpaced();
+            Ok(Input {
+                fps: source.frame_rate().unwrap_or(args.fps),
+                source: Box::new(source),
+                still: false,
+            })
+        }
+        spec @ SourceSpec::Image { .. } => Ok(Input {
+            source: spec.open(None, args.fps)?,
+            fps: args.fps,
+            still: sequence_path(input, 0).is_none(),
+        }),
+        _ => Err(unsupported()),
+    }
+}
+
+fn open_destination(output: &str, (width, height): (u32, u32), fps: FrameRate) -> anyhow::Result<Destination> {
+    if let Some(format) = SnapshotFormat::from_path(Path::new(output)) {
+        return Ok(Destination::Files {
+            format,
+            pattern: output.to_string(),
+        });
+    }
+
+    let spec = SinkSpec::parse(output).map_err(|e| anyhow::anyhow!(e))?;
+    let mut sink: Box<dyn OutputSink> = match spec {
+        SinkSpec::V4l2(_) => {
+            return Err(anyhow::anyhow!(
+                "convert writes files. For a loopback device, run virtual-ascii --input <file> instead"
+            ))
+        }
+        SinkSpec::Cast(ref path) => Box::new(CastSink::create(path)?.timed_by(fps)),
+        // Raw frames go to .rgb/.raw files, or to FIFOs and devices without
+        // an extension, rather than to a .jpg or .mp4 that would not open
+        SinkSpec::File(ref path)
+            if path
+                .extension()
+                .and_then(|e| e.to_str())
+                .is_some_and(|e| !["y4m", "rgb", "raw"].contains(&e.to_ascii_lowercase().as_str())) =>
+        {
+            return Err(anyhow::anyhow!(
+                "Unsupported output '{}': expected a .y4m, .rgb, .raw, .cast, .png, .txt, .ans, .html \
+                 or .svg file, pipe:<command>, or - for stdout",
+                output
+            ))
+        }
+        _ => spec.open(width, height, V4l2Options::default())?,
+    };
+    sink.set_frame_rate(fps)?;
+    Ok(Destination::Stream(sink))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::AppearanceArgs;
+
+    fn args(input: &str, output: &str, theme: &str) -> ConvertArgs {
+        ConvertArgs {
+            input: input.to_string(),
+            output: output.to_string(),
+            appearance: AppearanceArgs {
+                definition: 1,
+                theme: theme.to_string(),
+                fg_color: None,
+                bg_color: None,
+                brightness_curve: "linear".into(),
+                invert: false,
+            },
+            fps: FrameRate::from_int(10),
+            input_format: "gray".into(),
+            input_size: Some((64, 48)),
+            output_size: Some((320, 240)),
+            scale_mode: "fill".into(),
+            seed: 1,
+        }
+    }
+
+    #[test]
+    fn test_convert_is_reproducible() {
+        let dir = std::env::temp_dir().join(format!("virtual-ascii-convert-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        // Three gray frames getting brighter
+        let input = dir.join("in.raw");
+        let data: Vec<u8> = [40u8, 120, 250].iter().flat_map(|&v| vec![v; 64 * 48]).collect();
+        std::fs::write(&input, data).unwrap();
+
+        let convert = |output: &str, theme: &str| {
+            let output = dir.join(output);
+            run(args(input.to_str().unwrap(), output.to_str().unwrap(), theme)).unwrap();
+        };
+        let read = |name: &str| std::fs::read_to_string(dir.join(name)).unwrap();
+
+        convert("rain1.cast", "matrix");
+        convert("rain2.cast", "matrix");
+        // The header holds the wall-clock start time; the events must match
+        let strip_timestamp = |text: String| text.split_once('\n').unwrap().1.to_string();
+        let first = strip_timestamp(read("rain1.cast"));
+        assert_eq!(first, strip_timestamp(read("rain2.cast")));
+        // Events at the file's 10 fps, whatever the conversion speed
+        assert!(first.starts_with("[0.000000, "));
+        assert!(first.lines().nth(1).unwrap().starts_with("[0.100000, "));
+
+        // One text file per frame with a placeholder, only the first without
+        convert("frame%d.txt", "green");
+        let frames: Vec<String> = (1..=3).map(|i| read(&format!("frame{}.txt", i))).collect();
+        assert!(!dir.join("frame4.txt").exists());
+        assert_ne!(frames[0], frames[2]);
+        convert("frame.txt", "green");
+        assert_eq!(read("frame.txt"), frames[0]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,5 +1,11 @@
+use std::fmt;
 use std::fs::OpenOptions;
 use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+
+use crate::capture::CaptureFormat;
+use crate::framerate::FrameRate;
+use crate::hotplug;
 
 // V4L2 capability flags
 const V4L2_CAP_VIDEO_CAPTURE: u32 = 0x00000001;
@@ -11,13 +17,47 @@ const V4L2_BUF_TYPE_VIDEO_CAPTURE: u32 = 1;
 
 // V4L2 frame size types
 const V4L2_FRMSIZE_TYPE_DISCRETE: u32 = 1;
+const V4L2_FRMSIZE_TYPE_CONTINUOUS: u32 = 2;
+const V4L2_FRMSIZE_TYPE_STEPWISE: u32 = 3;
 
 // V4L2 frame interval types
 const V4L2_FRMIVAL_TYPE_DISCRETE: u32 = 1;
+const V4L2_FRMIVAL_TYPE_CONTINUOUS: u32 = 2;
+const V4L2_FRMIVAL_TYPE_STEPWISE: u32 = 3;
+
+/// Sizes offered from a stepwise or continuous range, largest first. The
+/// range's maximum is always offered as well.
+const STANDARD_SIZES: [(u32, u32); 12] = [
+    (3840, 2160),
+    (2560, 1440),
+    (1920, 1440),
+    (1920, 1080),
+    (1600, 1200),
+    (1280, 960),
+    (1280, 720),
+    (1024, 768),
+    (800, 600),
+    (640, 480),
+    (320, 240),
+    (160, 120),
+];
+
+// Capture pixel format fourccs
+const V4L2_PIX_FMT_MJPEG: u32 = fourcc(b'M', b'J', b'P', b'G');
+const V4L2_PIX_FMT_YUYV: u32 = fourcc(b'Y', b'U', b'Y', b'V');
+const V4L2_PIX_FMT_NV12: u32 = fourcc(b'N', b'V', b'1', b'2');
+
+const fn fourcc(a: u8, b: u8, c: u8, d: u8) -> u32 {
+    (a as u32) | ((b as u32) << 8) | ((c as u32) << 16) | ((d as u32) << 24)
+}
 
-// MJPEG fourcc
-const V4L2_PIX_FMT_MJPEG: u32 =
-    (b'M' as u32) | ((b'J' as u32) << 8) | ((b'P' as u32) << 16) | ((b'G' as u32) << 24);
+fn format_fourcc(format: CaptureFormat) -> u32 {
+    match format {
+        CaptureFormat::Mjpeg => V4L2_PIX_FMT_MJPEG,
+        CaptureFormat::Yuyv => V4L2_PIX_FMT_YUYV,
+        CaptureFormat::Nv12 => V4L2_PIX_FMT_NV12,
+    }
+}
 
 /// V4L2 format descriptor for VIDIOC_ENUM_FMT
 #[repr(C)]
@@ -40,10 +80,9 @@ struct V4l2FrmSizeEnum {
     index: u32,
     pixel_format: u32,
     type_: u32,
-    // union, for discrete (type=1): width, height
-    width: u32,
-    height: u32,
-    _padding: [u8; 16],
+    // union: discrete is [width, height, ..], stepwise/continuous is
+    // [min_width, max_width, step_width, min_height, max_height, step_height]
+    size: [u32; 6],
     reserved: [u32; 2],
 }
 
@@ -58,10 +97,9 @@ struct V4l2FrmIvalEnum {
     width: u32,
     height: u32,
     type_: u32,
-    // union, for discrete (type=1): numerator, denominator
-    numerator: u32,
-    denominator: u32,
-    _padding: [u8; 16],
+    // union: discrete is [numerator, denominator, ..], stepwise/continuous is
+    // [min.numerator, min.denominator, max.numerator, max.denominator, step.numerator, step.denominator]
+    interval: [u32; 6],
     reserved: [u32; 2],
 }
 
@@ -105,6 +143,11 @@ fn cap_driver(cap: &V4l2Capability) -> &str {
     std::str::from_utf8(&cap.driver[..len]).unwrap_or("")
 }
 
+fn cap_card(cap: &V4l2Capability) -> &str {
+    let len = cap.card.iter().position(|&b| b == 0).unwrap_or(cap.card.len());
+    std::str::from_utf8(&cap.card[..len]).unwrap_or("")
+}
+
 fn cap_bus_info(cap: &V4l2Capability) -> &str {
     let len = cap.bus_info.iter().position(|&b| b == 0).unwrap_or(cap.bus_info.len());
     std::str::from_utf8(&cap.bus_info[..len]).unwrap_or("")
@@ -128,9 +171,22 @@ fn is_capture(cap: &V4l2Capability) -> bool {
     caps & V4L2_CAP_VIDEO_CAPTURE != 0 || caps & V4L2_CAP_VIDEO_CAPTURE_MPLANE != 0
 }
 
+/// Indices of the /dev/videoN nodes that currently exist, ascending. Cheaper than
+/// opening every possible index, and doesn't stop at an arbitrary upper bound.
+fn video_indices() -> Vec<u32> {
+    let mut indices: Vec<u32> = match std::fs::read_dir("/dev") {
+        Ok(entries) => entries
+            .filter_map(|entry| hotplug::video_index(&entry.ok()?.file_name()))
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    indices.sort_unstable();
+    indices
+}
+
 /// Find the first real capture camera, skipping loopback and the configured output device.
 pub fn detect_camera(output_device: &str) -> Option<u32> {
-    for index in 0..64 {
+    for index in video_indices() {
         let dev_path = format!("/dev/video{}", index);
         if dev_path == output_device {
             continue;
@@ -164,7 +220,7 @@ pub struct CameraInfo {
 /// List all real capture cameras, skipping loopback and the configured output device.
 pub fn list_cameras(output_device: &str) -> Vec<CameraInfo> {
     let mut cameras = Vec::new();
-    for index in 0..64 {
+    for index in video_indices() {
         let dev_path = format!("/dev/video{}", index);
         if dev_path == output_device {
             continue;
@@ -185,8 +241,83 @@ pub fn list_cameras(output_device: &str) -> Vec<CameraInfo> {
     cameras
 }
 
-/// Find the MJPEG fourcc for a capture device by enumerating pixel formats.
-fn find_mjpeg_fourcc(fd: std::os::unix::io::RawFd) -> Option<u32> {
+/// How a camera was picked on the command line. `/dev/videoN` numbers shuffle
+/// when cameras are replugged or modules reloaded, so anything other than a bare
+/// index is resolved again each time the camera is reopened.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CameraId {
+    /// /dev/videoN
+    Index(u32),
+    /// A device node or a symlink to one, e.g. /dev/v4l/by-id/usb-...-video-index0
+    Path(PathBuf),
+    /// Exact V4L2 bus_info (e.g. `usb-0000:00:14.0-2`), else a case-insensitive
+    /// substring of the card name
+    Query(String),
+}
+
+impl CameraId {
+    pub fn parse(s: &str) -> Self {
+        let s = s.trim();
+        if let Ok(index) = s.parse() {
+            CameraId::Index(index)
+        } else if s.starts_with('/') {
+            CameraId::Path(PathBuf::from(s))
+        } else {
+            CameraId::Query(s.to_string())
+        }
+    }
+}
+
+impl fmt::Display for CameraId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CameraId::Index(index) => write!(f, "{}", index),
+            CameraId::Path(path) => write!(f, "{}", path.display()),
+            CameraId::Query(query) => write!(f, "{}", query),
+        }
+    }
+}
+
+/// The N of a /dev/videoN path, following symlinks such as /dev/v4l/by-id entries.
+fn path_index(path: &Path) -> Option<u32> {
+    let real = std::fs::canonicalize(path).ok()?;
+    real.file_name()?.to_str()?.strip_prefix("video")?.parse().ok()
+}
+
+/// Resolve a camera identity to its current /dev/videoN index. Name and bus_info
+/// queries only consider real capture devices, never loopback outputs.
+pub fn resolve_camera(id: &CameraId) -> Option<u32> {
+    match id {
+        CameraId::Index(index) => Some(*index),
+        CameraId::Path(path) => {
+            let index = path_index(path)?;
+            query_cap(index).filter(is_capture).map(|_| index)
+        }
+        CameraId::Query(query) => {
+            let cameras: Vec<(u32, V4l2Capability)> = video_indices()
+                .into_iter()
+                .filter_map(|index| query_cap(index).map(|cap| (index, cap)))
+                .filter(|(_, cap)| !is_loopback(cap) && is_capture(cap))
+                .collect();
+            // bus_info is unique per device, so it wins over a name that might
+            // match several identical cameras
+            let needle = query.to_lowercase();
+            cameras
+                .iter()
+                .find(|(_, cap)| cap_bus_info(cap) == query.as_str())
+                .or_else(|| {
+                    cameras
+                        .iter()
+                        .find(|(_, cap)| cap_card(cap).to_lowercase().contains(&needle))
+                })
+                .map(|&(index, _)| index)
+        }
+    }
+}
+
+/// Find the fourcc of `format` on a capture device by enumerating pixel formats.
+fn find_fourcc(fd: std::os::unix::io::RawFd, format: CaptureFormat) -> Option<u32> {
+    let wanted = format_fourcc(format);
     for i in 0u32.. {
         let mut desc: V4l2FmtDesc = unsafe { std::mem::zeroed() };
         desc.index = i;
@@ -194,16 +325,35 @@ fn find_mjpeg_fourcc(fd: std::os::unix::io::RawFd) -> Option<u32> {
         if unsafe { vidioc_enum_fmt(fd, &mut desc) }.is_err() {
             break;
         }
-        if desc.pixelformat == V4L2_PIX_FMT_MJPEG {
+        if desc.pixelformat == wanted {
             return Some(desc.pixelformat);
         }
     }
     None
 }
 
-/// List all supported resolutions for a camera (MJPEG format, discrete sizes).
+/// Sizes a stepwise or continuous range can produce: the standard sizes that fit
+/// its bounds and step, plus its maximum.
+fn sizes_in_range(size: &[u32; 6]) -> Vec<(u32, u32)> {
+    let [min_w, max_w, step_w, min_h, max_h, step_h] = *size;
+    let fits = |v: u32, min: u32, max: u32, step: u32| {
+        v >= min && v <= max && (v - min).is_multiple_of(step.max(1))
+    };
+    let mut sizes: Vec<(u32, u32)> = STANDARD_SIZES
+        .iter()
+        .copied()
+        .filter(|&(w, h)| fits(w, min_w, max_w, step_w) && fits(h, min_h, max_h, step_h))
+        .collect();
+    if max_w > 0 && max_h > 0 {
+        sizes.push((max_w, max_h));
+    }
+    sizes
+}
+
+/// List all supported resolutions for a camera in the given format. Stepwise
+/// and continuous ranges are expanded with `sizes_in_range`.
 /// Returns sorted by pixel count (largest first). Empty on error.
-pub fn list_resolutions(camera_index: u32) -> Vec<(u32, u32)> {
+pub fn list_resolutions(camera_index: u32, format: CaptureFormat) -> Vec<(u32, u32)> {
     let path = format!("/dev/video{}", camera_index);
     let file = match OpenOptions::new().read(true).write(true).open(&path) {
         Ok(f) => f,
@@ -211,7 +361,7 @@ pub fn list_resolutions(camera_index: u32) -> Vec<(u32, u32)> {
     };
     let fd = file.as_raw_fd();
 
-    let fourcc = match find_mjpeg_fourcc(fd) {
+    let fourcc = match find_fourcc(fd, format) {
         Some(f) => f,
         None => return Vec::new(),
     };
@@ -224,30 +374,42 @@ pub fn list_resolutions(camera_index: u32) -> Vec<(u32, u32)> {
         if unsafe { vidioc_enum_framesizes(fd, &mut frmsize) }.is_err() {
             break;
         }
-        if frmsize.type_ == V4L2_FRMSIZE_TYPE_DISCRETE {
-            resolutions.push((frmsize.width, frmsize.height));
+        match frmsize.type_ {
+            V4L2_FRMSIZE_TYPE_DISCRETE => resolutions.push((frmsize.size[0], frmsize.size[1])),
+            // A range is reported as the only entry
+            V4L2_FRMSIZE_TYPE_CONTINUOUS | V4L2_FRMSIZE_TYPE_STEPWISE => {
+                resolutions.extend(sizes_in_range(&frmsize.size));
+                break;
+            }
+            _ => {}
         }
     }
 
     resolutions.sort_by(|a, b| {
         let pa = (a.0 as u64) * (a.1 as u64);
         let pb = (b.0 as u64) * (b.1 as u64);
-        pb.cmp(&pa)
+        pb.cmp(&pa).then(b.cmp(a))
     });
     resolutions.dedup();
     resolutions
 }
 
-/// Query the maximum FPS for a given resolution (MJPEG format).
-/// Returns None on error or if no discrete intervals are reported.
-pub fn max_fps_for_resolution(camera_index: u32, width: u32, height: u32) -> Option<u32> {
+/// Query the maximum frame rate for a given resolution and format. For
+/// stepwise and continuous intervals that's the rate of the shortest interval.
+/// Returns None on error or if no intervals are reported.
+pub fn max_fps_for_resolution(
+    camera_index: u32,
+    format: CaptureFormat,
+    width: u32,
+    height: u32,
+) -> Option<FrameRate> {
     let path = format!("/dev/video{}", camera_index);
     let file = OpenOptions::new().read(true).write(true).open(&path).ok()?;
     let fd = file.as_raw_fd();
 
-    let fourcc = find_mjpeg_fourcc(fd)?;
+    let fourcc = find_fourcc(fd, format)?;
 
-    let mut max_fps: Option<u32> = None;
+    let mut max_fps: Option<FrameRate> = None;
     for i in 0u32.. {
         let mut frmival: V4l2FrmIvalEnum = unsafe { std::mem::zeroed() };
         frmival.index = i;
@@ -257,10 +419,414 @@ pub fn max_fps_for_resolution(camera_index: u32, width: u32, height: u32) -> Opt
         if unsafe { vidioc_enum_frameintervals(fd, &mut frmival) }.is_err() {
             break;
         }
-        if frmival.type_ == V4L2_FRMIVAL_TYPE_DISCRETE && frmival.numerator > 0 {
-            let fps = frmival.denominator / frmival.numerator;
+        // Discrete entries and the minimum of a range both sit in interval[0..2]
+        let fps = match frmival.type_ {
+            V4L2_FRMIVAL_TYPE_DISCRETE
+            | V4L2_FRMIVAL_TYPE_CONTINUOUS
+            | V4L2_FRMIVAL_TYPE_STEPWISE => {
+                FrameRate::from_interval(frmival.interval[0], frmival.interval[1])
+            }
+            _ => None,
+        };
+        if let Some(fps) = fps {
             max_fps = Some(max_fps.map_or(fps, |prev| prev.max(fps)));
         }
+        if frmival.type_ != V4L2_FRMIVAL_TYPE_DISCRETE {
+            break;
+        }
     }
     max_fps
 }
+
+// --- Camera controls ---
+
+// V4L2 control types
+const V4L2_CTRL_TYPE_INTEGER: u32 = 1;
+const V4L2_CTRL_TYPE_BOOLEAN: u32 = 2;
+const V4L2_CTRL_TYPE_MENU: u32 = 3;
+const V4L2_CTRL_TYPE_BUTTON: u32 = 4;
+const V4L2_CTRL_TYPE_CTRL_CLASS: u32 = 6;
+const V4L2_CTRL_TYPE_INTEGER_MENU: u32 = 9;
+
+// V4L2 control flags
+const V4L2_CTRL_FLAG_DISABLED: u32 = 0x0001;
+const V4L2_CTRL_FLAG_READ_ONLY: u32 = 0x0004;
+const V4L2_CTRL_FLAG_INACTIVE: u32 = 0x0010;
+const V4L2_CTRL_FLAG_WRITE_ONLY: u32 = 0x0040;
+const V4L2_CTRL_FLAG_NEXT_CTRL: u32 = 0x80000000;
+
+/// V4L2 control descriptor for VIDIOC_QUERYCTRL
+#[repr(C)]
+struct V4l2QueryCtrl {
+    id: u32,
+    type_: u32,
+    name: [u8; 32],
+    minimum: i32,
+    maximum: i32,
+    step: i32,
+    default_value: i32,
+    flags: u32,
+    reserved: [u32; 2],
+}
+
+// Verify struct matches kernel layout (68 bytes)
+const _: () = assert!(std::mem::size_of::<V4l2QueryCtrl>() == 68);
+
+/// V4L2 menu item for VIDIOC_QUERYMENU (packed in the kernel header)
+#[repr(C, packed)]
+struct V4l2QueryMenu {
+    id: u32,
+    index: u32,
+    // union: name[32] for menus, s64 value for integer menus
+    name: [u8; 32],
+    reserved: u32,
+}
+
+// Verify struct matches kernel layout (44 bytes)
+const _: () = assert!(std::mem::size_of::<V4l2QueryMenu>() == 44);
+
+#[repr(C)]
+struct V4l2Control {
+    id: u32,
+    value: i32,
+}
+
+// VIDIOC_G_CTRL = _IOWR('V', 27, struct v4l2_control)
+nix::ioctl_readwrite!(vidioc_g_ctrl, b'V', 27, V4l2Control);
+// VIDIOC_S_CTRL = _IOWR('V', 28, struct v4l2_control)
+nix::ioctl_readwrite!(vidioc_s_ctrl, b'V', 28, V4l2Control);
+// VIDIOC_QUERYCTRL = _IOWR('V', 36, struct v4l2_queryctrl)
+nix::ioctl_readwrite!(vidioc_queryctrl, b'V', 36, V4l2QueryCtrl);
+// VIDIOC_QUERYMENU = _IOWR('V', 37, struct v4l2_querymenu)
+nix::ioctl_readwrite!(vidioc_querymenu, b'V', 37, V4l2QueryMenu);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ControlKind {
+    Integer,
+    Boolean,
+    Menu,
+    IntegerMenu,
+    Button,
+}
+
+impl ControlKind {
+    fn from_v4l2(type_: u32) -> Option<Self> {
+        match type_ {
+            V4L2_CTRL_TYPE_INTEGER => Some(Self::Integer),
+            V4L2_CTRL_TYPE_BOOLEAN => Some(Self::Boolean),
+            V4L2_CTRL_TYPE_MENU => Some(Self::Menu),
+            V4L2_CTRL_TYPE_INTEGER_MENU => Some(Self::IntegerMenu),
+            V4L2_CTRL_TYPE_BUTTON => Some(Self::Button),
+            // 64-bit, string, bitmask and compound controls aren't settable via S_CTRL
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Integer => "int",
+            Self::Boolean => "bool",
+            Self::Menu => "menu",
+            Self::IntegerMenu => "intmenu",
+            Self::Button => "button",
+        }
+    }
+}
+
+/// A camera control as reported by VIDIOC_QUERYCTRL
+#[derive(Debug, Clone)]
+pub struct CameraControl {
+    pub id: u32,
+    /// Key used on the command line and control socket (e.g. `exposure_time_absolute`)
+    pub key: String,
+    /// Driver-provided name (e.g. "Exposure Time, Absolute")
+    pub label: String,
+    pub kind: ControlKind,
+    pub min: i32,
+    pub max: i32,
+    pub step: i32,
+    pub default: i32,
+    /// Current value, None for write-only controls
+    pub value: Option<i32>,
+    pub read_only: bool,
+    pub inactive: bool,
+    /// (index, label) of each valid menu item
+    pub menu: Vec<(i32, String)>,
+}
+
+/// Turn a driver control name into a key the way v4l2-ctl does:
+/// lowercase, runs of non-alphanumerics collapsed to `_`.
+pub fn control_key(label: &str) -> String {
+    let mut key = String::with_capacity(label.len());
+    for c in label.chars() {
+        if c.is_ascii_alphanumeric() {
+            key.push(c.to_ascii_lowercase());
+        } else if !key.is_empty() && !key.ends_with('_') {
+            key.push('_');
+        }
+    }
+    while key.ends_with('_') {
+        key.pop();
+    }
+    key
+}
+
+impl CameraControl {
+    /// Parse a value given on the command line: a number, `true`/`false` for
+    /// booleans, or a menu item by key (e.g. `manual_mode`).
+    pub fn parse_value(&self, value: &str) -> Result<i32, String> {
+        let parsed = match (self.kind, value.to_ascii_lowercase().as_str()) {
+            (ControlKind::Boolean, "true" | "on") => Some(1),
+            (ControlKind::Boolean, "false" | "off") => Some(0),
+            (ControlKind::Menu | ControlKind::IntegerMenu, v) => value
+                .parse()
+                .ok()
+                .or_else(|| {
+                    self.menu
+                        .iter()
+                        .find(|(_, label)| control_key(label) == control_key(v))
+                        .map(|&(index, _)| index)
+                }),
+            _ => value.parse().ok(),
+        };
+        let v = parsed.ok_or_else(|| format!("invalid value '{}' for {}", value, self.key))?;
+
+        if v < self.min || v > self.max {
+            return Err(format!(
+                "{}={} out of range ({}..{})",
+                self.key, v, self.min, self.max
+            ));
+        }
+        if matches!(self.kind, ControlKind::Menu | ControlKind::IntegerMenu)
+            && !self.menu.iter().any(|&(index, _)| index == v)
+        {
+            return Err(format!("{}={} is not a valid menu item", self.key, v));
+        }
+        Ok(v)
+    }
+}
+
+/// Most menu items queried per control. A driver reporting a wider range
+/// would cost one ioctl per index, so such menus are left without labels.
+const MAX_MENU_ITEMS: i64 = 256;
+
+/// The menu indices to query for a control reporting `minimum..=maximum`
+fn menu_indices(minimum: i32, maximum: i32) -> Option<std::ops::RangeInclusive<i32>> {
+    let span = maximum as i64 - minimum as i64 + 1;
+    (minimum >= 0 && (1..=MAX_MENU_ITEMS).contains(&span)).then_some(minimum..=maximum)
+}
+
+fn query_menu(fd: std::os::unix::io::RawFd, ctrl: &V4l2QueryCtrl, kind: ControlKind) -> Vec<(i32, String)> {
+    let mut items = Vec::new();
+    let Some(indices) = menu_indices(ctrl.minimum, ctrl.maximum) else {
+        return items;
+    };
+    for index in indices {
+        let mut item: V4l2QueryMenu = unsafe { std::mem::zeroed() };
+        item.id = ctrl.id;
+        item.index = index as u32;
+        // Drivers skip unsupported items by failing the ioctl for that index
+        if unsafe { vidioc_querymenu(fd, &mut item) }.is_err() {
+            continue;
+        }
+        let label = if kind == ControlKind::IntegerMenu {
+            let mut bytes = [0u8; 8];
+            bytes.copy_from_slice(&item.name[..8]);
+            i64::from_ne_bytes(bytes).to_string()
+        } else {
+            let len = item.name.iter().position(|&b| b == 0).unwrap_or(32);
+            String::from_utf8_lossy(&item.name[..len]).into_owned()
+        };
+        items.push((index, label));
+    }
+    items
+}
+
+fn controls_on_fd(fd: std::os::unix::io::RawFd) -> Vec<CameraControl> {
+    let mut controls = Vec::new();
+    let mut next_id = V4L2_CTRL_FLAG_NEXT_CTRL;
+    loop {
+        let mut ctrl: V4l2QueryCtrl = unsafe { std::mem::zeroed() };
+        ctrl.id = next_id;
+        if unsafe { vidioc_queryctrl(fd, &mut ctrl) }.is_err() {
+            break;
+        }
+        next_id = ctrl.id | V4L2_CTRL_FLAG_NEXT_CTRL;
+
+        if ctrl.flags & V4L2_CTRL_FLAG_DISABLED != 0 || ctrl.type_ == V4L2_CTRL_TYPE_CTRL_CLASS {
+            continue;
+        }
+        let kind = match ControlKind::from_v4l2(ctrl.type_) {
+            Some(k) => k,
+            None => continue,
+        };
+
+        let value = if ctrl.flags & V4L2_CTRL_FLAG_WRITE_ONLY != 0 || kind == ControlKind::Button {
+            None
+        } else {
+            let mut c = V4l2Control { id: ctrl.id, value: 0 };
+            unsafe { vidioc_g_ctrl(fd, &mut c) }.ok().map(|_| c.value)
+        };
+        let menu = match kind {
+            ControlKind::Menu | ControlKind::IntegerMenu => query_menu(fd, &ctrl, kind),
+            _ => Vec::new(),
+        };
+
+        let len = ctrl.name.iter().position(|&b| b == 0).unwrap_or(32);
+        let label = String::from_utf8_lossy(&ctrl.name[..len]).into_owned();
+        controls.push(CameraControl {
+            id: ctrl.id,
+            key: control_key(&label),
+            label,
+            kind,
+            min: ctrl.minimum,
+            max: ctrl.maximum,
+            step: ctrl.step,
+            default: ctrl.default_value,
+            value,
+            read_only: ctrl.flags & V4L2_CTRL_FLAG_READ_ONLY != 0,
+            inactive: ctrl.flags & V4L2_CTRL_FLAG_INACTIVE != 0,
+            menu,
+        });
+    }
+    controls
+}
+
+/// List the user-settable controls of a camera. Empty on error.
+pub fn list_controls(camera_index: u32) -> Vec<CameraControl> {
+    let path = format!("/dev/video{}", camera_index);
+    match OpenOptions::new().read(true).write(true).open(&path) {
+        Ok(file) => controls_on_fd(file.as_raw_fd()),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// Set a control by key. Controls are per device rather than per file handle,
+/// so this takes effect on a stream another handle has open.
+/// Returns the value read back from the driver.
+pub fn set_control(camera_index: u32, key: &str, value: &str) -> Result<i32, String> {
+    let path = format!("/dev/video{}", camera_index);
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(&path)
+        .map_err(|e| format!("cannot open {}: {}", path, e))?;
+    let fd = file.as_raw_fd();
+
+    let ctrl = controls_on_fd(fd)
+        .into_iter()
+        .find(|c| c.key == key)
+        .ok_or_else(|| format!("{} has no control '{}'", path, key))?;
+    if ctrl.read_only {
+        return Err(format!("control '{}' ({}) is read-only", key, ctrl.label));
+    }
+    let v = ctrl.parse_value(value)?;
+
+    let mut c = V4l2Control { id: ctrl.id, value: v };
+    unsafe { vidioc_s_ctrl(fd, &mut c) }.map_err(|e| {
+        if ctrl.inactive {
+            format!("cannot set {}: {} (inactive, check the matching auto control)", key, e)
+        } else {
+            format!("cannot set {}: {}", key, e)
+        }
+    })?;
+
+    // Drivers may clamp or round to `step`; report what was applied
+    let mut readback = V4l2Control { id: ctrl.id, value: 0 };
+    if unsafe { vidioc_g_ctrl(fd, &mut readback) }.is_ok() {
+        return Ok(readback.value);
+    }
+    Ok(c.value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sizes_in_range() {
+        // Continuous 160x120..1920x1080 in 1-pixel steps
+        let sizes = sizes_in_range(&[160, 1920, 1, 120, 1080, 1]);
+        assert!(sizes.contains(&(1920, 1080)));
+        assert!(sizes.contains(&(640, 480)));
+        assert!(!sizes.contains(&(3840, 2160)));
+        assert!(!sizes.contains(&(1920, 1440)));
+
+        // Stepwise in 16-pixel steps from 64x64: 800x600 is off the grid
+        let sizes = sizes_in_range(&[64, 1280, 16, 64, 720, 16]);
+        assert!(sizes.contains(&(1280, 720)));
+        assert!(sizes.contains(&(640, 480)));
+        assert!(!sizes.contains(&(800, 600)));
+    }
+
+    #[test]
+    fn test_parse_camera_id() {
+        assert_eq!(CameraId::parse("2"), CameraId::Index(2));
+        assert_eq!(
+            CameraId::parse("/dev/v4l/by-id/usb-Insta360_Link-video-index0"),
+            CameraId::Path(PathBuf::from("/dev/v4l/by-id/usb-Insta360_Link-video-index0"))
+        );
+        assert_eq!(
+            CameraId::parse(" usb-0000:00:14.0-2 "),
+            CameraId::Query("usb-0000:00:14.0-2".into())
+        );
+        assert_eq!(CameraId::parse("Insta360"), CameraId::Query("Insta360".into()));
+    }
+
+    fn menu_control() -> CameraControl {
+        CameraControl {
+            id: 0x009a0901,
+            key: "auto_exposure".into(),
+            label: "Auto Exposure".into(),
+            kind: ControlKind::Menu,
+            min: 0,
+            max: 3,
+            step: 1,
+            default: 3,
+            value: Some(3),
+            read_only: false,
+            inactive: false,
+            menu: vec![(1, "Manual Mode".into()), (3, "Aperture Priority Mode".into())],
+        }
+    }
+
+    #[test]
+    fn test_menu_indices() {
+        assert_eq!(menu_indices(0, 3), Some(0..=3));
+        assert_eq!(menu_indices(1, 256), Some(1..=256));
+        assert_eq!(menu_indices(0, 256), None);
+        assert_eq!(menu_indices(0, i32::MAX), None);
+        assert_eq!(menu_indices(-1, 3), None);
+        assert_eq!(menu_indices(3, 2), None);
+    }
+
+    #[test]
+    fn test_control_key() {
+        assert_eq!(control_key("Exposure Time, Absolute"), "exposure_time_absolute");
+        assert_eq!(control_key("White Balance, Automatic"), "white_balance_automatic");
+        assert_eq!(control_key("Gain"), "gain");
+        assert_eq!(control_key(" Power Line Frequency (50Hz) "), "power_line_frequency_50hz");
+    }
+
+    #[test]
+    fn test_parse_control_value() {
+        let menu = menu_control();
+        assert_eq!(menu.parse_value("1"), Ok(1));
+        assert_eq!(menu.parse_value("manual_mode"), Ok(1));
+        assert_eq!(menu.parse_value("Aperture Priority Mode"), Ok(3));
+        // 2 is in range but the driver doesn't offer it
+        assert!(menu.parse_value("2").is_err());
+
+        let boolean = CameraControl {
+            kind: ControlKind::Boolean,
+            min: 0,
+            max: 1,
+            menu: Vec::new(),
+            ..menu
+        };
+        assert_eq!(boolean.parse_value("on"), Ok(1));
+        assert_eq!(boolean.parse_value("false"), Ok(0));
+        assert!(boolean.parse_value("5").is_err());
+        assert!(boolean.parse_value("maybe").is_err());
+    }
+}
@@ -0,0 +1,164 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::time::Duration;
+
+/// Highest rate accepted, and the limit for sources that don't report one
+pub const MAX_FPS: FrameRate = FrameRate::from_int(240);
+
+/// Frames per second as an exact fraction, so NTSC rates such as 30000/1001
+/// (29.97) aren't rounded on their way from the camera to the control socket.
+/// Always reduced, with a non-zero numerator and denominator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FrameRate {
+    num: u32,
+    den: u32,
+}
+
+impl FrameRate {
+    /// A whole number of frames per second (0 is treated as 1).
+    pub const fn from_int(fps: u32) -> Self {
+        FrameRate {
+            num: if fps == 0 { 1 } else { fps },
+            den: 1,
+        }
+    }
+
+    /// `num / den` frames per second. None if either is zero.
+    pub fn new(num: u32, den: u32) -> Option<Self> {
+        if num == 0 || den == 0 {
+            return None;
+        }
+        let g = gcd(num, den);
+        Some(FrameRate {
+            num: num / g,
+            den: den / g,
+        })
+    }
+
+    /// From a V4L2 frame interval, which is seconds per frame.
+    pub fn from_interval(numerator: u32, denominator: u32) -> Option<Self> {
+        Self::new(denominator, numerator)
+    }
+
+    /// Parse `30`, `29.97`, `12.5` or `30000/1001`. Decimals that are an NTSC
+    /// rate (N * 1000/1001, e.g. 29.97, 59.94, 23.976) map to the exact fraction.
+    pub fn parse(s: &str) -> Option<Self> {
+        let s = s.trim();
+        if let Some((num, den)) = s.split_once('/') {
+            return Self::new(num.trim().parse().ok()?, den.trim().parse().ok()?);
+        }
+        let (int, frac) = match s.split_once('.') {
+            Some((int, frac)) => (int, frac),
+            None => (s, ""),
+        };
+        if frac.len() > 6 || !frac.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        let int: u64 = int.parse().ok()?;
+        let scale = 10u64.pow(frac.len() as u32);
+        let frac: u64 = if frac.is_empty() { 0 } else { frac.parse().ok()? };
+        let num = int * scale + frac;
+        if frac > 0 {
+            let ntsc = (num as f64 / scale as f64 * 1.001).round();
+            if ntsc >= 1.0 && (num as f64 / scale as f64 * 1.001 - ntsc).abs() < 0.005 {
+                return Self::new(u32::try_from(ntsc as u64 * 1000).ok()?, 1001);
+            }
+        }
+        let g = gcd64(num, scale);
+        Self::new(u32::try_from(num / g).ok()?, u32::try_from(scale / g).ok()?)
+    }
+
+    pub fn num(self) -> u32 {
+        self.num
+    }
+
+    pub fn den(self) -> u32 {
+        self.den
+    }
+
+    pub fn as_f64(self) -> f64 {
+        self.num as f64 / self.den as f64
+    }
+
+    /// Nearest whole rate, at least 1. For APIs that only take integer fps.
+    pub fn round(self) -> u32 {
+        ((self.num as u64 * 2 + self.den as u64) / (self.den as u64 * 2)).max(1) as u32
+    }
+
+    /// Time between frames
+    pub fn interval(self) -> Duration {
+        Duration::from_nanos(self.den as u64 * 1_000_000_000 / self.num as u64)
+    }
+}
+
+impl Ord for FrameRate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.num as u64 * other.den as u64).cmp(&(other.num as u64 * self.den as u64))
+    }
+}
+
+impl PartialOrd for FrameRate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for FrameRate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.den == 1 {
+            return write!(f, "{}", self.num);
+        }
+        // Up to three decimals without trailing zeros: 29.97, 12.5, 23.976
+        let s = format!("{:.3}", self.as_f64());
+        write!(f, "{}", s.trim_end_matches('0').trim_end_matches('.'))
+    }
+}
+
+fn gcd(a: u32, b: u32) -> u32 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+fn gcd64(a: u64, b: u64) -> u64 {
+    if b == 0 {
+        a
+    } else {
+        gcd64(b, a % b)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_frame_rate() {
+        assert_eq!(FrameRate::parse("30"), Some(FrameRate::from_int(30)));
+        assert_eq!(FrameRate::parse("30000/1001"), FrameRate::new(30000, 1001));
+        assert_eq!(FrameRate::parse("29.97"), FrameRate::new(30000, 1001));
+        assert_eq!(FrameRate::parse("59.94"), FrameRate::new(60000, 1001));
+        assert_eq!(FrameRate::parse("23.976"), FrameRate::new(24000, 1001));
+        assert_eq!(FrameRate::parse("12.5"), FrameRate::new(25, 2));
+        assert_eq!(FrameRate::parse("1.0"), Some(FrameRate::from_int(1)));
+        assert_eq!(FrameRate::parse("60/2"), Some(FrameRate::from_int(30)));
+        assert_eq!(FrameRate::parse("0"), None);
+        assert_eq!(FrameRate::parse("30/0"), None);
+        assert_eq!(FrameRate::parse("fast"), None);
+    }
+
+    #[test]
+    fn test_frame_rate_display_and_order() {
+        let ntsc = FrameRate::new(30000, 1001).unwrap();
+        assert_eq!(ntsc.to_string(), "29.97");
+        assert_eq!(FrameRate::new(24000, 1001).unwrap().to_string(), "23.976");
+        assert_eq!(FrameRate::from_int(60).to_string(), "60");
+        assert!(ntsc < FrameRate::from_int(30));
+        assert!(ntsc > FrameRate::from_int(29));
+        assert_eq!(ntsc.round(), 30);
+        assert_eq!(FrameRate::from_interval(1001, 30000), Some(ntsc));
+        assert_eq!(ntsc.interval(), Duration::from_nanos(33_366_666));
+    }
+}
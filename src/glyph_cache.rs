@@ -26,6 +26,15 @@ pub struct GlyphBitmap {
     pub glow: Option<GlowGlyph>,
 }
 
+/// Cell and font sizes, for outputs that lay the text out themselves
+#[derive(Debug, Clone, Copy)]
+pub struct FontMetrics {
+    pub cell_width: usize,
+    pub cell_height: usize,
+    pub font_size: f32,
+    pub ascent: f32,
+}
+
 pub struct GlyphCache {
     glyphs: HashMap<char, GlyphBitmap>,
     /// Uniform cell width (max across all glyphs)
@@ -34,6 +43,7 @@ pub struct GlyphCache {
     pub cell_height: usize,
     /// Font ascent in pixels (baseline to top of tallest glyph)
     pub ascent: f32,
+    pub font_size: f32,
 }
 
 /// Horizontally flip a coverage bitmap (row-by-row pixel reversal)
@@ -221,12 +231,22 @@ impl GlyphCache {
             cell_width,
             cell_height,
             ascent,
+            font_size,
         })
     }
 
     pub fn get(&self, ch: char) -> Option<&GlyphBitmap> {
         self.glyphs.get(&ch)
     }
+
+    pub fn metrics(&self) -> FontMetrics {
+        FontMetrics {
+            cell_width: self.cell_width,
+            cell_height: self.cell_height,
+            font_size: self.font_size,
+            ascent: self.ascent,
+        }
+    }
 }
 
 #[cfg(test)]
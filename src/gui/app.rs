@@ -1,5 +1,6 @@
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 use crossbeam_channel::{Receiver, Sender};
 use eframe::egui;
@@ -140,7 +141,7 @@ impl VirtualAsciiApp {
             let (resp_tx, _resp_rx) = crossbeam_channel::bounded(1);
             let _ = tx.try_send(CaptureCommand {
                 action: CaptureAction::ChangeFps {
-                    fps: self.state.fps,
+                    fps: self.state.frame_rate(),
                 },
                 response_tx: resp_tx,
             });
@@ -163,6 +164,7 @@ impl VirtualAsciiApp {
                     bg: self.state.bg_rgb(),
                     brightness_curve: self.state.brightness_curve(),
                     invert: self.state.invert,
+                    scale_mode: self.state.scale_mode,
                     theme_name: self.state.theme_name.clone(),
                 },
                 response_tx: resp_tx,
@@ -175,15 +177,19 @@ impl eframe::App for VirtualAsciiApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         self.poll_preview_frames(ctx);
         self.check_v4l2_results();
+        self.state.poll_hotplug();
         self.flush_settings();
 
         panels::settings_panel(ctx, self);
         panels::preview_panel(ctx, self);
         panels::status_bar(ctx, self);
 
-        // Keep repainting while pipeline is running
+        // Keep repainting while pipeline is running. Otherwise wake up now and
+        // then so hotplugged cameras show up without any input
         if self.state.pipeline_running {
             ctx.request_repaint();
+        } else {
+            ctx.request_repaint_after(Duration::from_secs(1));
         }
     }
 
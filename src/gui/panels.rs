@@ -1,5 +1,6 @@
 use eframe::egui;
 
+use crate::capture::CaptureFormat;
 use crate::config;
 
 use super::app::VirtualAsciiApp;
@@ -66,6 +67,23 @@ fn camera_section(ui: &mut egui::Ui, app: &mut VirtualAsciiApp) {
                 app.state.refresh_resolutions();
             }
 
+            // Capture format dropdown
+            let prev_format = app.state.capture_format;
+            egui::ComboBox::from_label("Capture Format")
+                .selected_text(app.state.capture_format.name())
+                .show_ui(ui, |ui| {
+                    for format in [CaptureFormat::Mjpeg, CaptureFormat::Yuyv, CaptureFormat::Nv12] {
+                        ui.selectable_value(&mut app.state.capture_format, format, format.name());
+                    }
+                });
+
+            if app.state.capture_format != prev_format {
+                app.state.refresh_resolutions();
+                if app.state.pipeline_running {
+                    app.change_camera(app.state.camera_index);
+                }
+            }
+
             // Resolution dropdown
             let res_text = app.state.available_resolutions[app.state.resolution_index].clone();
             let prev_res_index = app.state.resolution_index;
@@ -79,12 +97,16 @@ fn camera_section(ui: &mut egui::Ui, app: &mut VirtualAsciiApp) {
 
             if app.state.resolution_index != prev_res_index {
                 app.state.refresh_max_fps();
+                // The output and renderer follow the new size once frames arrive
+                if app.state.pipeline_running {
+                    app.change_camera(app.state.camera_index);
+                }
             }
 
             // FPS slider
             let mut fps = app.state.fps as i32;
             if ui
-                .add(egui::Slider::new(&mut fps, 1..=app.state.max_fps as i32).text("FPS"))
+                .add(egui::Slider::new(&mut fps, 1..=app.state.max_fps.round() as i32).text("FPS"))
                 .changed()
             {
                 app.state.fps = fps as u32;
@@ -184,6 +206,25 @@ fn appearance_section(ui: &mut egui::Ui, app: &mut VirtualAsciiApp) {
                 app.state.render_dirty = true;
                 app.state.last_change_time = Some(std::time::Instant::now());
             }
+
+            // Scale mode dropdown, for cameras whose shape differs from the output
+            let prev_scale_mode = app.state.scale_mode;
+            egui::ComboBox::from_label("Scale Mode")
+                .selected_text(app.state.scale_mode.name())
+                .show_ui(ui, |ui| {
+                    for mode in [
+                        config::ScaleMode::Fit,
+                        config::ScaleMode::Fill,
+                        config::ScaleMode::Stretch,
+                    ] {
+                        ui.selectable_value(&mut app.state.scale_mode, mode, mode.name());
+                    }
+                });
+
+            if app.state.scale_mode != prev_scale_mode {
+                app.state.render_dirty = true;
+                app.state.last_change_time = Some(std::time::Instant::now());
+            }
         });
 }
 
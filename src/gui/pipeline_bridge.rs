@@ -1,12 +1,16 @@
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
 use crossbeam_channel::bounded;
 
+use crate::capture::SourceSpec;
 use crate::config::definition_to_params;
 use crate::control::{CaptureAction, CaptureCommand};
+use crate::detect::CameraId;
 use crate::pipeline::Pipeline;
+use crate::output::V4l2Options;
 use crate::renderer::AsciiRenderer;
+use crate::sink::{SinkOptions, SinkSet, SinkSpec};
 
 use super::app::VirtualAsciiApp;
 use super::camera_check;
@@ -31,13 +35,18 @@ impl VirtualAsciiApp {
 
         // Probe camera resolution
         let resolution = self.state.resolution();
-        let (out_w, out_h) = crate::probe_camera_resolution(self.state.camera_index, resolution, self.state.fps)
-            .map_err(|e| format!("Camera probe failed: {}", e))?;
+        let (out_w, out_h) = crate::probe_camera_resolution(
+            self.state.camera_index,
+            resolution,
+            self.state.frame_rate(),
+            self.state.capture_format,
+        )
+        .map_err(|e| format!("Camera probe failed: {}", e))?;
 
         // Create renderer
         let (ascii_columns, charset) =
             definition_to_params(self.state.definition, &self.state.theme_name);
-        let renderer = AsciiRenderer::new(
+        let mut renderer = AsciiRenderer::new(
             &charset,
             self.state.fg_rgb(),
             self.state.bg_rgb(),
@@ -49,6 +58,7 @@ impl VirtualAsciiApp {
             &self.state.theme_name,
         )
         .map_err(|e| format!("Renderer init failed: {}", e))?;
+        renderer.set_scale_mode(self.state.scale_mode);
 
         // Create channels
         let (capture_cmd_tx, capture_cmd_rx) = bounded(4);
@@ -64,11 +74,18 @@ impl VirtualAsciiApp {
 
         // Start pipeline
         let pipeline = Pipeline::start(
-            self.state.camera_index,
+            SourceSpec::Webcam {
+                id: CameraId::Index(self.state.camera_index),
+                index: self.state.camera_index,
+                format: self.state.capture_format,
+            },
             resolution,
-            self.state.fps,
+            None,
+            self.state.frame_rate(),
             renderer,
-            None, // No v4l2 output initially
+            // No v4l2 output initially
+            Arc::new(Mutex::new(SinkSet::new(self.shutdown.clone(), self.state.frame_rate()))),
+            V4l2Options::default(),
             self.shutdown.clone(),
             capture_cmd_rx,
             render_cmd_rx,
@@ -129,17 +146,22 @@ impl VirtualAsciiApp {
             return Err("Virtual camera already active".into());
         }
 
-        let pipeline = self.pipeline.as_mut().ok_or("No pipeline")?;
+        let pipeline = self.pipeline.as_ref().ok_or("No pipeline")?;
 
-        let v4l2_output = crate::output::V4l2Output::new(
-            &self.state.output_device,
-            pipeline.output_width(),
-            pipeline.output_height(),
-        )
-        .map_err(|e| format!("V4L2 output failed: {}", e))?;
+        let spec = SinkSpec::V4l2(self.state.output_device.clone());
+        let (out_w, out_h) = pipeline.output_size();
+        let v4l2_output = spec
+            .open(out_w, out_h, V4l2Options::default())
+            .map_err(|e| format!("V4L2 output failed: {}", e))?;
 
         pipeline
-            .start_output(v4l2_output)
+            .attach_sink(
+                v4l2_output,
+                SinkOptions {
+                    critical: true,
+                    ..spec.default_options()
+                },
+            )
             .map_err(|e| format!("Start output failed: {}", e))?;
 
         self.state.v4l2_output_active = true;
@@ -149,8 +171,8 @@ impl VirtualAsciiApp {
 
     /// Stop v4l2 output without stopping capture+render
     pub fn stop_v4l2_output(&mut self) {
-        if let Some(ref mut pipeline) = self.pipeline {
-            pipeline.stop_output();
+        if let Some(ref pipeline) = self.pipeline {
+            pipeline.detach_sink(&self.state.output_device);
         }
         self.state.v4l2_output_active = false;
         self.state.status_message = "Virtual camera stopped".into();
@@ -163,7 +185,11 @@ impl VirtualAsciiApp {
             let (resp_tx, _resp_rx) = crossbeam_channel::bounded(1);
             let _ = tx.try_send(CaptureCommand {
                 action: CaptureAction::ChangeCamera {
-                    index: new_index,
+                    source: SourceSpec::Webcam {
+                        id: CameraId::Index(new_index),
+                        index: new_index,
+                        format: self.state.capture_format,
+                    },
                     resolution,
                 },
                 response_tx: resp_tx,
@@ -1,5 +1,8 @@
-use crate::config::{self, BrightnessCurve, ColorTheme, Rgb};
+use crate::capture::CaptureFormat;
+use crate::config::{self, BrightnessCurve, ColorTheme, Rgb, ScaleMode};
 use crate::detect;
+use crate::framerate::{FrameRate, MAX_FPS};
+use crate::hotplug::HotplugWatcher;
 
 #[derive(Clone, Copy, PartialEq)]
 pub enum ViewMode {
@@ -11,10 +14,12 @@ pub enum ViewMode {
 pub struct GuiState {
     // Camera settings
     pub camera_index: u32,
+    pub capture_format: CaptureFormat,
     pub resolution_index: usize,
     pub available_resolutions: Vec<String>,
+    /// Slider value. See `frame_rate` for what's sent to the pipeline
     pub fps: u32,
-    pub max_fps: u32,
+    pub max_fps: FrameRate,
 
     // Appearance settings
     pub theme_name: String,
@@ -23,6 +28,7 @@ pub struct GuiState {
     pub definition: u8,
     pub brightness_curve_name: String,
     pub invert: bool,
+    pub scale_mode: ScaleMode,
 
     // Output settings
     pub output_device: String,
@@ -32,6 +38,8 @@ pub struct GuiState {
     pub v4l2_output_active: bool,
     pub v4l2loopback_loaded: bool,
     pub detected_cameras: Vec<detect::CameraInfo>,
+    /// Keeps `detected_cameras` current as devices are plugged in and out
+    hotplug: Option<HotplugWatcher>,
     pub status_message: String,
     pub camera_conflict: Option<String>,
 
@@ -50,11 +58,15 @@ impl GuiState {
         let detected_cameras = detect::list_cameras("/dev/video20");
         let camera_index = detected_cameras.first().map(|c| c.index).unwrap_or(0);
 
-        let available_resolutions = Self::build_resolution_list(camera_index);
-        let max_fps = Self::detect_max_fps(camera_index, 0, &available_resolutions);
+        let capture_format = CaptureFormat::default();
+
+        let available_resolutions = Self::build_resolution_list(camera_index, capture_format);
+        let max_fps =
+            Self::detect_max_fps(camera_index, capture_format, 0, &available_resolutions);
 
         Self {
             camera_index,
+            capture_format,
             resolution_index: 0,
             available_resolutions,
             fps: 30,
@@ -65,11 +77,13 @@ impl GuiState {
             definition: 5,
             brightness_curve_name: "linear".into(),
             invert: false,
+            scale_mode: ScaleMode::default(),
             output_device: "/dev/video20".into(),
             pipeline_running: false,
             v4l2_output_active: false,
             v4l2loopback_loaded: false,
             detected_cameras,
+            hotplug: HotplugWatcher::new().ok(),
             status_message: "Ready".into(),
             camera_conflict: None,
             view_mode: ViewMode::SideBySide,
@@ -108,12 +122,39 @@ impl GuiState {
         self.detected_cameras = detect::list_cameras(&self.output_device);
     }
 
+    /// Refresh the camera list if video devices were added or removed since the
+    /// last call. A selected camera that just appeared gets its resolutions
+    /// re-queried; one that went away is replaced by the first camera left,
+    /// unless the pipeline is still trying to reconnect to it.
+    pub fn poll_hotplug(&mut self) {
+        match self.hotplug {
+            Some(ref watcher) if !watcher.pending().is_empty() => {}
+            _ => return,
+        }
+        let was_present = self.camera_present();
+        self.refresh_cameras();
+        if self.camera_present() {
+            if !was_present {
+                self.refresh_resolutions();
+            }
+        } else if !self.pipeline_running {
+            if let Some(first) = self.detected_cameras.first() {
+                self.camera_index = first.index;
+                self.refresh_resolutions();
+            }
+        }
+    }
+
+    fn camera_present(&self) -> bool {
+        self.detected_cameras.iter().any(|c| c.index == self.camera_index)
+    }
+
     /// Build the resolution dropdown list by querying V4L2 capabilities.
-    fn build_resolution_list(camera_index: u32) -> Vec<String> {
+    fn build_resolution_list(camera_index: u32, format: CaptureFormat) -> Vec<String> {
         let mut list = vec!["Auto".to_string()];
-        let resolutions = detect::list_resolutions(camera_index);
+        let resolutions = detect::list_resolutions(camera_index, format);
         for (w, h) in &resolutions {
-            let label = match detect::max_fps_for_resolution(camera_index, *w, *h) {
+            let label = match detect::max_fps_for_resolution(camera_index, format, *w, *h) {
                 Some(fps) => format!("{}x{} ({}fps)", w, h, fps),
                 None => format!("{}x{}", w, h),
             };
@@ -130,40 +171,56 @@ impl GuiState {
 
     /// Re-query resolutions when the camera changes.
     pub fn refresh_resolutions(&mut self) {
-        self.available_resolutions = Self::build_resolution_list(self.camera_index);
+        self.available_resolutions =
+            Self::build_resolution_list(self.camera_index, self.capture_format);
         self.resolution_index = 0; // Reset to "Auto"
         self.refresh_max_fps();
     }
 
+    /// The rate to run at. The slider is whole numbers only, so its top end
+    /// stands for the camera's exact maximum (e.g. 30 for 29.97).
+    pub fn frame_rate(&self) -> FrameRate {
+        FrameRate::from_int(self.fps).min(self.max_fps)
+    }
+
     /// Update max_fps based on the currently selected resolution.
     pub fn refresh_max_fps(&mut self) {
-        self.max_fps =
-            Self::detect_max_fps(self.camera_index, self.resolution_index, &self.available_resolutions);
-        if self.fps > self.max_fps {
-            self.fps = self.max_fps;
+        self.max_fps = Self::detect_max_fps(
+            self.camera_index,
+            self.capture_format,
+            self.resolution_index,
+            &self.available_resolutions,
+        );
+        if self.fps > self.max_fps.round() {
+            self.fps = self.max_fps.round();
         }
     }
 
     /// Detect max FPS for a given resolution selection.
     /// For "Auto" (index 0), returns the max across all resolutions.
-    /// Falls back to 240 if detection returns nothing.
-    fn detect_max_fps(camera_index: u32, resolution_index: usize, resolutions: &[String]) -> u32 {
+    /// Falls back to MAX_FPS if detection returns nothing.
+    fn detect_max_fps(
+        camera_index: u32,
+        format: CaptureFormat,
+        resolution_index: usize,
+        resolutions: &[String],
+    ) -> FrameRate {
         if resolution_index == 0 {
             // "Auto" mode: max FPS across all available resolutions
-            let all_res = detect::list_resolutions(camera_index);
+            let all_res = detect::list_resolutions(camera_index, format);
             let max = all_res
                 .iter()
-                .filter_map(|(w, h)| detect::max_fps_for_resolution(camera_index, *w, *h))
+                .filter_map(|(w, h)| detect::max_fps_for_resolution(camera_index, format, *w, *h))
                 .max();
-            max.unwrap_or(240)
+            max.unwrap_or(MAX_FPS)
         } else if let Some(text) = resolutions.get(resolution_index) {
             if let Ok((w, h)) = config::parse_resolution(text) {
-                detect::max_fps_for_resolution(camera_index, w, h).unwrap_or(240)
+                detect::max_fps_for_resolution(camera_index, format, w, h).unwrap_or(MAX_FPS)
             } else {
-                240
+                MAX_FPS
             }
         } else {
-            240
+            MAX_FPS
         }
     }
 }
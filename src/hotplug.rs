@@ -0,0 +1,82 @@
+use std::ffi::OsStr;
+use std::os::fd::AsFd;
+use std::time::Duration;
+
+use nix::poll::{poll, PollFd, PollFlags, PollTimeout};
+use nix::sys::inotify::{AddWatchFlags, InitFlags, Inotify};
+
+/// A /dev/videoN node appearing or going away
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeviceEvent {
+    Added(u32),
+    Removed(u32),
+}
+
+/// Watches /dev with inotify and reports video device nodes coming and going,
+/// so callers can react to a replug instead of polling every device.
+pub struct HotplugWatcher {
+    inotify: Inotify,
+}
+
+impl HotplugWatcher {
+    /// Fails if inotify is unavailable (e.g. watch limit reached). Callers
+    /// should fall back to timed retries.
+    pub fn new() -> nix::Result<Self> {
+        let inotify = Inotify::init(InitFlags::IN_NONBLOCK | InitFlags::IN_CLOEXEC)?;
+        // udev creates the node as root first and fixes its group afterwards, so
+        // IN_ATTRIB marks the point where the video group can actually open it
+        inotify.add_watch(
+            "/dev",
+            AddWatchFlags::IN_CREATE | AddWatchFlags::IN_DELETE | AddWatchFlags::IN_ATTRIB,
+        )?;
+        Ok(HotplugWatcher { inotify })
+    }
+
+    /// Block up to `timeout` for device events. Empty on timeout.
+    pub fn wait(&self, timeout: Duration) -> Vec<DeviceEvent> {
+        let mut fds = [PollFd::new(self.inotify.as_fd(), PollFlags::POLLIN)];
+        let timeout = PollTimeout::try_from(timeout).unwrap_or(PollTimeout::MAX);
+        match poll(&mut fds, timeout) {
+            Ok(n) if n > 0 => self.pending(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Drain queued device events without blocking.
+    pub fn pending(&self) -> Vec<DeviceEvent> {
+        let events = match self.inotify.read_events() {
+            Ok(events) => events,
+            Err(_) => return Vec::new(),
+        };
+        events
+            .iter()
+            .filter_map(|event| {
+                let index = video_index(event.name.as_deref()?)?;
+                if event.mask.contains(AddWatchFlags::IN_DELETE) {
+                    Some(DeviceEvent::Removed(index))
+                } else {
+                    Some(DeviceEvent::Added(index))
+                }
+            })
+            .collect()
+    }
+}
+
+/// The N of a `videoN` device node name.
+pub fn video_index(name: &OsStr) -> Option<u32> {
+    name.to_str()?.strip_prefix("video")?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_video_index() {
+        assert_eq!(video_index(OsStr::new("video0")), Some(0));
+        assert_eq!(video_index(OsStr::new("video42")), Some(42));
+        assert_eq!(video_index(OsStr::new("video")), None);
+        assert_eq!(video_index(OsStr::new("vbi0")), None);
+        assert_eq!(video_index(OsStr::new("video0.lock")), None);
+    }
+}
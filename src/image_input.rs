@@ -0,0 +1,146 @@
+use std::path::{Path, PathBuf};
+
+use image::imageops::{self, FilterType};
+use image::RgbImage;
+
+use crate::capture::{sequence_path, EndOfStream, FrameSource};
+
+/// A single image (re-emitted every frame) or a numbered image sequence
+/// (one file per frame, ending at the first missing number). Playback speed
+/// follows the capture thread's fps. Images are scaled to the requested
+/// resolution, or to the first image's size if none was given.
+pub struct ImageSource {
+    pattern: String,
+    looping: bool,
+    width: u32,
+    height: u32,
+    /// First and current sequence numbers; None for a still image
+    sequence: Option<(u32, u32)>,
+    started: bool,
+    rgb: Vec<u8>,
+}
+
+impl ImageSource {
+    pub fn open(
+        path: &Path,
+        looping: bool,
+        resolution: Option<(u32, u32)>,
+    ) -> anyhow::Result<Self> {
+        let pattern = path.to_string_lossy().into_owned();
+        let mut source = ImageSource {
+            pattern,
+            looping,
+            width: 0,
+            height: 0,
+            sequence: None,
+            started: false,
+            rgb: Vec::new(),
+        };
+
+        let first_path = if sequence_path(&source.pattern, 0).is_some() {
+            // Sequences conventionally start at 0 or 1
+            let first = if source.frame_path(0).exists() { 0 } else { 1 };
+            source.sequence = Some((first, first));
+            source.frame_path(first)
+        } else {
+            path.to_path_buf()
+        };
+
+        let image = load(&first_path)?;
+        (source.width, source.height) = resolution.unwrap_or(image.dimensions());
+        source.set_frame(image);
+        Ok(source)
+    }
+
+    fn frame_path(&self, index: u32) -> PathBuf {
+        PathBuf::from(sequence_path(&self.pattern, index).unwrap_or_default())
+    }
+
+    fn set_frame(&mut self, image: RgbImage) {
+        let image = if image.dimensions() != (self.width, self.height) {
+            imageops::resize(&image, self.width, self.height, FilterType::Triangle)
+        } else {
+            image
+        };
+        self.rgb = image.into_raw();
+    }
+
+    /// Load sequence image `index`, wrapping to the first when looping
+    fn load_index(&mut self, first: u32, mut index: u32) -> anyhow::Result<()> {
+        if !self.frame_path(index).exists() {
+            if !self.looping {
+                return Err(EndOfStream.into());
+            }
+            index = first;
+        }
+        let image = load(&self.frame_path(index))?;
+        self.set_frame(image);
+        self.sequence = Some((first, index));
+        Ok(())
+    }
+}
+
+fn load(path: &Path) -> anyhow::Result<RgbImage> {
+    let image = image::open(path)
+        .map_err(|e| anyhow::anyhow!("Cannot load image '{}': {}", path.display(), e))?;
+    Ok(image.to_rgb8())
+}
+
+impl FrameSource for ImageSource {
+    fn capture_frame(&mut self) -> anyhow::Result<Vec<u8>> {
+        // open() loads the first image; later calls step through the sequence
+        if self.started {
+            if let Some((first, current)) = self.sequence {
+                self.load_index(first, current + 1)?;
+            }
+        }
+        self.started = true;
+        Ok(self.rgb.clone())
+    }
+
+    fn resolution(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    fn stop_stream(&mut self) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sequence_path() {
+        assert_eq!(
+            sequence_path("frames/%05d.png", 42).as_deref(),
+            Some("frames/00042.png")
+        );
+        assert_eq!(sequence_path("img%d.png", 7).as_deref(), Some("img7.png"));
+        assert_eq!(sequence_path("photo.png", 1), None);
+        assert_eq!(sequence_path("100%.png", 1), None);
+    }
+
+    #[test]
+    fn test_sequence_plays_then_ends() {
+        let dir = std::env::temp_dir().join(format!("virtual-ascii-seq-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for (i, shade) in [(1, 10u8), (2, 20)] {
+            RgbImage::from_pixel(4, 2, image::Rgb([shade; 3]))
+                .save(dir.join(format!("f{:03}.png", i)))
+                .unwrap();
+        }
+        let pattern = dir.join("f%03d.png");
+
+        let mut source = ImageSource::open(&pattern, false, Some((2, 1))).unwrap();
+        assert_eq!(source.resolution(), (2, 1));
+        assert_eq!(source.capture_frame().unwrap(), vec![10; 6]);
+        assert_eq!(source.capture_frame().unwrap(), vec![20; 6]);
+        assert!(source.capture_frame().unwrap_err().is::<EndOfStream>());
+
+        let mut source = ImageSource::open(&pattern, true, None).unwrap();
+        let shades: Vec<u8> = (0..3).map(|_| source.capture_frame().unwrap()[0]).collect();
+        assert_eq!(shades, vec![10, 20, 10]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
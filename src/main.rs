@@ -1,31 +1,52 @@
 mod capture;
+mod cast;
 mod config;
 mod control;
+mod convert;
 mod detect;
+mod framerate;
 mod glyph_cache;
 #[cfg(feature = "gui")]
 mod gui;
+mod hotplug;
+#[cfg(feature = "images")]
+mod image_input;
 mod output;
+mod pattern;
 mod pipeline;
+mod pixfmt;
 mod rain;
+mod rawvideo;
 mod renderer;
+mod sink;
+mod snapshot;
+mod terminal;
+mod y4m;
 
 use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 
+use capture::SourceSpec;
 use clap::Parser;
-use config::{AppConfig, Cli, SetArgs, SubCommand};
+use config::{AppConfig, Cli, ControlsArgs, SetArgs, SnapshotArgs, SubCommand};
 use control::RuntimeState;
-use output::V4l2Output;
+use framerate::FrameRate;
+use output::{V4l2Options, V4l2Output};
+use sink::{OutputSink, SinkOptions, SinkSet, SinkSpec};
 use pipeline::Pipeline;
 use renderer::AsciiRenderer;
+use terminal::TerminalSink;
 
 fn main() -> anyhow::Result<()> {
     let cli = Cli::parse();
     match cli.command {
-        Some(SubCommand::Set(args)) => cmd_set(args),
+        Some(SubCommand::Set(args)) => cmd_set(*args),
         Some(SubCommand::Status) => cmd_status(),
+        Some(SubCommand::Controls(args)) => cmd_controls(args),
+        Some(SubCommand::Snapshot(args)) => cmd_snapshot(args),
+        Some(SubCommand::Convert(args)) => convert::run(*args),
         #[cfg(feature = "gui")]
         Some(SubCommand::Gui) => gui::run_gui(),
         None => cmd_run(cli),
@@ -56,22 +77,37 @@ fn cmd_run(cli: Cli) -> anyhow::Result<()> {
     );
     eprintln!("  Curve:      {:?}", config.brightness_curve);
     eprintln!("  FPS:        {}", config.fps);
-    let camera_name =
-        detect::device_name(config.camera_index).unwrap_or_else(|| "unknown".to_string());
-    eprintln!(
-        "  Camera:     /dev/video{} ({})",
-        config.camera_index, camera_name
-    );
-    eprintln!("  Output:     {}", config.output_device);
+    match config.source {
+        SourceSpec::Webcam { ref id, index, format } => {
+            let camera_name = detect::device_name(index).unwrap_or_else(|| "unknown".to_string());
+            eprintln!("  Camera:     /dev/video{} ({})", index, camera_name);
+            if !matches!(id, detect::CameraId::Index(_)) {
+                eprintln!("  Camera ID:  {} (re-resolved on reconnect)", id);
+            }
+            eprintln!("  Capture:    {}", format.name());
+        }
+        ref source => eprintln!("  Input:      {}", source),
+    }
+    match config.terminal {
+        Some(depth) => eprintln!("  Output:     terminal ({} colors)", depth.name()),
+        None => eprintln!("  Output:     {}", config.output_device),
+    }
+    for output in &config.extra_outputs {
+        eprintln!("  Also to:    {}", output);
+    }
 
     if let Some((w, h)) = config.resolution {
         eprintln!("  Resolution: {}x{} (user-specified)", w, h);
     }
 
-    let probe_res = probe_camera_resolution(config.camera_index, config.resolution, config.fps)?;
-    let (out_w, out_h) = probe_res;
-    let detected_max_fps =
-        detect::max_fps_for_resolution(config.camera_index, out_w, out_h);
+    let probe_res = probe_source_resolution(&config.source, config.resolution, config.fps)?;
+    let (src_w, src_h) = probe_res;
+    let detected_max_fps = match config.source {
+        SourceSpec::Webcam { index, format, .. } => {
+            detect::max_fps_for_resolution(index, format, src_w, src_h)
+        }
+        _ => None,
+    };
     if let Some(max_fps) = detected_max_fps {
         if config.fps > max_fps {
             eprintln!(
@@ -81,52 +117,150 @@ fn cmd_run(cli: Cli) -> anyhow::Result<()> {
             config.fps = max_fps;
         }
     }
-    eprintln!("  Source:     {}x{}", out_w, out_h);
+    eprintln!("  Source:     {}x{}", src_w, src_h);
     if let Some(max_fps) = detected_max_fps {
         eprintln!("  Max FPS:    {} (detected)", max_fps);
     }
 
-    let v4l2_output = V4l2Output::new(&config.output_device, out_w, out_h)?;
-    let (negotiated_w, negotiated_h) = v4l2_output.resolution();
-    eprintln!("  V4L2 out:   {}x{}", negotiated_w, negotiated_h);
+    // The output follows the camera unless sized explicitly; either way the
+    // renderer is built for whatever size the loopback device settles on
+    let (out_w, out_h) = config.output_size.unwrap_or(probe_res);
+    if config.output_size.is_some() {
+        eprintln!("  Out size:   {}x{} ({})", out_w, out_h, config.scale_mode.name());
+    }
+
+    // Create command channels. The terminal output sends its own resize
+    // requests to the render thread.
+    let (capture_cmd_tx, capture_cmd_rx) = crossbeam_channel::bounded(4);
+    let (render_cmd_tx, render_cmd_rx) = crossbeam_channel::bounded(4);
+
+    let main_spec = if config.output_device == "-" {
+        SinkSpec::Stdout { y4m: config.output_y4m }
+    } else {
+        SinkSpec::V4l2(config.output_device.clone())
+    };
+    let (main_output, negotiated_w, negotiated_h, output_options): (Box<dyn OutputSink>, _, _, _) =
+        match (config.terminal, &main_spec) {
+            // The terminal sizes the grid, and the renderer is fitted to it below
+            (Some(depth), _) => {
+                let sink = TerminalSink::new(depth, render_cmd_tx.clone())?;
+                (Box::new(sink), out_w, out_h, config.output_options)
+            }
+            (None, SinkSpec::V4l2(device)) => {
+                let v4l2_output = V4l2Output::new(device, out_w, out_h, config.output_options)?;
+                let (negotiated_w, negotiated_h) = v4l2_output.resolution();
+                let output_options = V4l2Options {
+                    io: v4l2_output.io(),
+                    ..config.output_options
+                };
+                eprintln!(
+                    "  V4L2 out:   {}x{} {} via {}",
+                    negotiated_w,
+                    negotiated_h,
+                    output_options.encoding,
+                    output_options.io.name()
+                );
+                (Box::new(v4l2_output), negotiated_w, negotiated_h, output_options)
+            }
+            (None, _) => {
+                let container = if config.output_y4m { "y4m" } else { "raw" };
+                eprintln!(
+                    "  Stdout:     {}x{} {} ({})",
+                    out_w, out_h, config.output_options.encoding, container
+                );
+                let sink = main_spec.open(out_w, out_h, config.output_options)?;
+                (sink, out_w, out_h, config.output_options)
+            }
+        };
 
-    let ascii_renderer = AsciiRenderer::new(
+    let terminal_grid = config.terminal.map(|_| terminal::terminal_size());
+    let mut ascii_renderer = AsciiRenderer::new(
         &config.charset,
         config.theme.fg,
         config.theme.bg,
         config.brightness_curve,
         config.invert,
-        negotiated_w,
+        terminal_grid.map_or(negotiated_w, |(columns, _)| columns * terminal::CELL_WIDTH),
         negotiated_h,
-        config.ascii_columns,
+        terminal_grid.map_or(config.ascii_columns, |(columns, _)| columns),
         &config.theme.name,
     )
     .map_err(|e| anyhow::anyhow!("Renderer init failed: {}", e))?;
+    ascii_renderer.set_scale_mode(config.scale_mode);
+    if let Some((columns, rows)) = terminal_grid {
+        ascii_renderer = ascii_renderer.with_grid(columns, rows);
+        eprintln!(
+            "  Terminal:   {}x{} cells ({}x{} for other outputs)",
+            columns, rows, ascii_renderer.output_width, ascii_renderer.output_height
+        );
+    }
+    // Other outputs get frames at the renderer's size, which for the terminal
+    // follows the grid rather than the source
+    let (frame_w, frame_h) = (ascii_renderer.output_width, ascii_renderer.output_height);
+
+    // The output the instance was started for is critical: if it fails the
+    // pipeline stops. Extra outputs only drop themselves.
+    let mut outputs: Vec<(Box<dyn OutputSink>, SinkOptions)> = vec![(
+        main_output,
+        SinkOptions {
+            critical: true,
+            ..main_spec.default_options()
+        },
+    )];
+    for spec in &config.extra_outputs {
+        let sink = spec
+            .open(frame_w, frame_h, config.output_options)
+            .map_err(|e| anyhow::anyhow!("Cannot open output {}: {}", spec, e))?;
+        outputs.push((sink, spec.default_options()));
+    }
+    let recording = match config.record {
+        Some(ref spec) => {
+            let sink = spec
+                .open(frame_w, frame_h, config.output_options)
+                .map_err(|e| anyhow::anyhow!("Cannot start recording: {}", e))?;
+            let name = sink.name();
+            eprintln!("  Recording:  {}", name);
+            outputs.push((sink, spec.default_options()));
+            Some(name)
+        }
+        None => None,
+    };
 
     // Set up shutdown signal
     let shutdown = Arc::new(AtomicBool::new(false));
     let shutdown_ctrlc = shutdown.clone();
     ctrlc::set_handler(move || {
+        terminal::restore_terminal();
         eprintln!("\nShutting down...");
         shutdown_ctrlc.store(true, Ordering::SeqCst);
     })?;
 
-    // Create command channels
-    let (capture_cmd_tx, capture_cmd_rx) = crossbeam_channel::bounded(4);
-    let (render_cmd_tx, render_cmd_rx) = crossbeam_channel::bounded(4);
+    // Attach outputs before the control socket starts, so it can report on them
+    let output_names = outputs.iter().map(|(sink, _)| sink.name()).collect();
+    let mut sink_set = SinkSet::new(shutdown.clone(), config.fps);
+    for (sink, options) in outputs {
+        sink_set.attach(sink, options)?;
+    }
+    let sinks = Arc::new(Mutex::new(sink_set));
 
     // Initialize runtime state
     let state = Arc::new(Mutex::new(RuntimeState {
-        camera_index: config.camera_index,
+        source: config.source.clone(),
         resolution: config.resolution,
         fps: config.fps,
-        max_fps: detected_max_fps.unwrap_or(240),
+        max_fps: detected_max_fps.unwrap_or(framerate::MAX_FPS),
         theme_name: config.theme.name.clone(),
         fg: config.theme.fg,
         bg: config.theme.bg,
         definition: config.definition,
         brightness_curve: config.brightness_curve,
         invert: config.invert,
+        scale_mode: config.scale_mode,
+        controls: Vec::new(),
+        outputs: output_names,
+        output_options,
+        recording,
+        recording_path: config.record.as_ref().and_then(SinkSpec::path).map(Path::to_path_buf),
     }));
 
     // Start control socket listener
@@ -134,6 +268,7 @@ fn cmd_run(cli: Cli) -> anyhow::Result<()> {
         state.clone(),
         capture_cmd_tx,
         render_cmd_tx,
+        sinks.clone(),
         shutdown.clone(),
     ) {
         Ok(_handle) => {
@@ -154,11 +289,14 @@ fn cmd_run(cli: Cli) -> anyhow::Result<()> {
     eprintln!("  Press Ctrl+C to stop");
 
     let pipeline = Pipeline::start(
-        config.camera_index,
+        config.source,
         config.resolution,
+        // A terminal grid doesn't follow the source
+        terminal_grid.map_or(config.output_size, |_| Some((frame_w, frame_h))),
         config.fps,
         ascii_renderer,
-        Some(v4l2_output),
+        sinks,
+        config.output_options,
         shutdown.clone(),
         capture_cmd_rx,
         render_cmd_rx,
@@ -169,29 +307,40 @@ fn cmd_run(cli: Cli) -> anyhow::Result<()> {
     )?;
 
     pipeline.wait();
+    terminal::restore_terminal();
     eprintln!("Shutdown complete.");
 
     Ok(())
 }
 
 fn cmd_set(args: SetArgs) -> anyhow::Result<()> {
-    let mut stream = control::connect_abstract_stream().map_err(|e| {
-        anyhow::anyhow!(
-            "Cannot connect to virtual-ascii: {}.\nIs virtual-ascii running?",
-            e
-        )
-    })?;
-
     // Build SET commands from args
     let mut lines = String::new();
     if let Some(i) = args.camera_index {
         lines.push_str(&format!("SET camera_index={}\n", i));
     }
+    if let Some(ref camera) = args.camera {
+        lines.push_str(&format!("SET camera={}\n", camera));
+    }
+    if let Some(ref f) = args.capture_format {
+        lines.push_str(&format!("SET capture_format={}\n", f));
+    }
+    if let Some(ref input) = args.input {
+        // The running instance may have a different working directory
+        let path = Path::new(input);
+        if path.is_relative() && !input.starts_with("pattern:") {
+            let abs = std::env::current_dir()?.join(path);
+            lines.push_str(&format!("SET input={}\n", abs.display()));
+        } else {
+            lines.push_str(&format!("SET input={}\n", input));
+        }
+    }
     if let Some((w, h)) = args.resolution {
         lines.push_str(&format!("SET resolution={}x{}\n", w, h));
     }
     if let Some(f) = args.fps {
-        lines.push_str(&format!("SET fps={}\n", f));
+        // Send the exact fraction so e.g. 30000/1001 isn't rounded through "29.97"
+        lines.push_str(&format!("SET fps={}/{}\n", f.num(), f.den()));
     }
     if let Some(ref t) = args.theme {
         lines.push_str(&format!("SET theme={}\n", t));
@@ -211,11 +360,72 @@ fn cmd_set(args: SetArgs) -> anyhow::Result<()> {
     if let Some(v) = args.invert {
         lines.push_str(&format!("SET invert={}\n", v));
     }
+    if let Some(ref m) = args.scale_mode {
+        lines.push_str(&format!("SET scale_mode={}\n", m));
+    }
+    for ctrl in &args.ctrl {
+        let (name, value) = ctrl
+            .split_once('=')
+            .ok_or_else(|| anyhow::anyhow!("Invalid --ctrl '{}': expected NAME=VALUE", ctrl))?;
+        lines.push_str(&format!("SET ctrl.{}={}\n", name.trim(), value.trim()));
+    }
+    for name in &args.remove_output {
+        lines.push_str(&format!("OUTPUT REMOVE {}\n", name));
+    }
+    if args.stop_recording {
+        lines.push_str("RECORD STOP\n");
+    }
+    if let Some(ref path) = args.record {
+        // The running instance may have a different working directory
+        let abs = std::env::current_dir()?.join(path);
+        lines.push_str(&format!("RECORD START {}\n", abs.display()));
+    }
+    if let Some(ref path) = args.record_cast {
+        let abs = std::env::current_dir()?.join(path);
+        lines.push_str(&format!("RECORD CAST {}\n", abs.display()));
+    }
+    for output in &args.add_output {
+        let spec = SinkSpec::parse(output).map_err(|e| anyhow::anyhow!(e))?;
+        match spec {
+            SinkSpec::File(path) | SinkSpec::Cast(path) if path.is_relative() => {
+                let abs = std::env::current_dir()?.join(path);
+                lines.push_str(&format!("OUTPUT ADD {}\n", abs.display()));
+            }
+            _ => lines.push_str(&format!("OUTPUT ADD {}\n", output)),
+        }
+    }
 
     if lines.is_empty() {
         eprintln!("No settings specified. Use --help for options.");
         return Ok(());
     }
+    send_commands(&lines)
+}
+
+fn cmd_snapshot(args: SnapshotArgs) -> anyhow::Result<()> {
+    let format = args
+        .format
+        .or_else(|| snapshot::SnapshotFormat::from_path(&args.path))
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "Cannot tell the snapshot format of '{}': use a .txt, .ans, .html, .svg or .png name, or --format",
+                args.path.display()
+            )
+        })?;
+    // The running instance may have a different working directory
+    let abs = std::env::current_dir()?.join(&args.path);
+    send_commands(&format!("SNAPSHOT {} {}\n", format.name(), abs.display()))
+}
+
+/// Send control commands to the running instance and print its answers,
+/// exiting with status 1 if any failed
+fn send_commands(lines: &str) -> anyhow::Result<()> {
+    let mut stream = control::connect_abstract_stream().map_err(|e| {
+        anyhow::anyhow!(
+            "Cannot connect to virtual-ascii: {}.\nIs virtual-ascii running?",
+            e
+        )
+    })?;
 
     stream.write_all(lines.as_bytes())?;
     // Shut down write side so the server sees EOF
@@ -275,18 +485,83 @@ fn cmd_status() -> anyhow::Result<()> {
     Ok(())
 }
 
+fn cmd_controls(args: ControlsArgs) -> anyhow::Result<()> {
+    let index = match (args.camera_index, &args.camera) {
+        (Some(i), _) => i,
+        (None, Some(camera)) => detect::resolve_camera(&detect::CameraId::parse(camera))
+            .ok_or_else(|| anyhow::anyhow!("No camera matching '{}' found", camera))?,
+        (None, None) => detect::detect_camera("/dev/video20")
+            .ok_or_else(|| anyhow::anyhow!("No webcam found. Use --camera-index to pick one."))?,
+    };
+    let controls = detect::list_controls(index);
+    if controls.is_empty() {
+        anyhow::bail!("/dev/video{} has no controls (or could not be opened)", index);
+    }
+
+    let name = detect::device_name(index).unwrap_or_else(|| "unknown".to_string());
+    println!("/dev/video{} ({})", index, name);
+    for c in &controls {
+        let mut line = format!("{:>32} ({:<7}):", c.key, c.kind.name());
+        match c.kind {
+            detect::ControlKind::Boolean => {
+                line.push_str(&format!(" default={}", c.default));
+            }
+            detect::ControlKind::Button => {}
+            _ => {
+                line.push_str(&format!(
+                    " min={} max={} step={} default={}",
+                    c.min, c.max, c.step, c.default
+                ));
+            }
+        }
+        if let Some(v) = c.value {
+            line.push_str(&format!(" value={}", v));
+        }
+        if c.read_only {
+            line.push_str(" flags=read-only");
+        } else if c.inactive {
+            line.push_str(" flags=inactive");
+        }
+        println!("{}", line);
+        for (i, label) in &c.menu {
+            println!("{:>38}: {}", i, label);
+        }
+    }
+    Ok(())
+}
+
+/// Quick probe to get the source resolution without keeping it open
+fn probe_source_resolution(
+    source: &SourceSpec,
+    resolution: Option<(u32, u32)>,
+    fps: FrameRate,
+) -> anyhow::Result<(u32, u32)> {
+    match source {
+        SourceSpec::Webcam { index, format, .. } => {
+            probe_camera_resolution(*index, resolution, fps, *format)
+        }
+        _ => {
+            let mut opened = source.open(resolution, fps)?;
+            let res = opened.resolution();
+            opened.stop_stream();
+            Ok(res)
+        }
+    }
+}
+
 /// Quick probe to get camera resolution without keeping it open
 pub fn probe_camera_resolution(
     camera_index: u32,
     resolution: Option<(u32, u32)>,
-    fps: u32,
+    fps: FrameRate,
+    format: capture::CaptureFormat,
 ) -> anyhow::Result<(u32, u32)> {
     use nokhwa::utils::CameraIndex;
     use nokhwa::Camera;
 
     let index = CameraIndex::Index(camera_index);
-    let format = capture::requested_format(resolution, fps);
-    let camera = Camera::new(index, format).map_err(|e| {
+    let requested = capture::requested_format(resolution, fps, format);
+    let camera = Camera::new(index, requested).map_err(|e| {
         let base = format!(
             "Cannot open camera index {}: {}.\n\
              Hint: Check that a webcam is connected and you have permission.",
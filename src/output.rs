@@ -1,17 +1,68 @@
 use std::fs::{File, OpenOptions};
 use std::io::Write;
-use std::os::unix::io::AsRawFd;
+use std::os::unix::io::{AsRawFd, RawFd};
 use std::path::Path;
 
+use nix::errno::Errno;
+
+use crate::framerate::FrameRate;
+use crate::pixfmt::{self, OutputEncoding, OutputFormat, YuvMatrix};
+use crate::sink::{OutputSink, RenderedFrame};
+
 // V4L2 constants
 const V4L2_BUF_TYPE_VIDEO_OUTPUT: u32 = 2;
 const V4L2_PIX_FMT_RGB24: u32 = fourcc(b'R', b'G', b'B', b'3');
+const V4L2_PIX_FMT_BGR24: u32 = fourcc(b'B', b'G', b'R', b'3');
+const V4L2_PIX_FMT_YUYV: u32 = fourcc(b'Y', b'U', b'Y', b'V');
+const V4L2_PIX_FMT_YUV420: u32 = fourcc(b'Y', b'U', b'1', b'2');
+const V4L2_PIX_FMT_NV12: u32 = fourcc(b'N', b'V', b'1', b'2');
 const V4L2_FIELD_NONE: u32 = 1;
+const V4L2_COLORSPACE_SMPTE170M: u32 = 1;
+const V4L2_COLORSPACE_REC709: u32 = 3;
+const V4L2_COLORSPACE_SRGB: u32 = 8;
+const V4L2_YCBCR_ENC_601: u32 = 1;
+const V4L2_YCBCR_ENC_709: u32 = 2;
+const V4L2_QUANTIZATION_FULL_RANGE: u32 = 1;
+const V4L2_QUANTIZATION_LIM_RANGE: u32 = 2;
+const V4L2_MEMORY_MMAP: u32 = 1;
+const V4L2_BUF_FLAG_TIMESTAMP_COPY: u32 = 0x4000;
+
+/// Device buffers requested for streaming I/O: one being filled, one queued,
+/// one with the consumer
+const MMAP_BUFFERS: u32 = 3;
 
 const fn fourcc(a: u8, b: u8, c: u8, d: u8) -> u32 {
     (a as u32) | ((b as u32) << 8) | ((c as u32) << 16) | ((d as u32) << 24)
 }
 
+fn pix_fmt(format: OutputFormat) -> u32 {
+    match format {
+        OutputFormat::Rgb24 => V4L2_PIX_FMT_RGB24,
+        OutputFormat::Bgr24 => V4L2_PIX_FMT_BGR24,
+        OutputFormat::Yuyv => V4L2_PIX_FMT_YUYV,
+        OutputFormat::I420 => V4L2_PIX_FMT_YUV420,
+        OutputFormat::Nv12 => V4L2_PIX_FMT_NV12,
+    }
+}
+
+/// Colorspace, YCbCr encoding and quantization fields for the format, so
+/// consumers that honour them convert back with the same matrix and range
+fn colorimetry(encoding: &OutputEncoding) -> (u32, u32, u32) {
+    if !encoding.format.is_yuv() {
+        return (V4L2_COLORSPACE_SRGB, 0, V4L2_QUANTIZATION_FULL_RANGE);
+    }
+    let (colorspace, ycbcr_enc) = match encoding.matrix {
+        YuvMatrix::Bt601 => (V4L2_COLORSPACE_SMPTE170M, V4L2_YCBCR_ENC_601),
+        YuvMatrix::Bt709 => (V4L2_COLORSPACE_REC709, V4L2_YCBCR_ENC_709),
+    };
+    let quantization = if encoding.full_range {
+        V4L2_QUANTIZATION_FULL_RANGE
+    } else {
+        V4L2_QUANTIZATION_LIM_RANGE
+    };
+    (colorspace, ycbcr_enc, quantization)
+}
+
 // V4L2 format structs (minimal subset for our needs)
 #[repr(C)]
 #[derive(Copy, Clone)]
@@ -47,19 +98,285 @@ struct V4l2Format {
 // Verify struct matches kernel layout (208 bytes on x86_64)
 const _: () = assert!(std::mem::size_of::<V4l2Format>() == 208);
 
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct V4l2RequestBuffers {
+    count: u32,
+    type_: u32,
+    memory: u32,
+    capabilities: u32,
+    flags: u8,
+    reserved: [u8; 3],
+}
+
+const _: () = assert!(std::mem::size_of::<V4l2RequestBuffers>() == 20);
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct V4l2Buffer {
+    index: u32,
+    type_: u32,
+    bytesused: u32,
+    flags: u32,
+    field: u32,
+    timestamp: libc::timeval,
+    // struct v4l2_timecode, unused
+    timecode: [u32; 4],
+    sequence: u32,
+    memory: u32,
+    // union { offset, userptr, planes, fd }; only the mmap offset is used
+    m: libc::c_ulong,
+    length: u32,
+    reserved2: u32,
+    request_fd: u32,
+}
+
+// Verify struct matches kernel layout (88 bytes on x86_64)
+const _: () = assert!(std::mem::size_of::<V4l2Buffer>() == 88);
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct V4l2StreamParm {
+    type_: u32,
+    // struct v4l2_outputparm, the OUTPUT member of the parm union
+    capability: u32,
+    outputmode: u32,
+    /// Seconds per frame as numerator, denominator
+    timeperframe: [u32; 2],
+    extendedmode: u32,
+    writebuffers: u32,
+    reserved: [u32; 4],
+    // Remaining bytes of the 200-byte parm union
+    _padding: [u8; 200 - 40],
+}
+
+const _: () = assert!(std::mem::size_of::<V4l2StreamParm>() == 204);
+
+impl V4l2Buffer {
+    fn mmap_output(index: u32) -> Self {
+        let mut buf: V4l2Buffer = unsafe { std::mem::zeroed() };
+        buf.index = index;
+        buf.type_ = V4L2_BUF_TYPE_VIDEO_OUTPUT;
+        buf.memory = V4L2_MEMORY_MMAP;
+        buf
+    }
+}
+
 // Generate ioctl wrapper using nix macros
 // VIDIOC_S_FMT = _IOWR('V', 5, struct v4l2_format)
 nix::ioctl_readwrite!(vidioc_s_fmt, b'V', 5, V4l2Format);
+nix::ioctl_readwrite!(vidioc_reqbufs, b'V', 8, V4l2RequestBuffers);
+nix::ioctl_readwrite!(vidioc_querybuf, b'V', 9, V4l2Buffer);
+nix::ioctl_readwrite!(vidioc_qbuf, b'V', 15, V4l2Buffer);
+nix::ioctl_readwrite!(vidioc_dqbuf, b'V', 17, V4l2Buffer);
+nix::ioctl_readwrite!(vidioc_g_parm, b'V', 21, V4l2StreamParm);
+nix::ioctl_readwrite!(vidioc_s_parm, b'V', 22, V4l2StreamParm);
+nix::ioctl_write_ptr!(vidioc_streamon, b'V', 18, libc::c_int);
+nix::ioctl_write_ptr!(vidioc_streamoff, b'V', 19, libc::c_int);
+
+/// How frames are handed to the loopback device
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputIo {
+    /// write() each frame, copying it into the driver
+    #[default]
+    Write,
+    /// Streaming I/O with mmap'd device buffers (REQBUFS/QBUF/DQBUF)
+    Mmap,
+}
+
+impl OutputIo {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "write" => Some(Self::Write),
+            "mmap" => Some(Self::Mmap),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Write => "write",
+            Self::Mmap => "mmap",
+        }
+    }
+}
+
+/// Settings for loopback outputs, shared by the main output device and any
+/// added at runtime
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct V4l2Options {
+    pub encoding: OutputEncoding,
+    pub io: OutputIo,
+}
+
+struct MappedBuffer {
+    ptr: *mut u8,
+    len: usize,
+}
+
+/// Device buffers mapped into our address space. Frames are converted
+/// straight into a free buffer and queued, rather than copied through write().
+struct MmapStream {
+    fd: RawFd,
+    buffers: Vec<MappedBuffer>,
+    /// Indices of buffers we own, i.e. not queued on the device
+    free: Vec<u32>,
+    streaming: bool,
+    sequence: u32,
+}
+
+// The mappings are only touched by the thread that owns the stream
+unsafe impl Send for MmapStream {}
+
+impl MmapStream {
+    fn start(fd: RawFd, frame_size: usize) -> nix::Result<Self> {
+        let mut req = V4l2RequestBuffers {
+            count: MMAP_BUFFERS,
+            type_: V4L2_BUF_TYPE_VIDEO_OUTPUT,
+            memory: V4L2_MEMORY_MMAP,
+            capabilities: 0,
+            flags: 0,
+            reserved: [0; 3],
+        };
+        unsafe { vidioc_reqbufs(fd, &mut req)? };
+        if req.count == 0 {
+            return Err(Errno::ENOMEM);
+        }
+
+        // If mapping fails part way, dropping `stream` releases what was mapped
+        let mut stream = MmapStream {
+            fd,
+            buffers: Vec::new(),
+            free: Vec::new(),
+            streaming: false,
+            sequence: 0,
+        };
+        for index in 0..req.count {
+            let mut buf = V4l2Buffer::mmap_output(index);
+            unsafe { vidioc_querybuf(fd, &mut buf)? };
+            let len = buf.length as usize;
+            if len < frame_size {
+                return Err(Errno::EINVAL);
+            }
+            let ptr = unsafe {
+                libc::mmap(
+                    std::ptr::null_mut(),
+                    len,
+                    libc::PROT_READ | libc::PROT_WRITE,
+                    libc::MAP_SHARED,
+                    fd,
+                    buf.m as libc::off_t,
+                )
+            };
+            if ptr == libc::MAP_FAILED {
+                return Err(Errno::last());
+            }
+            stream.buffers.push(MappedBuffer {
+                ptr: ptr as *mut u8,
+                len,
+            });
+            stream.free.push(index);
+        }
+        Ok(stream)
+    }
+
+    /// Fill a buffer through `fill` and queue it with a timestamp and sequence
+    /// number. When every buffer is queued, the oldest is reclaimed first.
+    fn queue(&mut self, bytesused: usize, fill: impl FnOnce(&mut [u8])) -> nix::Result<()> {
+        let index = match self.free.pop() {
+            Some(index) => index,
+            None => {
+                let mut buf = V4l2Buffer::mmap_output(0);
+                unsafe { vidioc_dqbuf(self.fd, &mut buf)? };
+                buf.index
+            }
+        };
+        let mapped = &self.buffers[index as usize];
+        let data = unsafe { std::slice::from_raw_parts_mut(mapped.ptr, mapped.len) };
+        fill(&mut data[..bytesused]);
+
+        let mut buf = V4l2Buffer::mmap_output(index);
+        buf.bytesused = bytesused as u32;
+        buf.field = V4L2_FIELD_NONE;
+        buf.flags = V4L2_BUF_FLAG_TIMESTAMP_COPY;
+        buf.timestamp = monotonic_now();
+        buf.sequence = self.sequence;
+        if let Err(e) = unsafe { vidioc_qbuf(self.fd, &mut buf) } {
+            self.free.push(index);
+            return Err(e);
+        }
+        self.sequence = self.sequence.wrapping_add(1);
+
+        if !self.streaming {
+            let buf_type = V4L2_BUF_TYPE_VIDEO_OUTPUT as libc::c_int;
+            unsafe { vidioc_streamon(self.fd, &buf_type)? };
+            self.streaming = true;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for MmapStream {
+    fn drop(&mut self) {
+        if self.streaming {
+            let buf_type = V4L2_BUF_TYPE_VIDEO_OUTPUT as libc::c_int;
+            let _ = unsafe { vidioc_streamoff(self.fd, &buf_type) };
+        }
+        for mapped in &self.buffers {
+            unsafe { libc::munmap(mapped.ptr as *mut libc::c_void, mapped.len) };
+        }
+        let mut req = V4l2RequestBuffers {
+            count: 0,
+            type_: V4L2_BUF_TYPE_VIDEO_OUTPUT,
+            memory: V4L2_MEMORY_MMAP,
+            capabilities: 0,
+            flags: 0,
+            reserved: [0; 3],
+        };
+        let _ = unsafe { vidioc_reqbufs(self.fd, &mut req) };
+    }
+}
+
+/// CLOCK_MONOTONIC as a timeval, the clock V4L2 buffer timestamps use
+fn monotonic_now() -> libc::timeval {
+    let mut ts = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut ts) };
+    libc::timeval {
+        tv_sec: ts.tv_sec,
+        tv_usec: ts.tv_nsec / 1000,
+    }
+}
 
 pub struct V4l2Output {
+    device: String,
+    /// Declared before `file` so the buffers are released before the fd closes
+    stream: Option<MmapStream>,
     file: File,
     width: u32,
     height: u32,
     frame_size: usize,
+    /// Bytes between rows of the first plane, which the driver may pad
+    stride: usize,
+    encoding: OutputEncoding,
+    /// Reused buffer for frames converted out of RGB24 on the write() path
+    converted: Vec<u8>,
+    /// Reused buffer for encoded frames before their rows are padded to `stride`
+    packed: Vec<u8>,
+    /// Reused buffer for frames rendered at a size the device didn't accept
+    scaled: Vec<u8>,
 }
 
 impl V4l2Output {
-    pub fn new(device_path: &str, width: u32, height: u32) -> anyhow::Result<Self> {
+    pub fn new(
+        device_path: &str,
+        width: u32,
+        height: u32,
+        options: V4l2Options,
+    ) -> anyhow::Result<Self> {
+        let encoding = options.encoding;
         let path = Path::new(device_path);
 
         if !path.exists() {
@@ -71,85 +388,95 @@ impl V4l2Output {
             ));
         }
 
-        let file = OpenOptions::new()
-            .read(true)
-            .write(true)
-            .open(device_path)
-            .map_err(|e| {
-                anyhow::anyhow!(
-                    "Cannot open '{}': {}.\n\
-                     Hint: Check permissions. You may need to add your user to the 'video' group:\n\
-                     sudo usermod -aG video $USER",
-                    device_path,
-                    e
-                )
-            })?;
-
-        let bytesperline = width
-            .checked_mul(3)
-            .ok_or_else(|| anyhow::anyhow!("Frame width overflow: {} * 3", width))?;
-        let sizeimage = bytesperline
-            .checked_mul(height)
-            .ok_or_else(|| anyhow::anyhow!("Frame size overflow: {} * {}", bytesperline, height))?;
-
-        // Set the output format via VIDIOC_S_FMT
-        let mut fmt = V4l2Format {
-            type_: V4L2_BUF_TYPE_VIDEO_OUTPUT,
-            _align_pad: 0,
-            fmt: V4l2PixFormat {
-                width,
-                height,
-                pixelformat: V4L2_PIX_FMT_RGB24,
-                field: V4L2_FIELD_NONE,
-                bytesperline,
-                sizeimage,
-                colorspace: 0,
-                priv_: 0,
-                flags: 0,
-                encoding: 0,
-                quantization: 0,
-                xfer_func: 0,
-            },
-            _padding: [0u8; 200 - std::mem::size_of::<V4l2PixFormat>()],
-        };
-
-        eprintln!("V4L2: setting format {}x{} RGB24 on {}", width, height, device_path);
-
-        let fd = file.as_raw_fd();
-        unsafe {
-            vidioc_s_fmt(fd, &mut fmt).map_err(|e| {
-                anyhow::anyhow!(
-                    "VIDIOC_S_FMT failed on '{}': {}.\n\
-                     Hint: Is this actually a v4l2loopback device? Check: v4l2-ctl --device={} --all",
-                    device_path, e, device_path
-                )
-            })?;
-        }
-
-        // Read back negotiated values
-        let negotiated_w = fmt.fmt.width;
-        let negotiated_h = fmt.fmt.height;
-        if negotiated_w != width || negotiated_h != height {
+        let file = open_device(device_path)?;
+        let negotiated = set_format(&file, device_path, width, height, &encoding)?;
+        if (negotiated.width, negotiated.height) != (width, height) {
             eprintln!(
-                "Warning: v4l2loopback negotiated {}x{} (requested {}x{})",
-                negotiated_w, negotiated_h, width, height
+                "Warning: v4l2loopback negotiated {}x{} (requested {}x{}), frames will be rescaled",
+                negotiated.width, negotiated.height, width, height
             );
         }
 
-        let frame_size =
-            (fmt.fmt.sizeimage as usize).max((negotiated_w * negotiated_h * 3) as usize);
+        let stream = match options.io {
+            OutputIo::Write => None,
+            OutputIo::Mmap => start_stream(&file, device_path, negotiated.frame_size),
+        };
 
-        Ok(V4l2Output {
+        let mut output = V4l2Output {
+            device: device_path.to_string(),
+            stream,
             file,
-            width: negotiated_w,
-            height: negotiated_h,
-            frame_size,
-        })
+            width: negotiated.width,
+            height: negotiated.height,
+            frame_size: negotiated.frame_size,
+            stride: negotiated.stride,
+            encoding,
+            converted: Vec::new(),
+            packed: Vec::new(),
+            scaled: Vec::new(),
+        };
+        output.allocate_buffers();
+        Ok(output)
+    }
+
+    /// Whether the driver asked for rows longer than the packed frame has
+    fn padded(&self) -> bool {
+        self.stride > self.encoding.format.bytes_per_line(self.width)
+    }
+
+    /// Size the scratch buffers for the current format and I/O method
+    fn allocate_buffers(&mut self) {
+        let format = self.encoding.format;
+        self.packed = if self.padded() {
+            vec![0; format.frame_size(self.width, self.height)]
+        } else {
+            Vec::new()
+        };
+        self.converted = if self.stream.is_none() && (self.padded() || format != OutputFormat::Rgb24) {
+            vec![0; self.frame_size]
+        } else {
+            Vec::new()
+        };
     }
 
-    pub fn write_frame(&mut self, rgb_data: &[u8]) -> anyhow::Result<()> {
+    /// Write a frame, rescaling it first if it isn't the negotiated size.
+    pub fn write_frame(&mut self, frame: &RenderedFrame) -> anyhow::Result<()> {
+        let (width, height, encoding) = (self.width, self.height, self.encoding);
+        let rgb_data = if (frame.width, frame.height) == (width, height) {
+            &frame.rgb
+        } else {
+            pixfmt::scale_rgb(&frame.rgb, frame.width, frame.height, width, height, &mut self.scaled);
+            &self.scaled
+        };
+        let (format, stride) = (encoding.format, self.stride);
+        let padded = self.padded();
+        if padded {
+            encoding.encode(rgb_data, width, height, &mut self.packed);
+        }
+        if let Some(ref mut stream) = self.stream {
+            let packed = &self.packed;
+            return stream
+                .queue(self.frame_size, |buf| {
+                    if padded {
+                        format.pad_rows(packed, width, height, stride, buf);
+                    } else {
+                        encoding.encode(rgb_data, width, height, buf);
+                    }
+                })
+                .map_err(|e| anyhow::anyhow!("Queueing buffer on v4l2 device failed: {}", e));
+        }
+
+        let frame = if padded {
+            format.pad_rows(&self.packed, width, height, stride, &mut self.converted);
+            &self.converted
+        } else if format == OutputFormat::Rgb24 {
+            rgb_data
+        } else {
+            encoding.encode(rgb_data, width, height, &mut self.converted);
+            &self.converted
+        };
         let mut written = 0;
-        let data = &rgb_data[..self.frame_size.min(rgb_data.len())];
+        let data = &frame[..self.frame_size.min(frame.len())];
         while written < data.len() {
             match self.file.write(&data[written..]) {
                 Ok(0) => return Err(anyhow::anyhow!("Write to v4l2 device returned 0 bytes")),
@@ -164,4 +491,206 @@ impl V4l2Output {
     pub fn resolution(&self) -> (u32, u32) {
         (self.width, self.height)
     }
+
+    /// Declare the frame rate to consumers through VIDIOC_S_PARM and return the
+    /// rate the device reports back through VIDIOC_G_PARM.
+    pub fn set_frame_rate(&mut self, fps: FrameRate) -> anyhow::Result<FrameRate> {
+        let fd = self.file.as_raw_fd();
+        let mut parm: V4l2StreamParm = unsafe { std::mem::zeroed() };
+        parm.type_ = V4L2_BUF_TYPE_VIDEO_OUTPUT;
+        parm.timeperframe = [fps.den(), fps.num()];
+        unsafe { vidioc_s_parm(fd, &mut parm) }
+            .map_err(|e| anyhow::anyhow!("VIDIOC_S_PARM failed on '{}': {}", self.device, e))?;
+
+        let mut parm: V4l2StreamParm = unsafe { std::mem::zeroed() };
+        parm.type_ = V4L2_BUF_TYPE_VIDEO_OUTPUT;
+        unsafe { vidioc_g_parm(fd, &mut parm) }
+            .map_err(|e| anyhow::anyhow!("VIDIOC_G_PARM failed on '{}': {}", self.device, e))?;
+        FrameRate::from_interval(parm.timeperframe[0], parm.timeperframe[1])
+            .ok_or_else(|| anyhow::anyhow!("'{}' reports no frame interval", self.device))
+    }
+
+    /// Switch the device to frames of a new size. Streaming buffers are released
+    /// first, since drivers refuse S_FMT while they're allocated. v4l2loopback
+    /// also refuses while a consumer is reading, so a failed S_FMT is retried
+    /// once on a fresh file descriptor; if that fails too, or the device can't
+    /// be reopened, the old size is kept and frames are rescaled to it. Streaming
+    /// restarts either way, falling back to write() if the driver refuses it.
+    /// Returns the size now in use.
+    pub fn resize(&mut self, width: u32, height: u32) -> anyhow::Result<(u32, u32)> {
+        if (width, height) == (self.width, self.height) {
+            return Ok((width, height));
+        }
+        let streaming = self.stream.take().is_some();
+
+        let negotiated = set_format(&self.file, &self.device, width, height, &self.encoding)
+            .or_else(|_| {
+                let file = open_device(&self.device)?;
+                self.file = file;
+                set_format(&self.file, &self.device, width, height, &self.encoding)
+            });
+        match negotiated {
+            Ok(negotiated) => {
+                if (negotiated.width, negotiated.height) != (width, height) {
+                    eprintln!(
+                        "Warning: '{}' negotiated {}x{} (requested {}x{}), frames will be rescaled",
+                        self.device, negotiated.width, negotiated.height, width, height
+                    );
+                }
+                self.width = negotiated.width;
+                self.height = negotiated.height;
+                self.frame_size = negotiated.frame_size;
+                self.stride = negotiated.stride;
+            }
+            Err(e) => eprintln!(
+                "Warning: '{}' kept {}x{}, frames will be rescaled: {}",
+                self.device, self.width, self.height, e
+            ),
+        }
+
+        if streaming {
+            self.stream = start_stream(&self.file, &self.device, self.frame_size);
+        }
+        self.allocate_buffers();
+        Ok((self.width, self.height))
+    }
+
+    /// The I/O method in use, which is `Write` if streaming was requested but rejected
+    pub fn io(&self) -> OutputIo {
+        if self.stream.is_some() {
+            OutputIo::Mmap
+        } else {
+            OutputIo::Write
+        }
+    }
+}
+
+impl OutputSink for V4l2Output {
+    fn name(&self) -> String {
+        self.device.clone()
+    }
+
+    fn write_frame(&mut self, frame: &RenderedFrame) -> anyhow::Result<()> {
+        V4l2Output::write_frame(self, frame)
+    }
+
+    fn set_frame_rate(&mut self, fps: FrameRate) -> anyhow::Result<Option<FrameRate>> {
+        V4l2Output::set_frame_rate(self, fps).map(Some)
+    }
+
+    fn resize(&mut self, width: u32, height: u32) -> anyhow::Result<Option<(u32, u32)>> {
+        V4l2Output::resize(self, width, height).map(Some)
+    }
+
+    fn resizable(&self) -> bool {
+        true
+    }
+}
+
+fn open_device(device_path: &str) -> anyhow::Result<File> {
+    OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(device_path)
+        .map_err(|e| {
+            anyhow::anyhow!(
+                "Cannot open '{}': {}.\n\
+                 Hint: Check permissions. You may need to add your user to the 'video' group:\n\
+                 sudo usermod -aG video $USER",
+                device_path,
+                e
+            )
+        })
+}
+
+/// What the driver settled on in VIDIOC_S_FMT
+struct NegotiatedFormat {
+    width: u32,
+    height: u32,
+    /// Bytes between rows of the first plane, at least the packed row length
+    stride: usize,
+    /// Bytes per frame, including any row padding
+    frame_size: usize,
+}
+
+/// VIDIOC_S_FMT for `width`x`height` frames in `encoding`
+fn set_format(
+    file: &File,
+    device_path: &str,
+    width: u32,
+    height: u32,
+    encoding: &OutputEncoding,
+) -> anyhow::Result<NegotiatedFormat> {
+    let format = encoding.format;
+    let bytesperline = u32::try_from(format.bytes_per_line(width))
+        .map_err(|_| anyhow::anyhow!("Frame width overflow: {}", width))?;
+    let sizeimage = u32::try_from(format.frame_size(width, height))
+        .map_err(|_| anyhow::anyhow!("Frame size overflow: {}x{}", width, height))?;
+    let (colorspace, ycbcr_enc, quantization) = colorimetry(encoding);
+
+    let mut fmt = V4l2Format {
+        type_: V4L2_BUF_TYPE_VIDEO_OUTPUT,
+        _align_pad: 0,
+        fmt: V4l2PixFormat {
+            width,
+            height,
+            pixelformat: pix_fmt(format),
+            field: V4L2_FIELD_NONE,
+            bytesperline,
+            sizeimage,
+            colorspace,
+            priv_: 0,
+            flags: 0,
+            encoding: ycbcr_enc,
+            quantization,
+            xfer_func: 0,
+        },
+        _padding: [0u8; 200 - std::mem::size_of::<V4l2PixFormat>()],
+    };
+
+    eprintln!("V4L2: setting format {}x{} {} on {}", width, height, encoding, device_path);
+
+    unsafe {
+        vidioc_s_fmt(file.as_raw_fd(), &mut fmt).map_err(|e| {
+            anyhow::anyhow!(
+                "VIDIOC_S_FMT failed on '{}': {}.\n\
+                 Hint: Is this actually a v4l2loopback device? Check: v4l2-ctl --device={} --all",
+                device_path, e, device_path
+            )
+        })?;
+    }
+
+    // Read back negotiated values
+    if fmt.fmt.pixelformat != pix_fmt(format) {
+        return Err(anyhow::anyhow!(
+            "'{}' does not accept {} output.\n\
+             Hint: v4l2loopback keeps its current format while a consumer has the device open",
+            device_path,
+            format.name()
+        ));
+    }
+    let (width, height) = (fmt.fmt.width, fmt.fmt.height);
+    let stride = (fmt.fmt.bytesperline as usize).max(format.bytes_per_line(width));
+    let frame_size = (fmt.fmt.sizeimage as usize).max(format.padded_frame_size(width, height, stride));
+    Ok(NegotiatedFormat {
+        width,
+        height,
+        stride,
+        frame_size,
+    })
 }
+
+/// Streaming I/O on `file`, or None (with a warning) if the driver rejects it
+fn start_stream(file: &File, device_path: &str, frame_size: usize) -> Option<MmapStream> {
+    match MmapStream::start(file.as_raw_fd(), frame_size) {
+        Ok(stream) => Some(stream),
+        Err(e) => {
+            eprintln!(
+                "Warning: '{}' rejected streaming I/O ({}), falling back to write()",
+                device_path, e
+            );
+            None
+        }
+    }
+}
+
@@ -0,0 +1,208 @@
+use crate::capture::{FrameSource, DEFAULT_RESOLUTION};
+use crate::framerate::FrameRate;
+
+/// Built-in synthetic test images
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Pattern {
+    /// Eight full-saturation colour bars
+    Bars,
+    /// Left-to-right black to white ramp
+    Gradient,
+    /// White square bouncing around a dark frame
+    BouncingBox,
+}
+
+impl Pattern {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "bars" => Some(Self::Bars),
+            "gradient" => Some(Self::Gradient),
+            "bouncing-box" => Some(Self::BouncingBox),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Bars => "bars",
+            Self::Gradient => "gradient",
+            Self::BouncingBox => "bouncing-box",
+        }
+    }
+
+    fn is_animated(self) -> bool {
+        self == Self::BouncingBox
+    }
+}
+
+const BARS: [[u8; 3]; 8] = [
+    [255, 255, 255],
+    [255, 255, 0],
+    [0, 255, 255],
+    [0, 255, 0],
+    [255, 0, 255],
+    [255, 0, 0],
+    [0, 0, 255],
+    [0, 0, 0],
+];
+const BOX_BG: [u8; 3] = [16, 16, 16];
+const BOX_FG: [u8; 3] = [255, 255, 255];
+/// Box speed in frame heights per second along each axis
+const BOX_SPEED: f64 = 0.5;
+
+/// Generates frames from a [`Pattern`]. Output is a pure function of the frame
+/// number, so the same pattern, size and fps always yield the same sequence.
+/// Pacing is left to the capture thread's frame limiter.
+pub struct PatternSource {
+    pattern: Pattern,
+    width: u32,
+    height: u32,
+    fps: FrameRate,
+    frame_number: u64,
+    rgb: Vec<u8>,
+}
+
+impl PatternSource {
+    pub fn new(pattern: Pattern, resolution: Option<(u32, u32)>, fps: FrameRate) -> Self {
+        let (width, height) = resolution.unwrap_or(DEFAULT_RESOLUTION);
+        PatternSource {
+            pattern,
+            width,
+            height,
+            fps,
+            frame_number: 0,
+            rgb: Vec::new(),
+        }
+    }
+
+    fn draw(&mut self) {
+        let w = self.width as usize;
+        let h = self.height as usize;
+        self.rgb.resize(w * h * 3, 0);
+
+        match self.pattern {
+            Pattern::Bars => {
+                for row in self.rgb.chunks_exact_mut(w * 3) {
+                    for (x, px) in row.chunks_exact_mut(3).enumerate() {
+                        px.copy_from_slice(&BARS[x * BARS.len() / w]);
+                    }
+                }
+            }
+            Pattern::Gradient => {
+                let denom = (w - 1).max(1);
+                for row in self.rgb.chunks_exact_mut(w * 3) {
+                    for (x, px) in row.chunks_exact_mut(3).enumerate() {
+                        px.fill((x * 255 / denom) as u8);
+                    }
+                }
+            }
+            Pattern::BouncingBox => {
+                for px in self.rgb.chunks_exact_mut(3) {
+                    px.copy_from_slice(&BOX_BG);
+                }
+                let size = (h / 5).clamp(1, w);
+                let (bx, by) = self.box_position(size);
+                for row in self.rgb.chunks_exact_mut(w * 3).skip(by).take(size) {
+                    for px in row[bx * 3..(bx + size) * 3].chunks_exact_mut(3) {
+                        px.copy_from_slice(&BOX_FG);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Top-left corner of the bouncing box for the current frame
+    fn box_position(&self, size: usize) -> (usize, usize) {
+        let t = self.frame_number as f64 / self.fps.as_f64();
+        let step = BOX_SPEED * self.height as f64 * t;
+        (
+            bounce(step, self.width as usize - size),
+            bounce(step, self.height as usize - size),
+        )
+    }
+}
+
+/// Triangle wave: position after travelling `distance` back and forth over 0..=range
+fn bounce(distance: f64, range: usize) -> usize {
+    if range == 0 {
+        return 0;
+    }
+    let period = 2 * range;
+    let pos = distance as usize % period;
+    if pos > range {
+        period - pos
+    } else {
+        pos
+    }
+}
+
+impl FrameSource for PatternSource {
+    fn capture_frame(&mut self) -> anyhow::Result<Vec<u8>> {
+        // Static patterns only need drawing once
+        if self.rgb.is_empty() || self.pattern.is_animated() {
+            self.draw();
+        }
+        self.frame_number += 1;
+        Ok(self.rgb.clone())
+    }
+
+    fn resolution(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    fn stop_stream(&mut self) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pixel(frame: &[u8], width: u32, x: u32, y: u32) -> [u8; 3] {
+        let i = ((y * width + x) * 3) as usize;
+        [frame[i], frame[i + 1], frame[i + 2]]
+    }
+
+    #[test]
+    fn test_static_patterns() {
+        let mut bars = PatternSource::new(Pattern::Bars, Some((80, 10)), FrameRate::from_int(30));
+        let frame = bars.capture_frame().unwrap();
+        assert_eq!(frame.len(), 80 * 10 * 3);
+        assert_eq!(pixel(&frame, 80, 0, 5), [255, 255, 255]);
+        assert_eq!(pixel(&frame, 80, 55, 0), [255, 0, 0]);
+        assert_eq!(pixel(&frame, 80, 79, 9), [0, 0, 0]);
+
+        let mut gradient = PatternSource::new(Pattern::Gradient, Some((256, 2)), FrameRate::from_int(30));
+        let frame = gradient.capture_frame().unwrap();
+        assert_eq!(pixel(&frame, 256, 0, 1), [0, 0, 0]);
+        assert_eq!(pixel(&frame, 256, 128, 1), [128, 128, 128]);
+        assert_eq!(pixel(&frame, 256, 255, 1), [255, 255, 255]);
+    }
+
+    #[test]
+    fn test_bouncing_box_is_deterministic() {
+        let fps = FrameRate::from_int(30);
+        let mut a = PatternSource::new(Pattern::BouncingBox, Some((64, 40)), fps);
+        let mut b = PatternSource::new(Pattern::BouncingBox, Some((64, 40)), fps);
+        let first = a.capture_frame().unwrap();
+        assert_eq!(pixel(&first, 64, 0, 0), BOX_FG);
+        assert_eq!(pixel(&first, 64, 63, 39), BOX_BG);
+        assert_eq!(b.capture_frame().unwrap(), first);
+
+        let mut moved = false;
+        for _ in 0..100 {
+            let frame = a.capture_frame().unwrap();
+            assert_eq!(frame, b.capture_frame().unwrap());
+            moved |= frame != first;
+        }
+        assert!(moved);
+    }
+
+    #[test]
+    fn test_bounce_reflects_at_edges() {
+        assert_eq!(bounce(0.0, 10), 0);
+        assert_eq!(bounce(7.0, 10), 7);
+        assert_eq!(bounce(13.0, 10), 7);
+        assert_eq!(bounce(20.0, 10), 0);
+        assert_eq!(bounce(5.0, 0), 0);
+    }
+}
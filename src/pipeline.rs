@@ -5,16 +5,27 @@ use std::time::{Duration, Instant};
 
 use crossbeam_channel::{bounded, Receiver, Sender};
 
-use crate::capture::WebcamCapture;
+use crate::capture::{DecodeDetail, EndOfStream, FrameSource, SourceSpec};
 use crate::control::{CaptureAction, CaptureCommand, RenderAction, RenderCommand};
-use crate::output::V4l2Output;
+use crate::framerate::FrameRate;
+use crate::glyph_cache::FontMetrics;
+use crate::hotplug::{DeviceEvent, HotplugWatcher};
+use crate::output::V4l2Options;
+use crate::pixfmt::PixelFormat;
 use crate::renderer::AsciiRenderer;
+use crate::sink::{self, OutputSink, RenderedFrame, RunningSink, SinkOptions, SinkSet};
+use crate::snapshot;
 
 /// Frame data passed between pipeline stages
 pub struct Frame {
-    pub rgb: Vec<u8>,
+    pub data: Vec<u8>,
+    pub format: PixelFormat,
+    /// Size of `data`, which can be a fraction of the source's size when
+    /// MJPEG is decoded at reduced scale
     pub width: u32,
     pub height: u32,
+    /// The source's own resolution, which the output follows
+    pub source_size: (u32, u32),
 }
 
 /// Frame data sent to GUI for preview display
@@ -27,24 +38,21 @@ pub struct PreviewFrame {
 
 pub struct Pipeline {
     threads: Vec<thread::JoinHandle<()>>,
-    out_w: u32,
-    out_h: u32,
-    /// Swappable sender: render thread sends frames through this indirection.
-    /// Some(tx) when output is active, None when stopped.
-    render_to_output_tx: Arc<Mutex<Option<Sender<Vec<u8>>>>>,
-    /// Handle and shutdown flag for the current output thread (if any).
-    output_handle: Option<thread::JoinHandle<()>>,
-    output_shutdown: Option<Arc<AtomicBool>>,
-    shutdown: Arc<AtomicBool>,
+    /// Outputs the render thread fans frames out to. Sinks can be attached
+    /// and detached while the pipeline runs; with none attached, rendering
+    /// carries on for the GUI preview.
+    sinks: Arc<Mutex<SinkSet>>,
 }
 
 impl Pipeline {
     pub fn start(
-        camera_index: u32,
+        source: SourceSpec,
         resolution: Option<(u32, u32)>,
-        target_fps: u32,
+        output_size: Option<(u32, u32)>,
+        target_fps: FrameRate,
         renderer: AsciiRenderer,
-        v4l2_output: Option<V4l2Output>,
+        sinks: Arc<Mutex<SinkSet>>,
+        output_options: V4l2Options,
         shutdown: Arc<AtomicBool>,
         capture_cmd_rx: Receiver<CaptureCommand>,
         render_cmd_rx: Receiver<RenderCommand>,
@@ -53,24 +61,40 @@ impl Pipeline {
     ) -> anyhow::Result<Self> {
         let (capture_tx, capture_rx): (Sender<Frame>, Receiver<Frame>) = bounded(2);
 
-        // Swappable output sender: render thread sends through this mutex.
-        // Allows start_output/stop_output to hot-swap the output channel.
-        let render_to_output_tx: Arc<Mutex<Option<Sender<Vec<u8>>>>> =
-            Arc::new(Mutex::new(None));
-
-        let out_w = renderer.output_width;
-        let out_h = renderer.output_height;
-
-        let mut frame_interval = Duration::from_secs_f64(1.0 / target_fps as f64);
+        sinks
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .set_frame_size(renderer.output_width, renderer.output_height);
+        // Without a fixed output size, the output follows the source's size
+        let follow_source = output_size.is_none();
+
+        // The render thread publishes its grid size so the capture thread can
+        // decode no more detail than the renderer will use
+        #[cfg(feature = "gui")]
+        let raw_preview = gui_raw_tx.is_some();
+        #[cfg(not(feature = "gui"))]
+        let raw_preview = false;
+        let decode_detail = Arc::new(Mutex::new(DecodeDetail {
+            grid: renderer.grid_size(),
+            full_size: renderer.needs_color() || raw_preview,
+        }));
+        let capture_detail = decode_detail.clone();
+
+        let mut frame_interval = target_fps.interval();
         let shutdown_capture = shutdown.clone();
+        let capture_sinks = sinks.clone();
+        // Set while the capture thread is reconnecting, so the render thread
+        // keeps outputs fed with a placeholder
+        let capture_down = Arc::new(AtomicBool::new(false));
+        let render_capture_down = capture_down.clone();
         let shutdown_render = shutdown.clone();
 
-        // Capture thread. Creates Camera internally to avoid Send issues.
+        // Capture thread. Opens the source internally to avoid Send issues.
         let capture_handle = thread::Builder::new()
             .name("capture".into())
             .spawn(move || {
                 let mut cur_fps = target_fps;
-                let mut camera = match WebcamCapture::new(camera_index, resolution, target_fps) {
+                let mut camera = match source.open(resolution, target_fps) {
                     Ok(c) => c,
                     Err(e) => {
                         eprintln!("Capture thread error: {}", e);
@@ -79,13 +103,16 @@ impl Pipeline {
                     }
                 };
 
-                let (mut w, mut h) = camera.resolution();
-                let mut cur_index = camera_index;
-                let mut cur_resolution = resolution;
+                let (w, h) = camera.resolution();
                 eprintln!("  Capturing: {}x{}", w, h);
+                let mut cur_source = source;
+                let mut cur_resolution = resolution;
 
                 let mut fps_counter = FpsCounter::new("Capture");
                 let mut consecutive_errors: u32 = 0;
+                // Device controls set over the control socket, reapplied whenever
+                // the source is reopened
+                let mut controls: Vec<(String, String)> = Vec::new();
 
                 while !shutdown_capture.load(Ordering::Relaxed) {
                     let start = Instant::now();
@@ -94,10 +121,10 @@ impl Pipeline {
                     while let Ok(cmd) = capture_cmd_rx.try_recv() {
                         match cmd.action {
                             CaptureAction::ChangeCamera {
-                                index,
+                                source: new_source,
                                 resolution: new_res,
                             } => {
-                                let old_index = cur_index;
+                                let old_source = cur_source.clone();
                                 let old_res = cur_resolution;
 
                                 // Stop and drop old camera. Sleep gives the UVC
@@ -106,23 +133,22 @@ impl Pipeline {
                                 drop(camera);
                                 thread::sleep(Duration::from_millis(200));
 
-                                match WebcamCapture::new(index, new_res, cur_fps) {
+                                match new_source.open(new_res, cur_fps) {
                                     Ok(new_cam) => {
                                         let (nw, nh) = new_cam.resolution();
                                         camera = new_cam;
-                                        w = nw;
-                                        h = nh;
-                                        cur_index = index;
-                                        cur_resolution = new_res;
-                                        consecutive_errors = 0;
-                                        let res_str = format!("{}x{}", nw, nh);
+                                        reapply_controls(camera.as_mut(), &controls);
+                                        let setting = new_source.as_setting();
                                         eprintln!(
-                                            "  Camera changed: /dev/video{} ({})",
-                                            index, res_str
+                                            "  Camera changed: {} ({}x{})",
+                                            new_source, nw, nh
                                         );
+                                        cur_source = new_source;
+                                        cur_resolution = new_res;
+                                        consecutive_errors = 0;
                                         let _ = cmd.response_tx.send(Ok(format!(
-                                            "camera_index={} ({})",
-                                            index, res_str
+                                            "{} ({}x{})",
+                                            setting, nw, nh
                                         )));
                                     }
                                     Err(e) => {
@@ -130,16 +156,11 @@ impl Pipeline {
                                         eprintln!("  Camera change failed: {}", err_msg);
                                         // Rollback to old camera
                                         thread::sleep(Duration::from_millis(200));
-                                        match WebcamCapture::new(old_index, old_res, cur_fps) {
+                                        match old_source.open(old_res, cur_fps) {
                                             Ok(old_cam) => {
-                                                let (ow, oh) = old_cam.resolution();
                                                 camera = old_cam;
-                                                w = ow;
-                                                h = oh;
-                                                eprintln!(
-                                                    "  Rolled back to /dev/video{}",
-                                                    old_index
-                                                );
+                                                reapply_controls(camera.as_mut(), &controls);
+                                                eprintln!("  Rolled back to {}", old_source);
                                                 let _ = cmd.response_tx.send(Err(err_msg));
                                             }
                                             Err(rollback_err) => {
@@ -158,27 +179,50 @@ impl Pipeline {
                                     }
                                 }
                             }
+                            CaptureAction::SetControls { controls: requested } => {
+                                let mut applied = Vec::new();
+                                let mut errors = Vec::new();
+                                for (key, value) in requested {
+                                    match camera.set_control(&key, &value) {
+                                        Ok(v) => {
+                                            eprintln!("  Control set: {}={}", key, v);
+                                            applied.push(format!("ctrl.{}={}", key, v));
+                                            controls.retain(|(k, _)| *k != key);
+                                            controls.push((key, value));
+                                        }
+                                        Err(e) => errors.push(format!("{}", e)),
+                                    }
+                                }
+                                let _ = cmd.response_tx.send(if errors.is_empty() {
+                                    Ok(applied.join(" "))
+                                } else {
+                                    Err(errors.join("; "))
+                                });
+                            }
                             CaptureAction::ChangeFps { fps } => {
                                 // Don't update frame_interval yet - wait for camera success
                                 camera.stop_stream();
                                 drop(camera);
                                 thread::sleep(Duration::from_millis(200));
-                                match WebcamCapture::new(cur_index, cur_resolution, fps) {
+                                match cur_source.open(cur_resolution, fps) {
                                     Ok(new_cam) => {
-                                        let (nw, nh) = new_cam.resolution();
                                         camera = new_cam;
-                                        w = nw;
-                                        h = nh;
+                                        reapply_controls(camera.as_mut(), &controls);
                                         cur_fps = fps;
-                                        frame_interval = Duration::from_secs_f64(1.0 / fps as f64);
+                                        frame_interval = fps.interval();
+                                        capture_sinks
+                                            .lock()
+                                            .unwrap_or_else(|e| e.into_inner())
+                                            .set_frame_rate(fps);
                                         eprintln!("  FPS changed: {} (camera reopened)", fps);
                                         let _ = cmd.response_tx.send(Ok(format!("fps={}", fps)));
                                     }
                                     Err(e) => {
                                         eprintln!("  FPS change failed: {}, reopening at old fps", e);
-                                        match WebcamCapture::new(cur_index, cur_resolution, cur_fps) {
+                                        match cur_source.open(cur_resolution, cur_fps) {
                                             Ok(old_cam) => {
                                                 camera = old_cam;
+                                                reapply_controls(camera.as_mut(), &controls);
                                                 let _ = cmd.response_tx.send(Err(format!("{}", e)));
                                             }
                                             Err(e2) => {
@@ -194,24 +238,35 @@ impl Pipeline {
                         }
                     }
 
+                    let detail = *capture_detail.lock().unwrap_or_else(|e| e.into_inner());
+                    camera.set_detail(detail);
+
                     match camera.capture_frame() {
-                        Ok(rgb) => {
+                        Ok(data) => {
                             consecutive_errors = 0;
+                            let format = camera.pixel_format();
+                            let (w, h) = camera.frame_dimensions();
 
                             // Send to GUI raw preview if available
                             #[cfg(feature = "gui")]
                             if let Some(ref gui_tx) = gui_raw_tx {
-                                let _ = gui_tx.try_send(PreviewFrame {
-                                    rgb: rgb.clone(),
-                                    width: w,
-                                    height: h,
-                                });
+                                if !gui_tx.is_full() {
+                                    let mut rgb = Vec::new();
+                                    format.to_rgb(&data, w, h, &mut rgb);
+                                    let _ = gui_tx.try_send(PreviewFrame {
+                                        rgb,
+                                        width: w,
+                                        height: h,
+                                    });
+                                }
                             }
 
                             let frame = Frame {
-                                rgb,
+                                data,
+                                format,
                                 width: w,
                                 height: h,
+                                source_size: camera.resolution(),
                             };
                             match capture_tx.try_send(frame) {
                                 Ok(()) => {}
@@ -224,6 +279,11 @@ impl Pipeline {
                             }
                             fps_counter.tick();
                         }
+                        Err(e) if e.is::<EndOfStream>() => {
+                            eprintln!("Capture: {}, shutting down", e);
+                            shutdown_capture.store(true, Ordering::SeqCst);
+                            break;
+                        }
                         Err(e) => {
                             consecutive_errors += 1;
                             // Only log the first error to avoid spam
@@ -234,16 +294,18 @@ impl Pipeline {
                                 eprintln!("Too many capture errors, attempting reconnect...");
                                 camera.stop_stream();
                                 drop(camera);
-                                match reconnect_camera(
-                                    cur_index,
+                                capture_down.store(true, Ordering::SeqCst);
+                                let reconnected = reconnect_camera(
+                                    &mut cur_source,
                                     cur_resolution,
                                     cur_fps,
                                     &shutdown_capture,
-                                ) {
-                                    Some((new_cam, nw, nh)) => {
+                                );
+                                capture_down.store(false, Ordering::SeqCst);
+                                match reconnected {
+                                    Some(new_cam) => {
                                         camera = new_cam;
-                                        w = nw;
-                                        h = nh;
+                                        reapply_controls(camera.as_mut(), &controls);
                                         consecutive_errors = 0;
                                         fps_counter = FpsCounter::new("Capture");
                                         continue; // Capture immediately without rate-limit sleep
@@ -263,13 +325,16 @@ impl Pipeline {
             })?;
 
         // Render thread
-        let render_output_tx = render_to_output_tx.clone();
+        let render_sinks = sinks.clone();
         let render_handle = thread::Builder::new()
             .name("render".into())
             .spawn(move || {
                 let mut renderer = renderer;
                 let mut fps_counter = FpsCounter::new("Render");
                 let timeout = Duration::from_millis(100);
+                let mut source_size = None;
+                // For snapshots, with the font it was drawn in
+                let mut last_frame: Option<(Arc<RenderedFrame>, FontMetrics)> = None;
 
                 loop {
                     if shutdown_render.load(Ordering::Relaxed) {
@@ -286,10 +351,15 @@ impl Pipeline {
                                 bg,
                                 brightness_curve,
                                 invert,
+                                scale_mode,
                                 theme_name,
                             } => {
                                 let out_w = renderer.output_width;
                                 let out_h = renderer.output_height;
+                                // A fixed grid keeps its size; the definition
+                                // level only picks the charset then
+                                let fixed_grid = renderer.fixed_grid();
+                                let columns = fixed_grid.map_or(ascii_columns, |(c, _)| c);
 
                                 match AsciiRenderer::new(
                                     &charset,
@@ -299,11 +369,24 @@ impl Pipeline {
                                     invert,
                                     out_w,
                                     out_h,
-                                    ascii_columns,
+                                    columns,
                                     &theme_name,
                                 ) {
-                                    Ok(new_renderer) => {
+                                    Ok(mut new_renderer) => {
+                                        new_renderer.set_scale_mode(scale_mode);
+                                        if let Some((c, r)) = fixed_grid {
+                                            new_renderer = new_renderer.with_grid(c, r);
+                                            render_sinks
+                                                .lock()
+                                                .unwrap_or_else(|e| e.into_inner())
+                                                .set_frame_size(new_renderer.output_width, new_renderer.output_height);
+                                        }
                                         renderer = new_renderer;
+                                        *decode_detail.lock().unwrap_or_else(|e| e.into_inner()) =
+                                            DecodeDetail {
+                                                grid: renderer.grid_size(),
+                                                full_size: renderer.needs_color() || raw_preview,
+                                            };
                                         eprintln!("  Renderer rebuilt ({} cols)", ascii_columns);
                                         let _ = cmd.response_tx.send(Ok(format!(
                                             "renderer rebuilt ({} cols)",
@@ -316,33 +399,103 @@ impl Pipeline {
                                     }
                                 }
                             }
+                            RenderAction::AttachSink { spec } => {
+                                let attached = spec
+                                    .open(renderer.output_width, renderer.output_height, output_options)
+                                    .and_then(|sink| {
+                                        render_sinks
+                                            .lock()
+                                            .unwrap_or_else(|e| e.into_inner())
+                                            .attach(sink, spec.default_options())
+                                    });
+                                match attached {
+                                    Ok(name) => {
+                                        eprintln!("  Output added: {}", name);
+                                        let _ = cmd.response_tx.send(Ok(format!("output added {}", name)));
+                                    }
+                                    Err(e) => {
+                                        eprintln!("  Adding output failed: {}", e);
+                                        let _ = cmd.response_tx.send(Err(format!("{}", e)));
+                                    }
+                                }
+                            }
+                            RenderAction::DetachSink { name } => {
+                                let removed = render_sinks
+                                    .lock()
+                                    .unwrap_or_else(|e| e.into_inner())
+                                    .detach(&name);
+                                if let Some(sink) = removed {
+                                    sink.stop();
+                                    eprintln!("  Output removed: {}", name);
+                                    let _ = cmd.response_tx.send(Ok(format!("output removed {}", name)));
+                                } else {
+                                    let _ = cmd.response_tx.send(Err(format!("no output '{}'", name)));
+                                }
+                            }
+                            RenderAction::FitGrid { columns, rows } => {
+                                if renderer.fixed_grid().is_none() {
+                                    let _ = cmd.response_tx.send(Err("renderer grid is not fixed".into()));
+                                    continue;
+                                }
+                                renderer = renderer.with_grid(columns, rows);
+                                render_sinks
+                                    .lock()
+                                    .unwrap_or_else(|e| e.into_inner())
+                                    .set_frame_size(renderer.output_width, renderer.output_height);
+                                *decode_detail.lock().unwrap_or_else(|e| e.into_inner()) =
+                                    DecodeDetail {
+                                        grid: renderer.grid_size(),
+                                        full_size: renderer.needs_color() || raw_preview,
+                                    };
+                                let _ = cmd.response_tx.send(Ok(format!("grid {}x{}", columns, rows)));
+                            }
+                            RenderAction::Snapshot { format, path } => {
+                                let Some((frame, metrics)) = last_frame.clone() else {
+                                    let _ = cmd.response_tx.send(Err("no frame rendered yet".into()));
+                                    continue;
+                                };
+                                // A PNG can take longer than a frame to encode,
+                                // so files are written off the render thread
+                                thread::spawn(move || {
+                                    let result = snapshot::write(format, &frame, metrics, &path)
+                                        .map(|size| {
+                                            format!("snapshot {} {} ({} bytes)", format.name(), path.display(), size)
+                                        })
+                                        .map_err(|e| e.to_string());
+                                    let _ = cmd.response_tx.send(result);
+                                });
+                            }
                         }
                     }
 
-                    match capture_rx.recv_timeout(timeout) {
+                    // While the camera is away, wait one frame interval at a time
+                    // and emit a placeholder so consumers don't see a frozen feed
+                    let capture_down = render_capture_down.load(Ordering::Relaxed);
+                    let wait = if capture_down {
+                        render_sinks
+                            .lock()
+                            .unwrap_or_else(|e| e.into_inner())
+                            .frame_rate()
+                            .interval()
+                    } else {
+                        timeout
+                    };
+
+                    let (grid, rendered) = match capture_rx.recv_timeout(wait) {
                         Ok(frame) => {
-                            let rendered = renderer.render(&frame.rgb, frame.width, frame.height);
-
-                            // Send to GUI rendered preview if available
-                            #[cfg(feature = "gui")]
-                            if let Some(ref gui_tx) = gui_rendered_tx {
-                                let _ = gui_tx.try_send(PreviewFrame {
-                                    rgb: rendered.clone(),
-                                    width: renderer.output_width,
-                                    height: renderer.output_height,
-                                });
-                            }
-
-                            // Send to output thread via swappable sender.
-                            // Render thread NEVER breaks on output disconnect.
-                            // It keeps running for GUI preview; pipeline shutdown is via AtomicBool.
-                            {
-                                let guard = render_output_tx.lock().unwrap_or_else(|e| e.into_inner());
-                                if let Some(ref tx) = *guard {
-                                    let _ = tx.try_send(rendered);
-                                }
+                            if follow_source && follow_source_size(&mut renderer, &render_sinks, &mut source_size, &frame) {
+                                *decode_detail.lock().unwrap_or_else(|e| e.into_inner()) =
+                                    DecodeDetail {
+                                        grid: renderer.grid_size(),
+                                        full_size: renderer.needs_color() || raw_preview,
+                                    };
                             }
-                            fps_counter.tick();
+                            let grid = renderer.analyze(&frame.data, frame.format, frame.width, frame.height);
+                            let rendered = renderer.rasterize(&grid);
+                            (grid, rendered)
+                        }
+                        Err(crossbeam_channel::RecvTimeoutError::Timeout) if capture_down => {
+                            renderer.render_no_signal()
                         }
                         Err(crossbeam_channel::RecvTimeoutError::Timeout) => continue,
                         Err(crossbeam_channel::RecvTimeoutError::Disconnected) => {
@@ -350,205 +503,191 @@ impl Pipeline {
                             shutdown_render.store(true, Ordering::SeqCst);
                             break;
                         }
+                    };
+
+                    // Send to GUI rendered preview if available
+                    #[cfg(feature = "gui")]
+                    if let Some(ref gui_tx) = gui_rendered_tx {
+                        let _ = gui_tx.try_send(PreviewFrame {
+                            rgb: rendered.clone(),
+                            width: renderer.output_width,
+                            height: renderer.output_height,
+                        });
                     }
+
+                    // Fan out to the attached sinks. The render thread never
+                    // stops because an output went away; it keeps running for
+                    // the GUI preview and pipeline shutdown is via AtomicBool.
+                    let frame = Arc::new(RenderedFrame {
+                        rgb: rendered,
+                        width: renderer.output_width,
+                        height: renderer.output_height,
+                        grid,
+                    });
+                    last_frame = Some((frame.clone(), renderer.font_metrics()));
+                    render_sinks
+                        .lock()
+                        .unwrap_or_else(|e| e.into_inner())
+                        .send(frame);
+                    fps_counter.tick();
                 }
             })?;
 
         let threads = vec![capture_handle, render_handle];
 
-        // Output thread, only spawned if v4l2_output is provided at startup
-        let (output_handle, output_shutdown) = if let Some(mut v4l2_output) = v4l2_output {
-            let (tx, rx): (Sender<Vec<u8>>, Receiver<Vec<u8>>) = bounded(2);
-            // Store initial sender in the shared mutex
-            {
-                let mut guard = render_to_output_tx.lock().unwrap_or_else(|e| e.into_inner());
-                *guard = Some(tx);
-            }
-
-            let out_shutdown = Arc::new(AtomicBool::new(false));
-            let shutdown_output = out_shutdown.clone();
-            let pipeline_shutdown = shutdown.clone();
-            let handle = thread::Builder::new()
-                .name("output".into())
-                .spawn(move || {
-                    let mut fps_counter = FpsCounter::new("Output");
-                    let timeout = Duration::from_millis(100);
-
-                    loop {
-                        if shutdown_output.load(Ordering::Relaxed)
-                            || pipeline_shutdown.load(Ordering::Relaxed)
-                        {
-                            break;
-                        }
-
-                        match rx.recv_timeout(timeout) {
-                            Ok(rendered_frame) => {
-                                if let Err(e) = v4l2_output.write_frame(&rendered_frame) {
-                                    if !pipeline_shutdown.load(Ordering::Relaxed) {
-                                        eprintln!("Output error: {}", e);
-                                    }
-                                    pipeline_shutdown.store(true, Ordering::SeqCst);
-                                    break;
-                                }
-                                fps_counter.tick();
-                            }
-                            Err(crossbeam_channel::RecvTimeoutError::Timeout) => continue,
-                            Err(crossbeam_channel::RecvTimeoutError::Disconnected) => {
-                                // Sender was taken by stop_output(), clean exit
-                                break;
-                            }
-                        }
-                    }
-                })?;
-            (Some(handle), Some(out_shutdown))
-        } else {
-            (None, None)
-        };
-
-        Ok(Pipeline {
-            threads,
-            out_w,
-            out_h,
-            render_to_output_tx,
-            output_handle,
-            output_shutdown,
-            shutdown: shutdown.clone(),
-        })
+        Ok(Pipeline { threads, sinks })
     }
 
-    pub fn output_width(&self) -> u32 {
-        self.out_w
+    /// Current size of the rendered frames, which follows the source unless fixed
+    pub fn output_size(&self) -> (u32, u32) {
+        self.sinks
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .frame_size()
     }
 
-    pub fn output_height(&self) -> u32 {
-        self.out_h
+    /// Attach an output to a running pipeline. Returns the sink's name, which
+    /// `detach_sink` takes.
+    pub fn attach_sink(
+        &self,
+        sink: Box<dyn OutputSink>,
+        options: SinkOptions,
+    ) -> anyhow::Result<String> {
+        self.sinks
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .attach(sink, options)
     }
 
-    /// Start the v4l2 output thread on an already-running pipeline.
-    pub fn start_output(&mut self, mut v4l2_output: V4l2Output) -> anyhow::Result<()> {
-        if self.output_handle.is_some() {
-            return Err(anyhow::anyhow!("Output already running"));
-        }
-
-        // Create a new channel pair and store sender in the shared mutex.
-        // The render thread immediately starts feeding the new channel.
-        let (tx, rx): (Sender<Vec<u8>>, Receiver<Vec<u8>>) = bounded(2);
-        {
-            let mut guard = self
-                .render_to_output_tx
-                .lock()
-                .unwrap_or_else(|e| e.into_inner());
-            *guard = Some(tx);
-        }
-
-        let out_shutdown = Arc::new(AtomicBool::new(false));
-        let shutdown_output = out_shutdown.clone();
-        let pipeline_shutdown = self.shutdown.clone();
-        let output_handle = thread::Builder::new()
-            .name("output".into())
-            .spawn(move || {
-                let mut fps_counter = FpsCounter::new("Output");
-                let timeout = Duration::from_millis(100);
-
-                loop {
-                    if shutdown_output.load(Ordering::Relaxed)
-                        || pipeline_shutdown.load(Ordering::Relaxed)
-                    {
-                        break;
-                    }
-
-                    match rx.recv_timeout(timeout) {
-                        Ok(rendered_frame) => {
-                            if let Err(e) = v4l2_output.write_frame(&rendered_frame) {
-                                if !pipeline_shutdown.load(Ordering::Relaxed) {
-                                    eprintln!("Output error: {}", e);
-                                }
-                                pipeline_shutdown.store(true, Ordering::SeqCst);
-                                break;
-                            }
-                            fps_counter.tick();
-                        }
-                        Err(crossbeam_channel::RecvTimeoutError::Timeout) => continue,
-                        Err(crossbeam_channel::RecvTimeoutError::Disconnected) => {
-                            // Sender was taken by stop_output(), clean exit
-                            break;
-                        }
-                    }
-                }
-            })?;
-        self.output_handle = Some(output_handle);
-        self.output_shutdown = Some(out_shutdown);
-        Ok(())
+    /// Stop one output (capture+render pipeline continues). Returns false if
+    /// no output has that name.
+    pub fn detach_sink(&self, name: &str) -> bool {
+        let removed = self
+            .sinks
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .detach(name);
+        removed.map(RunningSink::stop).is_some()
     }
 
-    /// Stop the v4l2 output thread (capture+render pipeline continues).
-    pub fn stop_output(&mut self) {
-        // Remove the sender, starving the output thread
-        {
-            let mut guard = self
-                .render_to_output_tx
-                .lock()
-                .unwrap_or_else(|e| e.into_inner());
-            *guard = None;
-        }
-
-        // Signal the output thread to stop
-        if let Some(ref flag) = self.output_shutdown {
-            flag.store(true, Ordering::SeqCst);
-        }
-
-        // Join the output thread (exits within ~100ms due to recv_timeout)
-        if let Some(handle) = self.output_handle.take() {
+    pub fn wait(self) {
+        for handle in self.threads {
             join_with_panic_log(handle);
         }
-        self.output_shutdown = None;
+        let sinks = self
+            .sinks
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .detach_all();
+        sink::stop_all(sinks);
     }
+}
 
-    pub fn wait(mut self) {
-        // Join output thread first if present
-        if let Some(handle) = self.output_handle.take() {
-            join_with_panic_log(handle);
-        }
-        for handle in self.threads {
-            join_with_panic_log(handle);
+/// Resize the outputs when the source's resolution changes (a camera or
+/// resolution change shows up here first). The size the frame was decoded at
+/// is left out: it shrinks whenever the decode scale follows a new grid, and
+/// the output must not shrink with it. Returns true if the outputs were
+/// reconfigured.
+fn follow_source_size(
+    renderer: &mut AsciiRenderer,
+    sinks: &Mutex<SinkSet>,
+    last: &mut Option<(u32, u32)>,
+    frame: &Frame,
+) -> bool {
+    let size = frame.source_size;
+    let changed = last.is_some_and(|s| s != size);
+    *last = Some(size);
+    if changed {
+        reconfigure_output(renderer, sinks, size);
+    }
+    changed
+}
+
+/// Bring the outputs to a new source size: sinks that can change size are asked
+/// to, then the renderer is rebuilt for the size the main loopback device
+/// settled on. A device that refuses keeps its old size, and the scale mode
+/// maps the new source onto it.
+fn reconfigure_output(renderer: &mut AsciiRenderer, sinks: &Mutex<SinkSet>, (width, height): (u32, u32)) {
+    let (w, h) = sink::resize_sinks(sinks, width, height).unwrap_or((width, height));
+    if (w, h) != (renderer.output_width, renderer.output_height) {
+        match renderer.resized(w, h) {
+            Ok(resized) => *renderer = resized,
+            Err(e) => eprintln!("  Renderer resize failed: {}", e),
         }
     }
+    sinks
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .set_frame_size(renderer.output_width, renderer.output_height);
+    eprintln!(
+        "  Output: {}x{} for a {}x{} source",
+        renderer.output_width, renderer.output_height, width, height
+    );
 }
 
 /// Attempt to reconnect the camera indefinitely until success or shutdown.
-/// Retries every 2 seconds (split into 100ms sleeps for shutdown responsiveness).
+/// Webcams are retried as soon as a video device node appears, other sources
+/// (and webcams without inotify) every 2 seconds, checking for shutdown every 100ms.
+/// A webcam picked by name, bus_info or path is looked up again on every attempt,
+/// so `source` is updated if it comes back under a different /dev/videoN.
 /// Returns None only if shutdown was requested.
 fn reconnect_camera(
-    camera_index: u32,
+    source: &mut SourceSpec,
     resolution: Option<(u32, u32)>,
-    fps: u32,
+    fps: FrameRate,
     shutdown: &AtomicBool,
-) -> Option<(WebcamCapture, u32, u32)> {
+) -> Option<Box<dyn FrameSource>> {
+    // Start watching before the first attempt so a device that appears in
+    // between isn't missed
+    let watcher = match source {
+        SourceSpec::Webcam { .. } => HotplugWatcher::new().ok(),
+        _ => None,
+    };
     loop {
         if shutdown.load(Ordering::Relaxed) {
             return None;
         }
-        eprintln!("  Attempting camera reconnect (index {})...", camera_index);
-        match WebcamCapture::new(camera_index, resolution, fps) {
+        eprintln!("  Attempting camera reconnect ({})...", source);
+        let opened = source
+            .resolve_camera()
+            .and_then(|()| source.open(resolution, fps));
+        match opened {
             Ok(cam) => {
                 let (w, h) = cam.resolution();
-                eprintln!("  Camera reconnected: {}x{}", w, h);
-                return Some((cam, w, h));
+                eprintln!("  Camera reconnected: {} {}x{}", source, w, h);
+                return Some(cam);
             }
             Err(e) => {
                 eprintln!("  Reconnect failed: {}", e);
-                // Wait 2s before retrying, checking shutdown every 100ms
                 for _ in 0..20 {
                     if shutdown.load(Ordering::Relaxed) {
                         return None;
                     }
-                    thread::sleep(Duration::from_millis(100));
+                    match watcher {
+                        Some(ref w) => {
+                            let events = w.wait(Duration::from_millis(100));
+                            if events.iter().any(|e| matches!(e, DeviceEvent::Added(_))) {
+                                break;
+                            }
+                        }
+                        None => thread::sleep(Duration::from_millis(100)),
+                    }
                 }
             }
         }
     }
 }
 
+/// Reapply device controls to a freshly opened source. A control the new
+/// device doesn't have is logged and skipped.
+fn reapply_controls(camera: &mut dyn FrameSource, controls: &[(String, String)]) {
+    for (key, value) in controls {
+        if let Err(e) = camera.set_control(key, value) {
+            eprintln!("  Could not reapply ctrl.{}={}: {}", key, value, e);
+        }
+    }
+}
+
 /// Join a thread handle and log any panic payload.
 fn join_with_panic_log(handle: thread::JoinHandle<()>) {
     let name = handle.thread().name().unwrap_or("unnamed").to_string();
@@ -565,22 +704,22 @@ fn join_with_panic_log(handle: thread::JoinHandle<()>) {
 }
 
 /// Simple FPS counter that prints to stderr every 5 seconds
-struct FpsCounter {
-    name: &'static str,
+pub struct FpsCounter {
+    name: String,
     count: u32,
     last_report: Instant,
 }
 
 impl FpsCounter {
-    fn new(name: &'static str) -> Self {
+    pub fn new(name: impl Into<String>) -> Self {
         FpsCounter {
-            name,
+            name: name.into(),
             count: 0,
             last_report: Instant::now(),
         }
     }
 
-    fn tick(&mut self) {
+    pub fn tick(&mut self) {
         self.count += 1;
         let elapsed = self.last_report.elapsed();
         if elapsed >= Duration::from_secs(5) {
@@ -591,3 +730,38 @@ impl FpsCounter {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{BrightnessCurve, Rgb};
+
+    #[test]
+    fn test_decode_scale_keeps_output_size() {
+        let charset: Vec<char> = " .:#@".chars().collect();
+        let fg = Rgb { r: 0, g: 255, b: 0 };
+        let bg = Rgb { r: 0, g: 0, b: 0 };
+        let mut renderer =
+            AsciiRenderer::new(&charset, fg, bg, BrightnessCurve::Linear, false, 1920, 1080, 80, "mono").unwrap();
+        let sinks = Mutex::new(SinkSet::new(Arc::new(AtomicBool::new(false)), FrameRate::from_int(30)));
+        let frame = |(width, height): (u32, u32), source_size: (u32, u32)| Frame {
+            data: vec![128; (width * height) as usize],
+            format: PixelFormat::Gray,
+            width,
+            height,
+            source_size,
+        };
+        let mut last = None;
+
+        // Full-size RGB for a colour theme, then 1/8 scale luma after a theme change
+        assert!(!follow_source_size(&mut renderer, &sinks, &mut last, &frame((1920, 1080), (1920, 1080))));
+        assert!(!follow_source_size(&mut renderer, &sinks, &mut last, &frame((240, 135), (1920, 1080))));
+        assert!(!follow_source_size(&mut renderer, &sinks, &mut last, &frame((480, 270), (1920, 1080))));
+        assert_eq!((renderer.output_width, renderer.output_height), (1920, 1080));
+
+        // A new camera resolution does resize, to the camera's size
+        assert!(follow_source_size(&mut renderer, &sinks, &mut last, &frame((160, 90), (1280, 720))));
+        assert_eq!((renderer.output_width, renderer.output_height), (1280, 720));
+        assert_eq!(sinks.lock().unwrap().frame_size(), (1280, 720));
+    }
+}
@@ -0,0 +1,666 @@
+/// Chroma subsampling of a planar YUV image
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Chroma {
+    Yuv420,
+    Yuv422,
+    Yuv444,
+    /// Luma only, no chroma planes
+    Mono,
+}
+
+impl Chroma {
+    /// Horizontal and vertical chroma subsampling as bit shifts
+    fn shifts(self) -> (u32, u32) {
+        match self {
+            Chroma::Yuv420 => (1, 1),
+            Chroma::Yuv422 => (1, 0),
+            Chroma::Yuv444 | Chroma::Mono => (0, 0),
+        }
+    }
+
+    /// Dimensions of one chroma plane, rounded up for odd sizes
+    pub fn plane_dims(self, width: u32, height: u32) -> (usize, usize) {
+        if self == Chroma::Mono {
+            return (0, 0);
+        }
+        let (xs, ys) = self.shifts();
+        (
+            (width as usize + (1 << xs) - 1) >> xs,
+            (height as usize + (1 << ys) - 1) >> ys,
+        )
+    }
+
+    /// Total bytes for one 8-bit planar frame (Y plane followed by U and V planes)
+    pub fn frame_size(self, width: u32, height: u32) -> usize {
+        let (cw, ch) = self.plane_dims(width, height);
+        width as usize * height as usize + 2 * cw * ch
+    }
+}
+
+/// Pixel layouts of raw video input and of frames handed to the renderer
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PixelFormat {
+    Rgb24,
+    /// YUV 4:2:2 packed as Y0 U Y1 V (ffmpeg's `yuyv422`)
+    Yuyv,
+    /// YUV 4:2:0 with a full luma plane followed by one interleaved UV plane
+    Nv12,
+    /// 8-bit luma only
+    Gray,
+}
+
+/// Borrowed view of the luma samples inside a frame.
+/// Sample (x, y) is at `data[y * stride + x * step]`.
+pub struct LumaPlane<'a> {
+    pub data: &'a [u8],
+    pub stride: usize,
+    pub step: usize,
+    /// Samples use the limited 16..235 range rather than 0..255
+    pub limited_range: bool,
+}
+
+impl LumaPlane<'_> {
+    /// Scale an averaged luma value to 0.0..=1.0 brightness, expanding limited range
+    pub fn normalize(&self, avg: f32) -> f32 {
+        if self.limited_range {
+            ((avg - 16.0) / 219.0).clamp(0.0, 1.0)
+        } else {
+            avg / 255.0
+        }
+    }
+}
+
+impl PixelFormat {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "rgb24" => Some(Self::Rgb24),
+            "yuyv" | "yuyv422" => Some(Self::Yuyv),
+            "nv12" => Some(Self::Nv12),
+            "gray" | "gray8" => Some(Self::Gray),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Rgb24 => "rgb24",
+            Self::Yuyv => "yuyv",
+            Self::Nv12 => "nv12",
+            Self::Gray => "gray",
+        }
+    }
+
+    /// Bytes per frame. YUYV rows are padded to an even width.
+    pub fn frame_size(self, width: u32, height: u32) -> usize {
+        let (w, h) = (width as usize, height as usize);
+        match self {
+            Self::Rgb24 => w * h * 3,
+            Self::Yuyv => w.div_ceil(2) * 4 * h,
+            Self::Nv12 => Chroma::Yuv420.frame_size(width, height),
+            Self::Gray => w * h,
+        }
+    }
+
+    /// The luma samples of a frame, or None for RGB formats that need a conversion first
+    pub fn luma_plane(self, data: &[u8], width: u32) -> Option<LumaPlane<'_>> {
+        let w = width as usize;
+        let (stride, step, limited_range) = match self {
+            Self::Rgb24 => return None,
+            Self::Yuyv => (w.div_ceil(2) * 4, 2, true),
+            Self::Nv12 => (w, 1, true),
+            Self::Gray => (w, 1, false),
+        };
+        Some(LumaPlane {
+            data,
+            stride,
+            step,
+            limited_range,
+        })
+    }
+
+    /// Convert one frame of this format to packed RGB24
+    pub fn to_rgb(self, data: &[u8], width: u32, height: u32, rgb: &mut Vec<u8>) {
+        rgb.clear();
+        match self {
+            Self::Rgb24 => rgb.extend_from_slice(data),
+            Self::Yuyv => yuyv_to_rgb(data, width, height, rgb),
+            Self::Nv12 => nv12_to_rgb(data, width, height, rgb),
+            Self::Gray => {
+                rgb.reserve(data.len() * 3);
+                for &l in data {
+                    rgb.extend_from_slice(&[l, l, l]);
+                }
+            }
+        }
+    }
+}
+
+/// Convert one BT.601 YUV sample to RGB.
+/// Limited range maps Y 16..235 / UV 16..240; full range uses the whole 0..255.
+pub fn yuv_to_rgb(y: u8, u: u8, v: u8, full_range: bool) -> [u8; 3] {
+    let d = u as i32 - 128;
+    let e = v as i32 - 128;
+    // Fixed-point 8.8 coefficients
+    let (r, g, b) = if full_range {
+        let c = (y as i32) << 8;
+        (c + 359 * e, c - 88 * d - 183 * e, c + 454 * d)
+    } else {
+        let c = 298 * (y as i32 - 16);
+        (c + 409 * e, c - 100 * d - 208 * e, c + 516 * d)
+    };
+    [
+        ((r + 128) >> 8).clamp(0, 255) as u8,
+        ((g + 128) >> 8).clamp(0, 255) as u8,
+        ((b + 128) >> 8).clamp(0, 255) as u8,
+    ]
+}
+
+/// Convert an 8-bit planar YUV frame (Y, U, V planes back to back) to packed RGB24.
+pub fn planar_yuv_to_rgb(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    chroma: Chroma,
+    full_range: bool,
+    rgb: &mut Vec<u8>,
+) {
+    let w = width as usize;
+    let h = height as usize;
+    let (xs, ys) = chroma.shifts();
+    let (cw, ch) = chroma.plane_dims(width, height);
+    let (y_plane, rest) = data.split_at(w * h);
+    let (u_plane, v_plane) = rest.split_at(cw * ch);
+
+    rgb.clear();
+    rgb.reserve(w * h * 3);
+    for y in 0..h {
+        let chroma_row = (y >> ys) * cw;
+        for x in 0..w {
+            let luma = y_plane[y * w + x];
+            let px = if chroma == Chroma::Mono {
+                yuv_to_rgb(luma, 128, 128, full_range)
+            } else {
+                let ci = chroma_row + (x >> xs);
+                yuv_to_rgb(luma, u_plane[ci], v_plane[ci], full_range)
+            };
+            rgb.extend_from_slice(&px);
+        }
+    }
+}
+
+/// Convert a limited-range YUYV (4:2:2 packed) frame to packed RGB24.
+pub fn yuyv_to_rgb(data: &[u8], width: u32, height: u32, rgb: &mut Vec<u8>) {
+    let w = width as usize;
+    let stride = w.div_ceil(2) * 4;
+
+    rgb.clear();
+    rgb.reserve(w * height as usize * 3);
+    for row in data.chunks_exact(stride).take(height as usize) {
+        for x in 0..w {
+            let pair = &row[(x / 2) * 4..(x / 2) * 4 + 4];
+            let luma = pair[(x % 2) * 2];
+            rgb.extend_from_slice(&yuv_to_rgb(luma, pair[1], pair[3], false));
+        }
+    }
+}
+
+/// Convert a limited-range NV12 frame to packed RGB24.
+pub fn nv12_to_rgb(data: &[u8], width: u32, height: u32, rgb: &mut Vec<u8>) {
+    let w = width as usize;
+    let h = height as usize;
+    let (cw, _) = Chroma::Yuv420.plane_dims(width, height);
+    let (y_plane, uv_plane) = data.split_at(w * h);
+
+    rgb.clear();
+    rgb.reserve(w * h * 3);
+    for y in 0..h {
+        let uv_row = (y >> 1) * cw * 2;
+        for x in 0..w {
+            let ci = uv_row + (x >> 1) * 2;
+            let px = yuv_to_rgb(y_plane[y * w + x], uv_plane[ci], uv_plane[ci + 1], false);
+            rgb.extend_from_slice(&px);
+        }
+    }
+}
+
+/// Pixel layouts a v4l2loopback device can be fed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Rgb24,
+    Bgr24,
+    /// YUV 4:2:2 packed as Y0 U Y1 V
+    Yuyv,
+    /// YUV 4:2:0 with Y, U and V planes back to back (V4L2 `YU12`)
+    I420,
+    /// YUV 4:2:0 with a Y plane followed by one interleaved UV plane
+    Nv12,
+}
+
+impl OutputFormat {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "rgb24" => Some(Self::Rgb24),
+            "bgr24" => Some(Self::Bgr24),
+            "yuyv" | "yuyv422" => Some(Self::Yuyv),
+            "i420" | "yuv420" | "yuv420p" | "yu12" => Some(Self::I420),
+            "nv12" => Some(Self::Nv12),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Rgb24 => "rgb24",
+            Self::Bgr24 => "bgr24",
+            Self::Yuyv => "yuyv",
+            Self::I420 => "i420",
+            Self::Nv12 => "nv12",
+        }
+    }
+
+    pub fn is_yuv(self) -> bool {
+        !matches!(self, Self::Rgb24 | Self::Bgr24)
+    }
+
+    /// Bytes per row of the first plane. YUYV rows are padded to an even width.
+    pub fn bytes_per_line(self, width: u32) -> usize {
+        let w = width as usize;
+        match self {
+            Self::Rgb24 | Self::Bgr24 => w * 3,
+            Self::Yuyv => w.div_ceil(2) * 4,
+            Self::I420 | Self::Nv12 => w,
+        }
+    }
+
+    /// Bytes per frame
+    pub fn frame_size(self, width: u32, height: u32) -> usize {
+        match self {
+            Self::Rgb24 | Self::Bgr24 | Self::Yuyv => {
+                self.bytes_per_line(width) * height as usize
+            }
+            Self::I420 | Self::Nv12 => Chroma::Yuv420.frame_size(width, height),
+        }
+    }
+
+    /// Bytes per row, rows and row pitch of each plane when rows of the first
+    /// plane are `stride` bytes apart. I420 chroma rows take half the stride,
+    /// as V4L2 lays them out; unused planes have no rows.
+    fn plane_rows(self, width: u32, height: u32, stride: usize) -> [(usize, usize, usize); 3] {
+        let h = height as usize;
+        let (cw, ch) = Chroma::Yuv420.plane_dims(width, height);
+        match self {
+            Self::Rgb24 | Self::Bgr24 | Self::Yuyv => {
+                [(self.bytes_per_line(width), h, stride), (0, 0, 0), (0, 0, 0)]
+            }
+            Self::I420 => [(width as usize, h, stride), (cw, ch, stride / 2), (cw, ch, stride / 2)],
+            Self::Nv12 => [(width as usize, h, stride), (2 * cw, ch, stride), (0, 0, 0)],
+        }
+    }
+
+    /// Bytes per frame with rows of the first plane `stride` bytes apart
+    pub fn padded_frame_size(self, width: u32, height: u32, stride: usize) -> usize {
+        self.plane_rows(width, height, stride)
+            .iter()
+            .map(|&(_, rows, pitch)| rows * pitch)
+            .sum()
+    }
+
+    /// Copy a packed frame into `out` with rows of the first plane `stride`
+    /// bytes apart, for drivers that pad their lines
+    pub fn pad_rows(self, packed: &[u8], width: u32, height: u32, stride: usize, out: &mut [u8]) {
+        let (mut src, mut dst) = (0, 0);
+        for (row_bytes, rows, pitch) in self.plane_rows(width, height, stride) {
+            for _ in 0..rows {
+                out[dst..dst + row_bytes].copy_from_slice(&packed[src..src + row_bytes]);
+                src += row_bytes;
+                dst += pitch;
+            }
+        }
+    }
+}
+
+/// YCbCr matrix coefficients
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum YuvMatrix {
+    /// SD video, and what most webcams produce
+    Bt601,
+    /// HD video
+    Bt709,
+}
+
+impl YuvMatrix {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "bt601" | "601" => Some(Self::Bt601),
+            "bt709" | "709" => Some(Self::Bt709),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Bt601 => "bt601",
+            Self::Bt709 => "bt709",
+        }
+    }
+
+    /// Red and blue luma weights (Kr, Kb)
+    fn weights(self) -> (f64, f64) {
+        match self {
+            Self::Bt601 => (0.299, 0.114),
+            Self::Bt709 => (0.2126, 0.0722),
+        }
+    }
+}
+
+/// How rendered RGB24 frames are encoded for a loopback device
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutputEncoding {
+    pub format: OutputFormat,
+    pub matrix: YuvMatrix,
+    /// YUV samples use 0..255 rather than Y 16..235 / UV 16..240
+    pub full_range: bool,
+}
+
+impl Default for OutputEncoding {
+    fn default() -> Self {
+        OutputEncoding {
+            format: OutputFormat::Rgb24,
+            matrix: YuvMatrix::Bt601,
+            full_range: false,
+        }
+    }
+}
+
+impl std::fmt::Display for OutputEncoding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.format.is_yuv() {
+            let range = if self.full_range { "full" } else { "limited" };
+            write!(f, "{} ({}, {} range)", self.format.name(), self.matrix.name(), range)
+        } else {
+            write!(f, "{}", self.format.name())
+        }
+    }
+}
+
+impl OutputEncoding {
+    /// Convert a packed RGB24 frame to this encoding, writing the first
+    /// `format.frame_size()` bytes of `out` (e.g. straight into a device buffer).
+    pub fn encode(&self, rgb: &[u8], width: u32, height: u32, out: &mut [u8]) {
+        match self.format {
+            OutputFormat::Rgb24 => {
+                let n = rgb.len().min(out.len());
+                out[..n].copy_from_slice(&rgb[..n]);
+            }
+            OutputFormat::Bgr24 => {
+                for (dst, px) in out.chunks_exact_mut(3).zip(rgb.chunks_exact(3)) {
+                    dst.copy_from_slice(&[px[2], px[1], px[0]]);
+                }
+            }
+            OutputFormat::Yuyv => {
+                let yuv = RgbToYuv::new(self.matrix, self.full_range);
+                rgb_to_yuyv(rgb, width, height, &yuv, out);
+            }
+            OutputFormat::I420 | OutputFormat::Nv12 => {
+                let yuv = RgbToYuv::new(self.matrix, self.full_range);
+                rgb_to_yuv420(rgb, width, height, &yuv, self.format == OutputFormat::Nv12, out);
+            }
+        }
+    }
+}
+
+/// RGB to YCbCr conversion in 16.16 fixed point, one row of weights per
+/// component, with the range scaling folded in.
+pub struct RgbToYuv {
+    y: [i32; 3],
+    u: [i32; 3],
+    v: [i32; 3],
+    y_offset: i32,
+}
+
+impl RgbToYuv {
+    pub fn new(matrix: YuvMatrix, full_range: bool) -> Self {
+        let (kr, kb) = matrix.weights();
+        let kg = 1.0 - kr - kb;
+        let (y_scale, c_scale, y_offset) = if full_range {
+            (1.0, 1.0, 0)
+        } else {
+            (219.0 / 255.0, 224.0 / 255.0, 16)
+        };
+        let fixed = |rgb: [f64; 3], scale: f64| rgb.map(|k| (k * scale * 65536.0).round() as i32);
+        RgbToYuv {
+            y: fixed([kr, kg, kb], y_scale),
+            u: fixed([-kr / (2.0 * (1.0 - kb)), -kg / (2.0 * (1.0 - kb)), 0.5], c_scale),
+            v: fixed([0.5, -kg / (2.0 * (1.0 - kr)), -kb / (2.0 * (1.0 - kr))], c_scale),
+            y_offset,
+        }
+    }
+
+    pub fn luma(&self, r: i32, g: i32, b: i32) -> u8 {
+        let y = (self.y[0] * r + self.y[1] * g + self.y[2] * b + 32768) >> 16;
+        (y + self.y_offset).clamp(0, 255) as u8
+    }
+
+    pub fn chroma(&self, r: i32, g: i32, b: i32) -> (u8, u8) {
+        let u = (self.u[0] * r + self.u[1] * g + self.u[2] * b + 32768) >> 16;
+        let v = (self.v[0] * r + self.v[1] * g + self.v[2] * b + 32768) >> 16;
+        ((u + 128).clamp(0, 255) as u8, (v + 128).clamp(0, 255) as u8)
+    }
+}
+
+/// Average RGB of the pixels in `xs` x `ys` of a packed RGB24 frame
+fn block_average(rgb: &[u8], w: usize, xs: std::ops::Range<usize>, ys: std::ops::Range<usize>) -> (i32, i32, i32) {
+    let (mut r, mut g, mut b, mut n) = (0i32, 0i32, 0i32, 0i32);
+    for y in ys {
+        for x in xs.clone() {
+            let i = (y * w + x) * 3;
+            r += rgb[i] as i32;
+            g += rgb[i + 1] as i32;
+            b += rgb[i + 2] as i32;
+            n += 1;
+        }
+    }
+    ((r + n / 2) / n, (g + n / 2) / n, (b + n / 2) / n)
+}
+
+/// Nearest-neighbour resize of a packed RGB24 frame into `out`, which is
+/// resized to fit
+pub fn scale_rgb(rgb: &[u8], width: u32, height: u32, out_w: u32, out_h: u32, out: &mut Vec<u8>) {
+    let (w, h) = (width as usize, height as usize);
+    let (ow, oh) = (out_w as usize, out_h as usize);
+    out.resize(ow * oh * 3, 0);
+    let xs: Vec<usize> = (0..ow).map(|x| (x * w / ow).min(w - 1) * 3).collect();
+    for (y, row) in out.chunks_exact_mut(ow * 3).enumerate() {
+        let src_row = &rgb[(y * h / oh).min(h - 1) * w * 3..];
+        for (px, &sx) in row.chunks_exact_mut(3).zip(&xs) {
+            px.copy_from_slice(&src_row[sx..sx + 3]);
+        }
+    }
+}
+
+/// Convert packed RGB24 to YUYV, averaging chroma over each pixel pair.
+/// Odd widths repeat the last pixel into the padding sample.
+pub fn rgb_to_yuyv(rgb: &[u8], width: u32, height: u32, yuv: &RgbToYuv, out: &mut [u8]) {
+    let w = width as usize;
+    let stride = OutputFormat::Yuyv.bytes_per_line(width);
+    for (y, row) in out.chunks_exact_mut(stride).take(height as usize).enumerate() {
+        for (x, pair) in (0..w).step_by(2).zip(row.chunks_exact_mut(4)) {
+            let x1 = (x + 1).min(w - 1);
+            let p0 = &rgb[(y * w + x) * 3..];
+            let p1 = &rgb[(y * w + x1) * 3..];
+            let (r, g, b) = block_average(rgb, w, x..x1 + 1, y..y + 1);
+            let (u, v) = yuv.chroma(r, g, b);
+            pair.copy_from_slice(&[
+                yuv.luma(p0[0] as i32, p0[1] as i32, p0[2] as i32),
+                u,
+                yuv.luma(p1[0] as i32, p1[1] as i32, p1[2] as i32),
+                v,
+            ]);
+        }
+    }
+}
+
+/// Convert packed RGB24 to 4:2:0, either planar I420 or NV12 with interleaved
+/// UV, averaging chroma over each 2x2 block.
+pub fn rgb_to_yuv420(
+    rgb: &[u8],
+    width: u32,
+    height: u32,
+    yuv: &RgbToYuv,
+    interleaved: bool,
+    out: &mut [u8],
+) {
+    let (w, h) = (width as usize, height as usize);
+    let (cw, ch) = Chroma::Yuv420.plane_dims(width, height);
+    let (y_plane, chroma) = out.split_at_mut(w * h);
+    for (luma, px) in y_plane.iter_mut().zip(rgb.chunks_exact(3)) {
+        *luma = yuv.luma(px[0] as i32, px[1] as i32, px[2] as i32);
+    }
+
+    for cy in 0..ch {
+        let ys = cy * 2..(cy * 2 + 2).min(h);
+        for cx in 0..cw {
+            let (r, g, b) = block_average(rgb, w, cx * 2..(cx * 2 + 2).min(w), ys.clone());
+            let (u, v) = yuv.chroma(r, g, b);
+            let i = cy * cw + cx;
+            if interleaved {
+                chroma[i * 2] = u;
+                chroma[i * 2 + 1] = v;
+            } else {
+                chroma[i] = u;
+                chroma[cw * ch + i] = v;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_yuv_to_rgb_range_endpoints() {
+        assert_eq!(yuv_to_rgb(16, 128, 128, false), [0, 0, 0]);
+        assert_eq!(yuv_to_rgb(235, 128, 128, false), [255, 255, 255]);
+        assert_eq!(yuv_to_rgb(0, 128, 128, true), [0, 0, 0]);
+        assert_eq!(yuv_to_rgb(255, 128, 128, true), [255, 255, 255]);
+    }
+
+    #[test]
+    fn test_yuv_to_rgb_primaries() {
+        // BT.601 limited-range pure red is roughly Y=81 U=90 V=240
+        let [r, g, b] = yuv_to_rgb(81, 90, 240, false);
+        assert!(r > 250 && g < 5 && b < 5, "got {},{},{}", r, g, b);
+    }
+
+    #[test]
+    fn test_chroma_frame_size_odd_dimensions() {
+        // 5x3 4:2:0 -> 15 luma + 2 * (3x2) chroma
+        assert_eq!(Chroma::Yuv420.frame_size(5, 3), 15 + 12);
+        assert_eq!(Chroma::Yuv422.frame_size(5, 3), 15 + 18);
+        assert_eq!(Chroma::Yuv444.frame_size(5, 3), 45);
+        assert_eq!(Chroma::Mono.frame_size(5, 3), 15);
+    }
+
+    #[test]
+    fn test_yuyv_to_rgb_odd_width() {
+        // 3x1 frame padded to two YUYV pairs: black, white, (white, padding)
+        let data = [16, 128, 235, 128, 235, 128, 0, 128];
+        assert_eq!(PixelFormat::Yuyv.frame_size(3, 1), data.len());
+        let mut rgb = Vec::new();
+        PixelFormat::Yuyv.to_rgb(&data, 3, 1, &mut rgb);
+        assert_eq!(rgb, vec![0, 0, 0, 255, 255, 255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn test_nv12_luma_plane_and_rgb() {
+        // 2x2 NV12: four luma samples, then one interleaved UV pair
+        let data = [16, 235, 235, 16, 128, 128];
+        assert_eq!(PixelFormat::Nv12.frame_size(2, 2), data.len());
+
+        let luma = PixelFormat::Nv12.luma_plane(&data, 2).unwrap();
+        assert_eq!(luma.data[luma.stride + luma.step], 16);
+        assert_eq!(luma.normalize(235.0), 1.0);
+
+        let mut rgb = Vec::new();
+        PixelFormat::Nv12.to_rgb(&data, 2, 2, &mut rgb);
+        assert_eq!(&rgb[..6], &[0, 0, 0, 255, 255, 255]);
+        assert!(PixelFormat::Rgb24.luma_plane(&rgb, 2).is_none());
+    }
+
+    #[test]
+    fn test_output_format_sizes() {
+        assert_eq!(OutputFormat::Rgb24.bytes_per_line(5), 15);
+        assert_eq!(OutputFormat::Yuyv.bytes_per_line(5), 12);
+        assert_eq!(OutputFormat::Yuyv.frame_size(5, 3), 36);
+        assert_eq!(OutputFormat::I420.bytes_per_line(5), 5);
+        assert_eq!(OutputFormat::I420.frame_size(5, 3), 27);
+        assert_eq!(OutputFormat::Nv12.frame_size(4, 2), 12);
+    }
+
+    #[test]
+    fn test_pad_rows() {
+        // 3x2 I420 padded to a 4-byte stride: Y rows of 4, U and V rows of 2
+        let packed = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10];
+        assert_eq!(OutputFormat::I420.padded_frame_size(3, 2, 4), 12);
+        let mut out = [0; 12];
+        OutputFormat::I420.pad_rows(&packed, 3, 2, 4, &mut out);
+        assert_eq!(out, [1, 2, 3, 0, 4, 5, 6, 0, 7, 8, 9, 10]);
+
+        // NV12 keeps the full stride for its interleaved UV rows
+        let packed = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12];
+        assert_eq!(OutputFormat::Nv12.padded_frame_size(4, 2, 6), 18);
+        let mut out = [0; 18];
+        OutputFormat::Nv12.pad_rows(&packed, 4, 2, 6, &mut out);
+        assert_eq!(out, [1, 2, 3, 4, 0, 0, 5, 6, 7, 8, 0, 0, 9, 10, 11, 12, 0, 0]);
+
+        let mut out = [0; 16];
+        OutputFormat::Rgb24.pad_rows(&[7; 12], 2, 2, 8, &mut out);
+        assert_eq!(out, [7, 7, 7, 7, 7, 7, 0, 0, 7, 7, 7, 7, 7, 7, 0, 0]);
+    }
+
+    #[test]
+    fn test_encode_round_trips_through_yuv() {
+        // White, black and grey survive every matrix and range exactly
+        let rgb = [255, 255, 255, 0, 0, 0, 128, 128, 128, 128, 128, 128];
+        for matrix in [YuvMatrix::Bt601, YuvMatrix::Bt709] {
+            for full_range in [false, true] {
+                let enc = OutputEncoding { format: OutputFormat::Yuyv, matrix, full_range };
+                let mut yuyv = vec![0; 8];
+                enc.encode(&rgb, 4, 1, &mut yuyv);
+                assert_eq!(&yuyv[4..], &[yuyv[4], 128, yuyv[4], 128]);
+                if !full_range {
+                    assert_eq!((yuyv[0], yuyv[2]), (235, 16));
+                    let mut back = Vec::new();
+                    PixelFormat::Yuyv.to_rgb(&yuyv, 4, 1, &mut back);
+                    assert_eq!(&back[6..], &rgb[6..]);
+                }
+            }
+        }
+
+        // BT.601 limited-range red matches the decoder's reference values
+        let enc = OutputEncoding { format: OutputFormat::I420, ..OutputEncoding::default() };
+        let mut i420 = vec![0; 6];
+        enc.encode(&[255, 0, 0].repeat(4), 2, 2, &mut i420);
+        assert_eq!(i420, vec![81, 81, 81, 81, 90, 240]);
+
+        let enc = OutputEncoding { format: OutputFormat::Nv12, ..enc };
+        let mut nv12 = vec![0; 6];
+        enc.encode(&[0, 0, 255].repeat(4), 2, 2, &mut nv12);
+        assert_eq!(&nv12[4..], &[240, 110]);
+    }
+
+    #[test]
+    fn test_scale_rgb() {
+        // 2x1 red/blue doubled to 4x2, then shrunk back
+        let rgb = [255, 0, 0, 0, 0, 255];
+        let mut big = Vec::new();
+        scale_rgb(&rgb, 2, 1, 4, 2, &mut big);
+        let row = [255, 0, 0, 255, 0, 0, 0, 0, 255, 0, 0, 255];
+        assert_eq!(&big[..12], &row);
+        assert_eq!(&big[12..], &row);
+        let mut small = Vec::new();
+        scale_rgb(&big, 4, 2, 2, 1, &mut small);
+        assert_eq!(small, rgb);
+    }
+}
@@ -8,12 +8,30 @@ const HEAD_COLOR: Rgb = Rgb {
 };
 
 /// Per-cell render instruction produced by the rain simulation
+#[derive(Debug, Clone, Copy)]
 pub struct CellRender {
     pub ch: char,
     pub color: Rgb,
     pub intensity: f32,
 }
 
+impl CellRender {
+    /// The colour the glyph is drawn in, dimmed by its intensity, or None
+    /// for a cell that shows nothing. The cut-off is the pixel renderer's,
+    /// below which a glyph doesn't show.
+    pub fn shade(&self) -> Option<Rgb> {
+        if self.ch == ' ' || self.intensity < 0.005 {
+            return None;
+        }
+        let t = self.intensity.min(1.0);
+        Some(Rgb {
+            r: (self.color.r as f32 * t) as u8,
+            g: (self.color.g as f32 * t) as u8,
+            b: (self.color.b as f32 * t) as u8,
+        })
+    }
+}
+
 /// A single rain stream within a column
 struct RainStream {
     /// Fractional row position of the stream head
@@ -65,6 +83,11 @@ impl MatrixRainState {
             .duration_since(std::time::UNIX_EPOCH)
             .map(|d| d.as_nanos() as u64)
             .unwrap_or(0xdeadbeef_cafebabe);
+        Self::with_seed(cols, rows, charset_len, is_movie_mode, seed)
+    }
+
+    /// Rain that falls the same way every time for the same seed and steps
+    pub fn with_seed(cols: u32, rows: u32, charset_len: usize, is_movie_mode: bool, seed: u64) -> Self {
         let mut rng = seed | 1; // ensure non-zero
 
         let mut columns = Vec::with_capacity(cols as usize);
@@ -0,0 +1,83 @@
+use std::io::{self, Read, Stdin};
+
+use crate::capture::{EndOfStream, FrameSource};
+use crate::pixfmt::PixelFormat;
+
+/// Fixed-size raw frames read back to back from a byte stream (normally stdin,
+/// e.g. `ffmpeg -f rawvideo -pix_fmt rgb24 -`).
+///
+/// Frames are read as fast as the capture thread asks for them, so a pipe
+/// producer is paced by the target fps. A short read or EOF ends the stream.
+pub struct RawVideoSource<R: Read> {
+    reader: R,
+    format: PixelFormat,
+    width: u32,
+    height: u32,
+    raw: Vec<u8>,
+    rgb: Vec<u8>,
+}
+
+impl RawVideoSource<Stdin> {
+    pub fn stdin(format: PixelFormat, size: (u32, u32)) -> Self {
+        Self::new(io::stdin(), format, size)
+    }
+}
+
+impl<R: Read> RawVideoSource<R> {
+    pub fn new(reader: R, format: PixelFormat, (width, height): (u32, u32)) -> Self {
+        RawVideoSource {
+            reader,
+            format,
+            width,
+            height,
+            raw: Vec::new(),
+            rgb: Vec::new(),
+        }
+    }
+}
+
+impl<R: Read> FrameSource for RawVideoSource<R> {
+    fn capture_frame(&mut self) -> anyhow::Result<Vec<u8>> {
+        self.raw
+            .resize(self.format.frame_size(self.width, self.height), 0);
+        match self.reader.read_exact(&mut self.raw) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Err(EndOfStream.into()),
+            Err(e) => return Err(anyhow::anyhow!("Raw video read failed: {}", e)),
+        }
+
+        self.format
+            .to_rgb(&self.raw, self.width, self.height, &mut self.rgb);
+        Ok(self.rgb.clone())
+    }
+
+    fn resolution(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    fn stop_stream(&mut self) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reads_whole_frames_then_ends() {
+        // Two 2x1 gray frames followed by half a frame
+        let stream: &[u8] = &[0, 255, 10, 20, 30];
+        let mut source = RawVideoSource::new(stream, PixelFormat::Gray, (2, 1));
+        assert_eq!(source.resolution(), (2, 1));
+        assert_eq!(
+            source.capture_frame().unwrap(),
+            vec![0, 0, 0, 255, 255, 255]
+        );
+        assert_eq!(
+            source.capture_frame().unwrap(),
+            vec![10, 10, 10, 20, 20, 20]
+        );
+
+        let err = source.capture_frame().unwrap_err();
+        assert!(err.is::<EndOfStream>());
+    }
+}
@@ -1,8 +1,9 @@
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
-use crate::config::{BrightnessCurve, Rgb};
-use crate::glyph_cache::GlyphCache;
-use crate::rain::MatrixRainState;
+use crate::config::{BrightnessCurve, Rgb, ScaleMode};
+use crate::glyph_cache::{FontMetrics, GlyphBitmap, GlyphCache};
+use crate::pixfmt::{LumaPlane, PixelFormat};
+use crate::rain::{CellRender, MatrixRainState};
 
 const BLOOM_DS_FACTOR: usize = 4;
 const BLOOM_BLUR_RADIUS: usize = 12;
@@ -10,6 +11,74 @@ const BLOOM_BLUR_PASSES: usize = 3;
 const BLOOM_STRENGTH: f32 = 1.0;
 const BLOOM_THRESHOLD: u8 = 12;
 
+/// Banner shown by `render_no_signal`
+const NO_SIGNAL_TITLE: &str = "CAMERA DISCONNECTED";
+const NO_SIGNAL_STATUS: &str = "reconnecting";
+
+/// The part of the source sampled into the grid and the cells it covers.
+/// Cells outside `cols`/`rows` are the letterbox bars of `ScaleMode::Fit`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Viewport {
+    src_x: f32,
+    src_y: f32,
+    src_w: f32,
+    src_h: f32,
+    col0: usize,
+    row0: usize,
+    cols: usize,
+    rows: usize,
+}
+
+impl Viewport {
+    fn contains(&self, col: usize, row: usize) -> bool {
+        (self.col0..self.col0 + self.cols).contains(&col)
+            && (self.row0..self.row0 + self.rows).contains(&row)
+    }
+
+    /// Source pixel range averaged into a cell inside the viewport
+    fn cell_source(&self, col: usize, row: usize, src_w: u32, src_h: u32) -> (usize, usize, usize, usize) {
+        let cell_w = self.src_w / self.cols as f32;
+        let cell_h = self.src_h / self.rows as f32;
+        let (c, r) = ((col - self.col0) as f32, (row - self.row0) as f32);
+        let x0 = (self.src_x + c * cell_w) as usize;
+        let y0 = (self.src_y + r * cell_h) as usize;
+        let x1 = ((self.src_x + (c + 1.0) * cell_w) as usize).min(src_w as usize);
+        let y1 = ((self.src_y + (r + 1.0) * cell_h) as usize).min(src_h as usize);
+        (x0, y0, x1, y1)
+    }
+}
+
+/// The characters behind a rendered frame: what each cell shows, in what
+/// colour and how brightly, over the theme background
+#[derive(Debug, Clone)]
+pub struct CellGrid {
+    /// Row-major, `columns` wide
+    pub cells: Vec<CellRender>,
+    pub columns: u32,
+    pub rows: u32,
+    pub bg: Rgb,
+    /// Cells vary in colour and intensity (the color theme and rain).
+    /// Otherwise every character is drawn in the foreground colour.
+    pub colored: bool,
+}
+
+impl CellGrid {
+    pub fn blank(columns: u32, rows: u32, bg: Rgb) -> Self {
+        let blank = CellRender {
+            ch: ' ',
+            color: bg,
+            intensity: 0.0,
+        };
+        CellGrid {
+            cells: vec![blank; (columns * rows) as usize],
+            columns,
+            rows,
+            bg,
+            colored: false,
+        }
+    }
+}
+
 pub struct AsciiRenderer {
     glyph_cache: GlyphCache,
     charset: Vec<char>,
@@ -17,10 +86,16 @@ pub struct AsciiRenderer {
     bg: Rgb,
     brightness_curve: BrightnessCurve,
     invert: bool,
+    scale_mode: ScaleMode,
     pub output_width: u32,
     pub output_height: u32,
     ascii_cols: u32,
     ascii_rows: u32,
+    /// Columns asked for, which `ascii_cols` may fall short of on a narrow output
+    requested_columns: u32,
+    /// Set when the grid was sized first and the output follows it
+    fixed_grid: bool,
+    theme_name: String,
     /// Font ascent in pixels (for glyph placement within cell)
     ascent: f32,
     rain_state: Option<MatrixRainState>,
@@ -28,6 +103,10 @@ pub struct AsciiRenderer {
     bloom_buf: Vec<u16>,
     bloom_tmp: Vec<u16>,
     is_color_mode: bool,
+    /// Glyphs for the "no signal" banner, rasterized on first use
+    banner_glyphs: Option<GlyphCache>,
+    /// When the current run of placeholder frames started, for the animated dots
+    no_signal_since: Option<Instant>,
 }
 
 impl AsciiRenderer {
@@ -101,133 +180,471 @@ impl AsciiRenderer {
             bg,
             brightness_curve,
             invert,
+            scale_mode: ScaleMode::default(),
             output_width,
             output_height,
             ascii_cols,
             ascii_rows,
+            requested_columns: ascii_columns,
+            fixed_grid: false,
+            theme_name: theme_name.to_string(),
             ascent,
             rain_state,
             last_render: Instant::now(),
             bloom_buf,
             bloom_tmp,
             is_color_mode,
+            banner_glyphs: None,
+            no_signal_since: None,
         })
     }
 
-    /// Convert an RGB frame to an ASCII-art RGB frame
-    pub fn render(&mut self, rgb_frame: &[u8], frame_width: u32, frame_height: u32) -> Vec<u8> {
-        let out_w = self.output_width as usize;
-        let out_h = self.output_height as usize;
-        let mut output = vec![0u8; out_w * out_h * 3];
+    /// Resize the grid to exactly `columns`x`rows`, keeping the font, and
+    /// size the output to fit it. For a text display with a given number of
+    /// character cells, such as a terminal.
+    pub fn with_grid(mut self, columns: u32, rows: u32) -> Self {
+        let (columns, rows) = (columns.max(1), rows.max(1));
+        self.ascii_cols = columns;
+        self.ascii_rows = rows;
+        self.requested_columns = columns;
+        self.output_width = columns * self.glyph_cache.cell_width as u32;
+        self.output_height = rows * self.glyph_cache.cell_height as u32;
+        self.fixed_grid = true;
+        if self.rain_state.is_some() {
+            self.rain_state = Some(MatrixRainState::new(columns, rows, self.charset.len(), true));
+        }
+        let ds_len = (self.output_width as usize / BLOOM_DS_FACTOR)
+            * (self.output_height as usize / BLOOM_DS_FACTOR)
+            * 3;
+        self.bloom_buf = vec![0u16; ds_len];
+        self.bloom_tmp = vec![0u16; ds_len];
+        self.banner_glyphs = None;
+        self
+    }
 
-        // Fill background
-        for pixel in output.chunks_exact_mut(3) {
-            pixel[0] = self.bg.r;
-            pixel[1] = self.bg.g;
-            pixel[2] = self.bg.b;
+    /// The grid size if it was fixed with `with_grid`, rather than derived
+    /// from the output size
+    pub fn fixed_grid(&self) -> Option<(u32, u32)> {
+        self.fixed_grid.then_some((self.ascii_cols, self.ascii_rows))
+    }
+
+    /// A renderer with the same settings for a different output size
+    pub fn resized(&self, output_width: u32, output_height: u32) -> Result<Self, String> {
+        let mut renderer = AsciiRenderer::new(
+            &self.charset,
+            self.fg,
+            self.bg,
+            self.brightness_curve,
+            self.invert,
+            output_width,
+            output_height,
+            self.requested_columns,
+            &self.theme_name,
+        )?;
+        renderer.set_scale_mode(self.scale_mode);
+        Ok(renderer)
+    }
+
+    /// ASCII grid size as (columns, rows)
+    pub fn grid_size(&self) -> (u32, u32) {
+        (self.ascii_cols, self.ascii_rows)
+    }
+
+    pub fn font_metrics(&self) -> FontMetrics {
+        self.glyph_cache.metrics()
+    }
+
+    pub fn set_scale_mode(&mut self, mode: ScaleMode) {
+        self.scale_mode = mode;
+    }
+
+    /// Whether rendering samples per-cell colour from the source frame
+    pub fn needs_color(&self) -> bool {
+        self.is_color_mode
+    }
+
+    /// Restart the rain from `seed`, so the same frames at the same timing
+    /// always give the same picture
+    pub fn seed_rain(&mut self, seed: u64) {
+        if self.rain_state.is_some() {
+            self.rain_state = Some(MatrixRainState::with_seed(
+                self.ascii_cols,
+                self.ascii_rows,
+                self.charset.len(),
+                true,
+                seed,
+            ));
+        }
+    }
+
+    /// Let the rain fall for `duration` in steps of `step` without drawing
+    /// anything, so the first frame shows it already under way rather than
+    /// just starting from the top
+    pub fn run_rain(&mut self, duration: Duration, step: Duration) {
+        if let Some(ref mut rain) = self.rain_state {
+            let steps = (duration.as_secs_f64() / step.as_secs_f64()) as u32;
+            for _ in 0..steps {
+                rain.advance(step.as_secs_f32());
+            }
         }
+    }
+
+    /// Work out what each cell of the grid shows for a frame. YUV and gray
+    /// frames are sampled straight from their luma plane; they are only
+    /// converted to RGB when the color theme needs per-cell colour. Advances
+    /// the rain in the matrix theme by the time since the last frame.
+    pub fn analyze(
+        &mut self,
+        frame: &[u8],
+        format: PixelFormat,
+        frame_width: u32,
+        frame_height: u32,
+    ) -> CellGrid {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_render);
+        self.last_render = now;
+        self.analyze_timed(frame, format, frame_width, frame_height, elapsed)
+    }
+
+    /// `analyze` for frames that carry their own timing, such as a file
+    /// converted faster than real time: the rain advances by `interval`
+    /// rather than by the wall clock.
+    pub fn analyze_timed(
+        &mut self,
+        frame: &[u8],
+        format: PixelFormat,
+        frame_width: u32,
+        frame_height: u32,
+        interval: Duration,
+    ) -> CellGrid {
+        let mut cells = CellGrid::blank(self.ascii_cols, self.ascii_rows, self.bg);
+        cells.colored = self.rain_state.is_some() || self.is_color_mode;
+        self.no_signal_since = None;
 
         // Guard against short/malformed frames from the camera
-        let expected = (frame_width as usize) * (frame_height as usize) * 3;
-        if rgb_frame.len() < expected {
-            return output;
+        let expected = format.frame_size(frame_width, frame_height);
+        if frame.len() < expected {
+            return cells;
         }
 
-        // Step 1: Convert to grayscale
-        let grayscale = rgb_to_grayscale(rgb_frame, frame_width, frame_height);
+        // Step 1: Find the luma samples, converting RGB to grayscale
+        let grayscale;
+        let luma = match format.luma_plane(frame, frame_width) {
+            Some(luma) => luma,
+            None => {
+                grayscale = rgb_to_grayscale(frame, frame_width, frame_height);
+                LumaPlane {
+                    data: &grayscale,
+                    stride: frame_width as usize,
+                    step: 1,
+                    limited_range: false,
+                }
+            }
+        };
 
         // Step 2: Downsample to ASCII grid (sqrt lifts midtones for all themes)
-        let grid: Vec<f32> = self.downsample_to_grid(&grayscale, frame_width, frame_height)
+        let view = self.viewport(frame_width, frame_height);
+        let grid: Vec<f32> = self.downsample_to_grid(&luma, &view, frame_width, frame_height)
             .into_iter()
             .map(|b| b.sqrt())
             .collect();
 
-        if self.rain_state.is_some() {
-            // Rain path: advance simulation, compute cells, composite
-            let now = Instant::now();
-            let dt = now.duration_since(self.last_render).as_secs_f32();
-            self.last_render = now;
-
-            let rain = self.rain_state.as_mut().unwrap();
-            rain.advance(dt);
-
-            // Re-borrow as immutable for compute_cells
-            let rain = self.rain_state.as_ref().unwrap();
-            let cells = rain.compute_cells(
+        // Step 3: Pick each cell's character, colour and intensity
+        if let Some(ref mut rain) = self.rain_state {
+            // Rain: advance the simulation and let the image modulate it
+            rain.advance(interval.as_secs_f32());
+            cells.cells = rain.compute_cells(
                 &grid,
                 &self.charset,
                 self.brightness_curve,
                 self.invert,
                 self.fg,
             );
-
-            self.composite_rain_glyphs(&cells, &mut output);
-            apply_bloom(
-                &mut output,
-                &mut self.bloom_buf,
-                &mut self.bloom_tmp,
-                out_w,
-                out_h,
-            );
-        } else if self.is_color_mode {
-            // Color mode: per-cell webcam color
-            let color_grid = self.downsample_to_color_grid(rgb_frame, frame_width, frame_height);
-            let chars = self.map_to_characters(&grid);
-            let cells: Vec<crate::rain::CellRender> = grid
+        } else {
+            // Brightness picks the character. The color theme takes each
+            // cell's colour from the frame and dims it with the brightness;
+            // the others draw every character at full foreground colour.
+            let colors = if self.is_color_mode {
+                let converted;
+                let rgb_frame = if format == PixelFormat::Rgb24 {
+                    frame
+                } else {
+                    let mut rgb = Vec::new();
+                    format.to_rgb(frame, frame_width, frame_height, &mut rgb);
+                    converted = rgb;
+                    &converted
+                };
+                Some(self.downsample_to_color_grid(rgb_frame, &view, frame_width, frame_height))
+            } else {
+                None
+            };
+            cells.cells = grid
                 .iter()
-                .zip(chars.iter())
-                .zip(color_grid.iter())
-                .map(|((&brightness, &ch), color)| {
+                .enumerate()
+                .map(|(i, &brightness)| {
                     let mut t = self.brightness_curve.apply(brightness);
                     if self.invert {
                         t = 1.0 - t;
                     }
-                    crate::rain::CellRender {
-                        ch,
-                        color: *color,
-                        intensity: t,
+                    let ch = self.character_for(t);
+                    match colors {
+                        Some(ref colors) => CellRender {
+                            ch,
+                            color: colors[i],
+                            intensity: t,
+                        },
+                        None => CellRender {
+                            ch,
+                            color: self.fg,
+                            intensity: if ch == ' ' { 0.0 } else { 1.0 },
+                        },
                     }
                 })
                 .collect();
-            self.composite_rain_glyphs(&cells, &mut output);
-        } else {
-            // Normal path: map brightness to characters and composite
-            let chars = self.map_to_characters(&grid);
-            self.composite_glyphs(&chars, &mut output);
+        }
+        self.blank_outside(&view, &mut cells.cells);
+        cells
+    }
+
+    /// Draw a cell grid as an RGB24 frame at the output size, with the glow
+    /// and bloom of the matrix theme. Cells past the output's edges are cut off.
+    pub fn rasterize(&mut self, grid: &CellGrid) -> Vec<u8> {
+        let out_w = self.output_width as usize;
+        let out_h = self.output_height as usize;
+        let mut output = vec![0u8; out_w * out_h * 3];
+        for pixel in output.chunks_exact_mut(3) {
+            pixel.copy_from_slice(&[grid.bg.r, grid.bg.g, grid.bg.b]);
         }
 
+        self.composite_cells(grid, &mut output);
+        if self.rain_state.is_some() {
+            apply_bloom(
+                &mut output,
+                &mut self.bloom_buf,
+                &mut self.bloom_tmp,
+                out_w,
+                out_h,
+            );
+        }
         output
     }
 
-    fn downsample_to_grid(&self, gray: &[u8], src_w: u32, src_h: u32) -> Vec<f32> {
+    /// Placeholder for while the source is unavailable, as cells and as a
+    /// frame: a "camera disconnected" banner over the theme background, or
+    /// over dimmed rain in the matrix theme. The frame's banner is drawn in a
+    /// larger font than the grid's.
+    pub fn render_no_signal(&mut self) -> (CellGrid, Vec<u8>) {
+        let mut cells = CellGrid::blank(self.ascii_cols, self.ascii_rows, self.bg);
+        if let Some(ref mut rain) = self.rain_state {
+            let now = Instant::now();
+            rain.advance(now.duration_since(self.last_render).as_secs_f32());
+            self.last_render = now;
+            // An all-dark grid leaves the rain at its dimmest
+            let grid = vec![0.0f32; (self.ascii_cols * self.ascii_rows) as usize];
+            cells.cells = rain.compute_cells(&grid, &self.charset, self.brightness_curve, false, self.fg);
+            cells.colored = true;
+        }
+        let mut output = self.rasterize(&cells);
+
+        let since = *self.no_signal_since.get_or_insert_with(Instant::now);
+        let dots = 1 + (since.elapsed().as_millis() / 500 % 3) as usize;
+        let status = format!(
+            "{:<width$}",
+            format!("{}{}", NO_SIGNAL_STATUS, ".".repeat(dots)),
+            width = NO_SIGNAL_STATUS.len() + 3
+        );
+        self.draw_banner(&[NO_SIGNAL_TITLE, &status], &mut output);
+        self.banner_cells(&[NO_SIGNAL_TITLE, &status], &mut cells);
+        (cells, output)
+    }
+
+    /// Draw centred lines of text in a bordered box, sized to about 60% of
+    /// the output width. Uses the ASCII font even for the matrix theme, whose
+    /// glyphs are mirrored katakana.
+    fn draw_banner(&mut self, lines: &[&str], output: &mut [u8]) {
+        let text_cols = lines.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+        // Border plus two columns of padding on each side, one row above and below
+        let box_cols = text_cols + 6;
+        let box_rows = lines.len() + 4;
+
+        if self.banner_glyphs.is_none() {
+            let mut chars: Vec<char> = " +-|."
+                .chars()
+                .chain(NO_SIGNAL_TITLE.chars())
+                .chain(NO_SIGNAL_STATUS.chars())
+                .collect();
+            chars.sort_unstable();
+            chars.dedup();
+            let probe_size = 100.0_f32;
+            let advance_per_unit = match GlyphCache::new(&chars, probe_size, false, false) {
+                Ok(probe) => probe.cell_width as f32 / probe_size,
+                Err(_) => return,
+            };
+            let font_size =
+                (self.output_width as f32 * 0.6 / box_cols as f32 / advance_per_unit).max(6.0);
+            self.banner_glyphs = GlyphCache::new(&chars, font_size, false, false).ok();
+        }
+        let glyphs = match self.banner_glyphs {
+            Some(ref g) => g,
+            None => return,
+        };
+
+        let (out_w, out_h) = (self.output_width as usize, self.output_height as usize);
+        let (cell_w, cell_h) = (glyphs.cell_width, glyphs.cell_height);
+        let x0 = out_w.saturating_sub(box_cols * cell_w) / 2;
+        let y0 = out_h.saturating_sub(box_rows * cell_h) / 2;
+
+        // Clear the box so rain doesn't run through the text
+        for y in y0..(y0 + box_rows * cell_h).min(out_h) {
+            for x in x0..(x0 + box_cols * cell_w).min(out_w) {
+                let idx = (y * out_w + x) * 3;
+                output[idx..idx + 3].copy_from_slice(&[self.bg.r, self.bg.g, self.bg.b]);
+            }
+        }
+
+        for row in 0..box_rows {
+            for col in 0..box_cols {
+                let top_or_bottom = row == 0 || row == box_rows - 1;
+                let side = col == 0 || col == box_cols - 1;
+                let ch = match (top_or_bottom, side) {
+                    (true, true) => '+',
+                    (true, false) => '-',
+                    (false, true) => '|',
+                    (false, false) => {
+                        let line = match row.checked_sub(2).and_then(|i| lines.get(i)) {
+                            Some(line) => line,
+                            None => continue,
+                        };
+                        let indent = (text_cols - line.chars().count()) / 2 + 3;
+                        match col.checked_sub(indent).and_then(|i| line.chars().nth(i)) {
+                            Some(ch) => ch,
+                            None => continue,
+                        }
+                    }
+                };
+                let glyph = match glyphs.get(ch) {
+                    Some(g) if g.width > 0 && g.height > 0 => g,
+                    _ => continue,
+                };
+                let gx = (x0 + col * cell_w) as i32 + glyph.xmin;
+                let gy = (y0 + row * cell_h) as i32
+                    + (glyphs.ascent as i32 - glyph.ymin - glyph.height as i32);
+                blit_glyph(output, out_w, out_h, glyph, gx, gy, self.fg);
+            }
+        }
+    }
+
+    /// The same box as `draw_banner`, written into the cell grid at one
+    /// character per cell and clipped to the grid
+    fn banner_cells(&self, lines: &[&str], grid: &mut CellGrid) {
+        let text_cols = lines.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+        let box_cols = (text_cols + 6).min(self.ascii_cols as usize);
+        let box_rows = (lines.len() + 4).min(self.ascii_rows as usize);
+        let cols = self.ascii_cols as usize;
+        let col0 = (cols - box_cols) / 2;
+        let row0 = (self.ascii_rows as usize - box_rows) / 2;
+        let indent = |line: &str| (text_cols - line.chars().count()) / 2 + 3;
+
+        for row in 0..box_rows {
+            for col in 0..box_cols {
+                let top_or_bottom = row == 0 || row == box_rows - 1;
+                let side = col == 0 || col == box_cols - 1;
+                let ch = match (top_or_bottom, side) {
+                    (true, true) => '+',
+                    (true, false) => '-',
+                    (false, true) => '|',
+                    (false, false) => row
+                        .checked_sub(2)
+                        .and_then(|i| lines.get(i))
+                        .and_then(|line| col.checked_sub(indent(line)).and_then(|i| line.chars().nth(i)))
+                        .unwrap_or(' '),
+                };
+                grid.cells[(row0 + row) * cols + col0 + col] = CellRender {
+                    ch,
+                    color: self.fg,
+                    intensity: if ch == ' ' { 0.0 } else { 1.0 },
+                };
+            }
+        }
+    }
+
+    /// Where the source lands on the grid under the current scale mode. Aspect
+    /// ratios are compared in output pixels, since cells aren't square.
+    fn viewport(&self, src_w: u32, src_h: u32) -> Viewport {
         let cols = self.ascii_cols as usize;
         let rows = self.ascii_rows as usize;
-        let mut grid = vec![0.0f32; cols * rows];
+        let mut view = Viewport {
+            src_x: 0.0,
+            src_y: 0.0,
+            src_w: src_w as f32,
+            src_h: src_h as f32,
+            col0: 0,
+            row0: 0,
+            cols,
+            rows,
+        };
+        let grid_aspect = (cols * self.glyph_cache.cell_width) as f32
+            / (rows * self.glyph_cache.cell_height) as f32;
+        let src_aspect = src_w as f32 / src_h as f32;
+
+        match self.scale_mode {
+            ScaleMode::Stretch => {}
+            ScaleMode::Fill => {
+                if src_aspect > grid_aspect {
+                    view.src_w = src_h as f32 * grid_aspect;
+                    view.src_x = (src_w as f32 - view.src_w) / 2.0;
+                } else {
+                    view.src_h = src_w as f32 / grid_aspect;
+                    view.src_y = (src_h as f32 - view.src_h) / 2.0;
+                }
+            }
+            ScaleMode::Fit => {
+                if src_aspect > grid_aspect {
+                    view.rows = ((rows as f32 * grid_aspect / src_aspect).round() as usize).clamp(1, rows);
+                    view.row0 = (rows - view.rows) / 2;
+                } else {
+                    view.cols = ((cols as f32 * src_aspect / grid_aspect).round() as usize).clamp(1, cols);
+                    view.col0 = (cols - view.cols) / 2;
+                }
+            }
+        }
+        view
+    }
 
-        let cell_src_w = src_w as f32 / cols as f32;
-        let cell_src_h = src_h as f32 / rows as f32;
+    /// Hide cells in the letterbox bars
+    fn blank_outside(&self, view: &Viewport, cells: &mut [CellRender]) {
+        let cols = self.ascii_cols as usize;
+        for (i, cell) in cells.iter_mut().enumerate() {
+            if !view.contains(i % cols, i / cols) {
+                cell.ch = ' ';
+                cell.intensity = 0.0;
+            }
+        }
+    }
+
+    fn downsample_to_grid(&self, luma: &LumaPlane, view: &Viewport, src_w: u32, src_h: u32) -> Vec<f32> {
+        let cols = self.ascii_cols as usize;
+        let rows = self.ascii_rows as usize;
+        let mut grid = vec![0.0f32; cols * rows];
 
         for row in 0..rows {
             for col in 0..cols {
-                let x0 = (col as f32 * cell_src_w) as usize;
-                let y0 = (row as f32 * cell_src_h) as usize;
-                let x1 = ((col + 1) as f32 * cell_src_w) as usize;
-                let y1 = ((row + 1) as f32 * cell_src_h) as usize;
-
-                let x1 = x1.min(src_w as usize);
-                let y1 = y1.min(src_h as usize);
+                if !view.contains(col, row) {
+                    continue;
+                }
+                let (x0, y0, x1, y1) = view.cell_source(col, row, src_w, src_h);
 
                 let mut sum: u32 = 0;
                 let mut count: u32 = 0;
                 for y in y0..y1 {
                     for x in x0..x1 {
-                        sum += gray[y * src_w as usize + x] as u32;
+                        sum += luma.data[y * luma.stride + x * luma.step] as u32;
                         count += 1;
                     }
                 }
 
                 let avg = if count > 0 {
-                    sum as f32 / count as f32 / 255.0
+                    luma.normalize(sum as f32 / count as f32)
                 } else {
                     0.0
                 };
@@ -238,20 +655,18 @@ impl AsciiRenderer {
         grid
     }
 
-    fn downsample_to_color_grid(&self, rgb: &[u8], src_w: u32, src_h: u32) -> Vec<Rgb> {
+    fn downsample_to_color_grid(&self, rgb: &[u8], view: &Viewport, src_w: u32, src_h: u32) -> Vec<Rgb> {
         let cols = self.ascii_cols as usize;
         let rows = self.ascii_rows as usize;
         let mut grid = Vec::with_capacity(cols * rows);
 
-        let cell_src_w = src_w as f32 / cols as f32;
-        let cell_src_h = src_h as f32 / rows as f32;
-
         for row in 0..rows {
             for col in 0..cols {
-                let x0 = (col as f32 * cell_src_w) as usize;
-                let y0 = (row as f32 * cell_src_h) as usize;
-                let x1 = ((col + 1) as f32 * cell_src_w).min(src_w as f32) as usize;
-                let y1 = ((row + 1) as f32 * cell_src_h).min(src_h as f32) as usize;
+                if !view.contains(col, row) {
+                    grid.push(Rgb { r: 0, g: 0, b: 0 });
+                    continue;
+                }
+                let (x0, y0, x1, y1) = view.cell_source(col, row, src_w, src_h);
 
                 let mut sum_r: u32 = 0;
                 let mut sum_g: u32 = 0;
@@ -281,116 +696,31 @@ impl AsciiRenderer {
         grid
     }
 
-    fn map_to_characters(&self, grid: &[f32]) -> Vec<char> {
+    /// Character for a cell at curved brightness `t`, darkest first
+    fn character_for(&self, t: f32) -> char {
         let n = self.charset.len();
         if n == 0 {
-            return vec![' '; grid.len()];
-        }
-
-        grid.iter()
-            .map(|&brightness| {
-                let mut t = self.brightness_curve.apply(brightness);
-                if self.invert {
-                    t = 1.0 - t;
-                }
-                let idx = (t * (n - 1) as f32).round() as usize;
-                self.charset[idx.min(n - 1)]
-            })
-            .collect()
-    }
-
-    fn composite_glyphs(&self, chars: &[char], output: &mut [u8]) {
-        let out_w = self.output_width as usize;
-        let cell_w = self.glyph_cache.cell_width;
-        let cell_h = self.glyph_cache.cell_height;
-        let cols = self.ascii_cols as usize;
-        let rows = self.ascii_rows as usize;
-        let ascent = self.ascent;
-
-        for row in 0..rows {
-            for col in 0..cols {
-                let ch = chars[row * cols + col];
-
-                // Skip space characters (they're just background)
-                if ch == ' ' {
-                    continue;
-                }
-
-                let glyph = match self.glyph_cache.get(ch) {
-                    Some(g) => g,
-                    None => continue,
-                };
-
-                if glyph.width == 0 || glyph.height == 0 {
-                    continue;
-                }
-
-                // Cell top-left in output
-                let cell_x = col * cell_w;
-                let cell_y = row * cell_h;
-
-                // Glyph position within cell:
-                // x: offset by xmin (horizontal bearing)
-                // y: ascent - ymin - height gives top of glyph from top of cell
-                let glyph_x = cell_x as i32 + glyph.xmin;
-                let glyph_y = cell_y as i32 + (ascent as i32 - glyph.ymin - glyph.height as i32);
-
-                // Blit glyph with alpha blending
-                for gy in 0..glyph.height {
-                    let out_y = glyph_y + gy as i32;
-                    if out_y < 0 || out_y >= self.output_height as i32 {
-                        continue;
-                    }
-
-                    for gx in 0..glyph.width {
-                        let out_x = glyph_x + gx as i32;
-                        if out_x < 0 || out_x >= self.output_width as i32 {
-                            continue;
-                        }
-
-                        let alpha = glyph.coverage[gy * glyph.width + gx];
-                        if alpha == 0 {
-                            continue;
-                        }
-
-                        let idx = (out_y as usize * out_w + out_x as usize) * 3;
-                        if alpha == 255 {
-                            output[idx] = self.fg.r;
-                            output[idx + 1] = self.fg.g;
-                            output[idx + 2] = self.fg.b;
-                        } else {
-                            let a = alpha as u16;
-                            let inv_a = 255 - a;
-                            output[idx] =
-                                ((self.fg.r as u16 * a + output[idx] as u16 * inv_a) / 255) as u8;
-                            output[idx + 1] = ((self.fg.g as u16 * a
-                                + output[idx + 1] as u16 * inv_a)
-                                / 255) as u8;
-                            output[idx + 2] = ((self.fg.b as u16 * a
-                                + output[idx + 2] as u16 * inv_a)
-                                / 255) as u8;
-                        }
-                    }
-                }
-            }
+            return ' ';
         }
+        let idx = (t * (n - 1) as f32).round() as usize;
+        self.charset[idx.min(n - 1)]
     }
 
-    fn composite_rain_glyphs(
-        &self,
-        cells: &[crate::rain::CellRender],
-        output: &mut [u8],
-    ) {
+    /// Blend each cell's glyph onto the frame in its colour scaled by its
+    /// intensity. Bright cells thicken toward the glow variant where the
+    /// font has one (the matrix theme).
+    fn composite_cells(&self, grid: &CellGrid, output: &mut [u8]) {
         let out_w = self.output_width as usize;
         let cell_w = self.glyph_cache.cell_width;
         let cell_h = self.glyph_cache.cell_height;
-        let cols = self.ascii_cols as usize;
-        let rows = self.ascii_rows as usize;
+        let cols = grid.columns as usize;
+        let rows = (grid.rows as usize).min(self.output_height as usize / cell_h + 1);
+        let shown_cols = cols.min(out_w / cell_w + 1);
         let ascent = self.ascent;
 
         for row in 0..rows {
-            for col in 0..cols {
-                let cell = &cells[row * cols + col];
+            for col in 0..shown_cols {
+                let cell = &grid.cells[row * cols + col];
 
                 if cell.ch == ' ' || cell.intensity < 0.005 {
                     continue;
@@ -703,6 +1033,39 @@ fn apply_bloom(
     }
 }
 
+/// Alpha-blend a glyph's coverage onto an RGB24 frame in `color`, clipped to the frame.
+fn blit_glyph(
+    output: &mut [u8],
+    out_w: usize,
+    out_h: usize,
+    glyph: &GlyphBitmap,
+    x: i32,
+    y: i32,
+    color: Rgb,
+) {
+    for gy in 0..glyph.height {
+        let out_y = y + gy as i32;
+        if out_y < 0 || out_y as usize >= out_h {
+            continue;
+        }
+        for gx in 0..glyph.width {
+            let out_x = x + gx as i32;
+            if out_x < 0 || out_x as usize >= out_w {
+                continue;
+            }
+            let alpha = glyph.coverage[gy * glyph.width + gx] as u16;
+            if alpha == 0 {
+                continue;
+            }
+            let idx = (out_y as usize * out_w + out_x as usize) * 3;
+            let inv_a = 255 - alpha;
+            for (c, value) in [color.r, color.g, color.b].into_iter().enumerate() {
+                output[idx + c] = ((value as u16 * alpha + output[idx + c] as u16 * inv_a) / 255) as u8;
+            }
+        }
+    }
+}
+
 fn rgb_to_grayscale(rgb: &[u8], width: u32, height: u32) -> Vec<u8> {
     let pixel_count = (width * height) as usize;
     let mut gray = Vec::with_capacity(pixel_count);
@@ -724,6 +1087,11 @@ mod tests {
     use super::*;
     use crate::config::BrightnessCurve;
 
+    fn render(renderer: &mut AsciiRenderer, frame: &[u8], format: PixelFormat, width: u32, height: u32) -> Vec<u8> {
+        let grid = renderer.analyze(frame, format, width, height);
+        renderer.rasterize(&grid)
+    }
+
     #[test]
     fn test_render_uniform_gray() {
         let charset: Vec<char> = " .:#@".chars().collect();
@@ -750,7 +1118,7 @@ mod tests {
         let in_h = 480;
         let frame: Vec<u8> = vec![128; (in_w * in_h * 3) as usize];
 
-        let output = renderer.render(&frame, in_w, in_h);
+        let output = render(&mut renderer, &frame, PixelFormat::Rgb24, in_w, in_h);
 
         // Output should be the right size
         assert_eq!(output.len(), (out_w * out_h * 3) as usize);
@@ -761,4 +1129,136 @@ mod tests {
             "Output should not be all zeros"
         );
     }
+
+    #[test]
+    fn test_render_luma_matches_rgb() {
+        let charset: Vec<char> = " .:#@".chars().collect();
+        let fg = Rgb { r: 0, g: 255, b: 0 };
+        let bg = Rgb { r: 0, g: 0, b: 0 };
+        let mut renderer = AsciiRenderer::new(
+            &charset,
+            fg,
+            bg,
+            BrightnessCurve::Linear,
+            false,
+            320,
+            240,
+            40,
+            "mono",
+        )
+        .expect("Failed to create renderer");
+
+        // Mid-gray as limited-range NV12 and as RGB should pick the same glyphs
+        let (in_w, in_h) = (64u32, 48u32);
+        let mut nv12 = vec![126u8; (in_w * in_h) as usize];
+        nv12.resize(PixelFormat::Nv12.frame_size(in_w, in_h), 128);
+        let rgb = vec![128u8; (in_w * in_h * 3) as usize];
+
+        let from_nv12 = render(&mut renderer, &nv12, PixelFormat::Nv12, in_w, in_h);
+        let from_rgb = render(&mut renderer, &rgb, PixelFormat::Rgb24, in_w, in_h);
+        assert_eq!(from_nv12, from_rgb);
+    }
+
+    #[test]
+    fn test_render_no_signal() {
+        let charset: Vec<char> = " .:#@".chars().collect();
+        let fg = Rgb { r: 0, g: 255, b: 0 };
+        let bg = Rgb { r: 0, g: 0, b: 40 };
+        let mut renderer =
+            AsciiRenderer::new(&charset, fg, bg, BrightnessCurve::Linear, false, 320, 240, 40, "mono")
+                .expect("Failed to create renderer");
+
+        let (_, card) = renderer.render_no_signal();
+        assert_eq!(card.len(), 320 * 240 * 3);
+        // Background at the corners, banner text in the middle
+        assert_eq!(&card[..3], &[0, 0, 40]);
+        assert!(card.chunks_exact(3).any(|px| px[1] > 100));
+    }
+
+    #[test]
+    fn test_scale_mode_viewport() {
+        let charset: Vec<char> = " .:#@".chars().collect();
+        let fg = Rgb { r: 0, g: 255, b: 0 };
+        let bg = Rgb { r: 0, g: 0, b: 0 };
+        let mut renderer =
+            AsciiRenderer::new(&charset, fg, bg, BrightnessCurve::Linear, false, 320, 240, 40, "mono")
+                .expect("Failed to create renderer");
+        let (cols, rows) = renderer.grid_size();
+        let (cols, rows) = (cols as usize, rows as usize);
+
+        // A 16:9 source on a roughly 4:3 grid
+        renderer.set_scale_mode(ScaleMode::Stretch);
+        let view = renderer.viewport(640, 360);
+        assert_eq!((view.src_w, view.src_h, view.cols, view.rows), (640.0, 360.0, cols, rows));
+
+        renderer.set_scale_mode(ScaleMode::Fill);
+        let view = renderer.viewport(640, 360);
+        assert!(view.src_x > 0.0 && view.src_w < 640.0);
+        assert_eq!((view.src_y, view.src_h), (0.0, 360.0));
+        assert_eq!((view.cols, view.rows), (cols, rows));
+
+        renderer.set_scale_mode(ScaleMode::Fit);
+        let view = renderer.viewport(640, 360);
+        assert!(view.row0 > 0 && view.rows < rows);
+        assert_eq!((view.col0, view.cols), (0, cols));
+
+        // The letterbox bars stay background even for a white frame
+        let frame = vec![255u8; 640 * 360 * 3];
+        let output = render(&mut renderer, &frame, PixelFormat::Rgb24, 640, 360);
+        assert!(output[..320 * 3 * 8].iter().all(|&b| b == 0));
+        assert!(output.iter().any(|&b| b != 0));
+    }
+
+    #[test]
+    fn test_analyze_then_rasterize() {
+        let charset: Vec<char> = " .:#@".chars().collect();
+        let fg = Rgb { r: 255, g: 255, b: 255 };
+        let bg = Rgb { r: 0, g: 0, b: 0 };
+        let mut renderer =
+            AsciiRenderer::new(&charset, fg, bg, BrightnessCurve::Linear, false, 320, 240, 40, "color")
+                .expect("Failed to create renderer");
+        renderer.set_scale_mode(ScaleMode::Fit);
+        let (cols, rows) = renderer.grid_size();
+
+        // A red 16:9 frame: coloured cells in the middle, blank letterbox rows
+        let frame: Vec<u8> = [200u8, 0, 0].repeat(640 * 360);
+        let grid = renderer.analyze(&frame, PixelFormat::Rgb24, 640, 360);
+        assert_eq!((grid.columns, grid.rows), (cols, rows));
+        assert!(grid.cells[..cols as usize].iter().all(|c| c.ch == ' ' && c.intensity == 0.0));
+        let middle = &grid.cells[(rows / 2 * cols) as usize];
+        assert_eq!((middle.color.r, middle.color.g, middle.color.b), (200, 0, 0));
+        assert!(middle.ch != ' ' && middle.intensity > 0.0);
+
+        // Rasterizing the same grid twice gives the same frame, and an empty
+        // grid is just background
+        assert_eq!(renderer.rasterize(&grid), render(&mut renderer, &frame, PixelFormat::Rgb24, 640, 360));
+        let empty = CellGrid::blank(cols, rows, Rgb { r: 1, g: 2, b: 3 });
+        assert!(renderer.rasterize(&empty).chunks_exact(3).all(|px| px == [1, 2, 3]));
+    }
+
+    #[test]
+    fn test_fixed_grid_cells() {
+        let charset: Vec<char> = " .:#@".chars().collect();
+        let fg = Rgb { r: 0, g: 255, b: 0 };
+        let bg = Rgb { r: 0, g: 0, b: 0 };
+        let mut renderer =
+            AsciiRenderer::new(&charset, fg, bg, BrightnessCurve::Linear, false, 320, 240, 40, "mono")
+                .expect("Failed to create renderer")
+                .with_grid(30, 12);
+        assert_eq!(renderer.grid_size(), (30, 12));
+        assert_eq!(renderer.fixed_grid(), Some((30, 12)));
+
+        // A white frame fills every cell with the brightest character
+        let frame = vec![255u8; 64 * 48 * 3];
+        let grid = renderer.analyze(&frame, PixelFormat::Rgb24, 64, 48);
+        assert_eq!(grid.cells.len(), 30 * 12);
+        assert!(grid.cells.iter().all(|c| c.ch == '@' && c.intensity == 1.0));
+        let output = renderer.rasterize(&grid);
+        assert_eq!(output.len(), (renderer.output_width * renderer.output_height * 3) as usize);
+
+        // The placeholder's banner shows up in the cells too
+        let (grid, _) = renderer.render_no_signal();
+        let text: String = grid.cells.iter().map(|c| c.ch).collect();
+        assert!(text.contains(NO_SIGNAL_TITLE));
+    }
 }
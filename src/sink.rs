@@ -0,0 +1,869 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter, Stdout, Write};
+use std::path::{Path, PathBuf};
+use std::os::unix::process::CommandExt;
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crossbeam_channel::{bounded, Receiver, Sender, TrySendError};
+
+use crate::cast::CastSink;
+use crate::framerate::FrameRate;
+use crate::output::{V4l2Options, V4l2Output};
+use crate::pipeline::FpsCounter;
+use crate::pixfmt::{self, Chroma, OutputEncoding, OutputFormat};
+use crate::renderer::CellGrid;
+use crate::y4m::Y4mHeader;
+
+/// A rendered RGB24 frame as handed to sinks, with the character cells it
+/// was drawn from for sinks that output text
+pub struct RenderedFrame {
+    pub rgb: Vec<u8>,
+    pub width: u32,
+    pub height: u32,
+    pub grid: CellGrid,
+}
+
+/// A destination for rendered RGB24 frames. Each attached sink runs on its own
+/// thread behind its own queue, so a slow one only drops its own frames.
+pub trait OutputSink: Send {
+    /// Name shown in logs and `status`, and used to detach the sink
+    fn name(&self) -> String;
+
+    fn write_frame(&mut self, frame: &RenderedFrame) -> anyhow::Result<()>;
+
+    /// Tell the consumer the rate frames will arrive at. Returns the rate the
+    /// destination reports back, if it has a notion of one.
+    fn set_frame_rate(&mut self, _fps: FrameRate) -> anyhow::Result<Option<FrameRate>> {
+        Ok(None)
+    }
+
+    /// Frames are about to change size. Returns the size the destination now
+    /// takes if it could follow; None if it keeps its size and rescales.
+    fn resize(&mut self, _width: u32, _height: u32) -> anyhow::Result<Option<(u32, u32)>> {
+        Ok(None)
+    }
+
+    /// Whether `resize` can change the destination's size. Only these sinks
+    /// are asked to, so a sink stuck on a slow consumer never holds up a
+    /// resize.
+    fn resizable(&self) -> bool {
+        false
+    }
+
+    /// Something that makes a `write_frame` blocked on a consumer that
+    /// stopped reading fail, called from another thread when the sink is
+    /// detached and its thread doesn't finish. Taken when the sink is attached.
+    fn interrupter(&self) -> Option<Box<dyn Fn() + Send>> {
+        None
+    }
+}
+
+/// `frame` as RGB24 at `width`x`height`, rescaled into `scaled` if needed.
+/// For raw outputs whose reader was told a size up front.
+fn frame_at_size<'a>(frame: &'a RenderedFrame, width: u32, height: u32, scaled: &'a mut Vec<u8>) -> &'a [u8] {
+    if (frame.width, frame.height) == (width, height) {
+        &frame.rgb
+    } else {
+        pixfmt::scale_rgb(&frame.rgb, frame.width, frame.height, width, height, scaled);
+        scaled
+    }
+}
+
+/// What a sink's queue does when the sink falls behind
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DropPolicy {
+    /// Discard the incoming frame, keeping what's queued
+    DropNewest,
+    /// Discard the oldest queued frame to make room, keeping latency low
+    DropOldest,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SinkOptions {
+    /// Frames that can wait for the sink before `drop` kicks in
+    pub queue: usize,
+    pub drop: DropPolicy,
+    /// Shut the whole pipeline down if this sink fails. Set for the virtual
+    /// camera the instance was started for, not for sinks added on the side.
+    pub critical: bool,
+}
+
+/// Where to send frames, as given on the command line or control socket:
+/// `/dev/videoN` for a v4l2loopback device, `pipe:<command>` to feed a
+/// command's stdin, `-` for stdout, anything else is a file (or named FIFO)
+/// of raw frames, or a Y4M file if it ends in `.y4m`. A `.cast` path records
+/// the character grid as an asciicast instead.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SinkSpec {
+    V4l2(String),
+    File(PathBuf),
+    /// An asciinema v2 recording of the character cells
+    Cast(PathBuf),
+    Pipe(String),
+    /// With `y4m`, a YUV4MPEG2 stream rather than bare frames
+    Stdout { y4m: bool },
+}
+
+impl SinkSpec {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let s = s.trim();
+        if let Some(command) = s.strip_prefix("pipe:") {
+            if command.trim().is_empty() {
+                return Err("pipe: needs a command, e.g. pipe:ffplay -f rawvideo ...".into());
+            }
+            Ok(SinkSpec::Pipe(command.trim().to_string()))
+        } else if s == "-" {
+            Ok(SinkSpec::Stdout { y4m: false })
+        } else if s.starts_with("/dev/video") {
+            Ok(SinkSpec::V4l2(s.to_string()))
+        } else if s.is_empty() {
+            Err("empty output".into())
+        } else if is_cast_path(Path::new(s)) {
+            Ok(SinkSpec::Cast(PathBuf::from(s)))
+        } else {
+            Ok(SinkSpec::File(PathBuf::from(s)))
+        }
+    }
+
+    /// Open the sink for frames of the given size. Loopback devices are
+    /// opened with `v4l2` and stdout uses its encoding; files and pipes always
+    /// get RGB24. All but loopback devices stay at this size if frames change.
+    pub fn open(
+        &self,
+        width: u32,
+        height: u32,
+        v4l2: V4l2Options,
+    ) -> anyhow::Result<Box<dyn OutputSink>> {
+        match self {
+            SinkSpec::V4l2(device) => Ok(Box::new(V4l2Output::new(device, width, height, v4l2)?)),
+            SinkSpec::File(path) if is_y4m_path(path) => {
+                Ok(Box::new(EncodedSink::y4m_file(path, width, height, v4l2.encoding)?))
+            }
+            SinkSpec::File(path) => Ok(Box::new(FileSink::create(path.clone(), width, height)?)),
+            SinkSpec::Cast(path) => Ok(Box::new(CastSink::create(path)?)),
+            SinkSpec::Pipe(command) => Ok(Box::new(PipeSink::spawn(command, width, height)?)),
+            SinkSpec::Stdout { y4m } => Ok(Box::new(EncodedSink::stdout(width, height, v4l2.encoding, *y4m))),
+        }
+    }
+
+    /// The file this spec writes to, for file and cast outputs
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            SinkSpec::File(path) | SinkSpec::Cast(path) => Some(path),
+            _ => None,
+        }
+    }
+
+    /// Live consumers get a short queue; files and pipes can absorb a burst,
+    /// and a pipe consumer wants the latest frames rather than stale ones.
+    pub fn default_options(&self) -> SinkOptions {
+        match self {
+            SinkSpec::V4l2(_) | SinkSpec::Stdout { .. } => SinkOptions {
+                queue: 2,
+                drop: DropPolicy::DropNewest,
+                critical: false,
+            },
+            SinkSpec::File(_) | SinkSpec::Cast(_) => SinkOptions {
+                queue: 8,
+                drop: DropPolicy::DropNewest,
+                critical: false,
+            },
+            SinkSpec::Pipe(_) => SinkOptions {
+                queue: 8,
+                drop: DropPolicy::DropOldest,
+                critical: false,
+            },
+        }
+    }
+}
+
+impl fmt::Display for SinkSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SinkSpec::V4l2(device) => write!(f, "{}", device),
+            SinkSpec::File(path) | SinkSpec::Cast(path) => write!(f, "{}", path.display()),
+            SinkSpec::Pipe(command) => write!(f, "pipe:{}", command),
+            SinkSpec::Stdout { .. } => write!(f, "-"),
+        }
+    }
+}
+
+/// Raw RGB24 frames appended back to back
+pub struct FileSink {
+    path: PathBuf,
+    writer: BufWriter<File>,
+    size: (u32, u32),
+    scaled: Vec<u8>,
+}
+
+impl FileSink {
+    pub fn create(path: PathBuf, width: u32, height: u32) -> anyhow::Result<Self> {
+        let file = File::create(&path)
+            .map_err(|e| anyhow::anyhow!("Cannot create '{}': {}", path.display(), e))?;
+        Ok(FileSink {
+            path,
+            writer: BufWriter::new(file),
+            size: (width, height),
+            scaled: Vec::new(),
+        })
+    }
+}
+
+impl OutputSink for FileSink {
+    fn name(&self) -> String {
+        self.path.display().to_string()
+    }
+
+    fn write_frame(&mut self, frame: &RenderedFrame) -> anyhow::Result<()> {
+        let (width, height) = self.size;
+        self.writer.write_all(frame_at_size(frame, width, height, &mut self.scaled))?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Raw RGB24 frames written to the stdin of a `sh -c` command
+pub struct PipeSink {
+    command: String,
+    child: Child,
+    /// Taken on drop to close the command's stdin
+    stdin: Option<ChildStdin>,
+    size: (u32, u32),
+    scaled: Vec<u8>,
+}
+
+impl PipeSink {
+    pub fn spawn(command: &str, width: u32, height: u32) -> anyhow::Result<Self> {
+        // In its own process group, so the command and anything `sh` started
+        // can be killed together if it stops reading
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .stdin(Stdio::piped())
+            .process_group(0)
+            .spawn()
+            .map_err(|e| anyhow::anyhow!("Cannot run '{}': {}", command, e))?;
+        let stdin = child.stdin.take();
+        Ok(PipeSink {
+            command: command.to_string(),
+            child,
+            stdin,
+            size: (width, height),
+            scaled: Vec::new(),
+        })
+    }
+}
+
+impl OutputSink for PipeSink {
+    fn name(&self) -> String {
+        format!("pipe:{}", self.command)
+    }
+
+    fn write_frame(&mut self, frame: &RenderedFrame) -> anyhow::Result<()> {
+        let stdin = self
+            .stdin
+            .as_mut()
+            .ok_or_else(|| anyhow::anyhow!("'{}' has no stdin", self.command))?;
+        let (width, height) = self.size;
+        stdin
+            .write_all(frame_at_size(frame, width, height, &mut self.scaled))
+            .map_err(|e| anyhow::anyhow!("'{}' stopped reading: {}", self.command, e))
+    }
+
+    fn interrupter(&self) -> Option<Box<dyn Fn() + Send>> {
+        // The child is reaped only when the sink is dropped, which can't
+        // happen while its thread is stuck in a write, so the group is still ours
+        let group = self.child.id() as libc::pid_t;
+        Some(Box::new(move || {
+            unsafe { libc::kill(-group, libc::SIGKILL) };
+        }))
+    }
+}
+
+impl Drop for PipeSink {
+    fn drop(&mut self) {
+        // Closing stdin is the command's end-of-stream; give it a moment to
+        // finish up (e.g. ffmpeg writing a trailer) before reaping it
+        drop(self.stdin.take());
+        for _ in 0..20 {
+            if let Ok(Some(_)) = self.child.try_wait() {
+                return;
+            }
+            thread::sleep(Duration::from_millis(100));
+        }
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Frames in an output encoding, bare or as a YUV4MPEG2 stream (which needs
+/// 4:2:0 frames). Used for stdout, as in `virtual-ascii --output - | ffmpeg ...`,
+/// and for `.y4m` recordings.
+pub struct EncodedSink<W: Write + Send> {
+    name: String,
+    writer: W,
+    encoding: OutputEncoding,
+    y4m: bool,
+    size: (u32, u32),
+    /// Rate for the Y4M header, which is written with the first frame
+    fps: FrameRate,
+    header_written: bool,
+    scaled: Vec<u8>,
+    encoded: Vec<u8>,
+}
+
+impl<W: Write + Send> EncodedSink<W> {
+    fn new(name: String, writer: W, width: u32, height: u32, encoding: OutputEncoding, y4m: bool) -> Self {
+        EncodedSink {
+            name,
+            writer,
+            encoding,
+            y4m,
+            size: (width, height),
+            fps: FrameRate::from_int(30),
+            header_written: false,
+            scaled: Vec::new(),
+            encoded: vec![0; encoding.format.frame_size(width, height)],
+        }
+    }
+
+    fn write_encoded(&mut self, frame: &RenderedFrame) -> io::Result<()> {
+        let (width, height) = self.size;
+        let rgb = frame_at_size(frame, width, height, &mut self.scaled);
+        let data = if self.encoding.format == OutputFormat::Rgb24 {
+            rgb
+        } else {
+            self.encoding.encode(rgb, width, height, &mut self.encoded);
+            &self.encoded
+        };
+
+        if self.y4m {
+            if !self.header_written {
+                let header = Y4mHeader {
+                    width,
+                    height,
+                    fps_num: self.fps.num(),
+                    fps_den: self.fps.den(),
+                    chroma: Chroma::Yuv420,
+                    full_range: self.encoding.full_range,
+                };
+                writeln!(self.writer, "{}", header)?;
+                self.header_written = true;
+            }
+            self.writer.write_all(b"FRAME\n")?;
+        }
+        self.writer.write_all(data)?;
+        self.writer.flush()
+    }
+}
+
+impl EncodedSink<Stdout> {
+    pub fn stdout(width: u32, height: u32, encoding: OutputEncoding, y4m: bool) -> Self {
+        Self::new("stdout".into(), io::stdout(), width, height, encoding, y4m)
+    }
+}
+
+impl EncodedSink<BufWriter<File>> {
+    /// A Y4M file of I420 frames, in `encoding`'s matrix and range
+    pub fn y4m_file(path: &Path, width: u32, height: u32, encoding: OutputEncoding) -> anyhow::Result<Self> {
+        let file = File::create(path)
+            .map_err(|e| anyhow::anyhow!("Cannot create '{}': {}", path.display(), e))?;
+        let encoding = OutputEncoding {
+            format: OutputFormat::I420,
+            ..encoding
+        };
+        Ok(Self::new(
+            path.display().to_string(),
+            BufWriter::new(file),
+            width,
+            height,
+            encoding,
+            true,
+        ))
+    }
+}
+
+impl<W: Write + Send> OutputSink for EncodedSink<W> {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn write_frame(&mut self, frame: &RenderedFrame) -> anyhow::Result<()> {
+        self.write_encoded(frame)
+            .map_err(|e| anyhow::anyhow!("Writing to {} failed: {}", self.name, e))
+    }
+
+    fn set_frame_rate(&mut self, fps: FrameRate) -> anyhow::Result<Option<FrameRate>> {
+        // A Y4M stream's rate is fixed once the header is out
+        if !self.header_written {
+            self.fps = fps;
+        }
+        Ok(None)
+    }
+}
+
+/// Whether a file output is written as Y4M rather than raw RGB24
+pub fn is_y4m_path(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case("y4m"))
+}
+
+/// Whether an output path is an asciicast recording of the character grid
+pub fn is_cast_path(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case("cast"))
+}
+
+/// A sink shared between its thread and whoever resizes it
+type SharedSink = Arc<Mutex<Box<dyn OutputSink>>>;
+
+/// How long stopping a sink waits for its thread, before and again after
+/// interrupting it
+const STOP_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// An attached sink and its thread. Taken out of the set by `detach`, then
+/// stopped with the set unlocked.
+pub struct RunningSink {
+    name: String,
+    /// Shared with the sink's thread, which locks it for each frame
+    sink: SharedSink,
+    options: SinkOptions,
+    tx: Sender<Arc<RenderedFrame>>,
+    /// Second handle on the queue so DropOldest can evict from the front
+    rx: Receiver<Arc<RenderedFrame>>,
+    stop: Arc<AtomicBool>,
+    handle: thread::JoinHandle<()>,
+    /// Rate the sink reported back after the last `set_frame_rate`
+    device_rate: Arc<Mutex<Option<FrameRate>>>,
+    resizable: bool,
+    interrupter: Option<Box<dyn Fn() + Send>>,
+}
+
+/// The sinks frames are currently fanned out to. Shared between the render
+/// thread, which feeds it, and whoever attaches and detaches sinks.
+pub struct SinkSet {
+    sinks: Vec<RunningSink>,
+    pipeline_shutdown: Arc<AtomicBool>,
+    /// Pipeline frame rate. Sink threads pick up changes before their next
+    /// frame, so a sink blocked on a slow consumer never holds up the caller.
+    frame_rate: Arc<Mutex<FrameRate>>,
+    /// Size of the frames being sent, as set by the render thread
+    frame_size: (u32, u32),
+}
+
+impl SinkSet {
+    pub fn new(pipeline_shutdown: Arc<AtomicBool>, frame_rate: FrameRate) -> Self {
+        SinkSet {
+            sinks: Vec::new(),
+            pipeline_shutdown,
+            frame_rate: Arc::new(Mutex::new(frame_rate)),
+            frame_size: (0, 0),
+        }
+    }
+
+    /// Start a thread for `sink`. Returns its name. Names are unique, so
+    /// attaching the same destination twice is refused.
+    pub fn attach(
+        &mut self,
+        sink: Box<dyn OutputSink>,
+        options: SinkOptions,
+    ) -> anyhow::Result<String> {
+        let name = sink.name();
+        if self.sinks.iter().any(|s| s.name == name) {
+            return Err(anyhow::anyhow!("Output '{}' is already attached", name));
+        }
+
+        let (tx, rx) = bounded::<Arc<RenderedFrame>>(options.queue.max(1));
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_rx = rx.clone();
+        let thread_stop = stop.clone();
+        let pipeline_shutdown = self.pipeline_shutdown.clone();
+        let frame_rate = self.frame_rate.clone();
+        let device_rate = Arc::new(Mutex::new(None));
+        let thread_device_rate = device_rate.clone();
+        let thread_name = name.clone();
+        let resizable = sink.resizable();
+        let interrupter = sink.interrupter();
+        let sink = Arc::new(Mutex::new(sink));
+        let thread_sink = sink.clone();
+        let handle = thread::Builder::new()
+            .name("output".into())
+            .spawn(move || {
+                let mut fps_counter = FpsCounter::new(format!("Output {}", thread_name));
+                let timeout = Duration::from_millis(100);
+                let mut applied_rate = None;
+
+                loop {
+                    if thread_stop.load(Ordering::Relaxed)
+                        || pipeline_shutdown.load(Ordering::Relaxed)
+                    {
+                        break;
+                    }
+
+                    match thread_rx.recv_timeout(timeout) {
+                        Ok(frame) => {
+                            let mut sink = thread_sink.lock().unwrap_or_else(|e| e.into_inner());
+                            let rate = *frame_rate.lock().unwrap_or_else(|e| e.into_inner());
+                            if applied_rate != Some(rate) {
+                                applied_rate = Some(rate);
+                                match sink.set_frame_rate(rate) {
+                                    Ok(reported) => {
+                                        *thread_device_rate.lock().unwrap_or_else(|e| e.into_inner()) = reported;
+                                    }
+                                    Err(e) => eprintln!("  Warning: {}: {}", thread_name, e),
+                                }
+                            }
+                            if let Err(e) = sink.write_frame(&frame) {
+                                if !pipeline_shutdown.load(Ordering::Relaxed) {
+                                    eprintln!("Output error ({}): {}", thread_name, e);
+                                }
+                                if options.critical {
+                                    pipeline_shutdown.store(true, Ordering::SeqCst);
+                                }
+                                break;
+                            }
+                            fps_counter.tick();
+                        }
+                        Err(crossbeam_channel::RecvTimeoutError::Timeout) => continue,
+                        Err(crossbeam_channel::RecvTimeoutError::Disconnected) => break,
+                    }
+                }
+            })?;
+
+        self.sinks.push(RunningSink {
+            name: name.clone(),
+            sink,
+            options,
+            tx,
+            rx,
+            stop,
+            handle,
+            device_rate,
+            resizable,
+            interrupter,
+        });
+        Ok(name)
+    }
+
+    pub fn frame_rate(&self) -> FrameRate {
+        *self.frame_rate.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Change the frame rate declared to every sink, now and when attached later.
+    pub fn set_frame_rate(&self, fps: FrameRate) {
+        *self.frame_rate.lock().unwrap_or_else(|e| e.into_inner()) = fps;
+    }
+
+    /// The rate reported back by the first sink that has one, normally the
+    /// main loopback device.
+    pub fn device_frame_rate(&self) -> Option<FrameRate> {
+        self.sinks
+            .iter()
+            .find_map(|s| *s.device_rate.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.sinks.iter().any(|s| s.name == name)
+    }
+
+    /// Width and height of the frames being sent
+    pub fn frame_size(&self) -> (u32, u32) {
+        self.frame_size
+    }
+
+    pub fn set_frame_size(&mut self, width: u32, height: u32) {
+        self.frame_size = (width, height);
+    }
+
+    /// The sinks that can follow a change in frame size, with their names
+    fn resizable_sinks(&self) -> Vec<(String, SharedSink)> {
+        self.sinks
+            .iter()
+            .filter(|s| s.resizable)
+            .map(|s| (s.name.clone(), s.sink.clone()))
+            .collect()
+    }
+
+    /// Take a sink out of the set, or None if no sink has that name. Unlock
+    /// the set before calling `stop` on it, which waits for the sink's thread.
+    pub fn detach(&mut self, name: &str) -> Option<RunningSink> {
+        let i = self.sinks.iter().position(|s| s.name == name)?;
+        Some(self.sinks.remove(i))
+    }
+
+    pub fn detach_all(&mut self) -> Vec<RunningSink> {
+        self.sinks.drain(..).collect()
+    }
+
+    /// Queue a frame on every sink, applying each one's drop policy. Sinks
+    /// whose thread has exited after an error are removed.
+    pub fn send(&mut self, frame: Arc<RenderedFrame>) {
+        self.sinks.retain(|s| {
+            if s.handle.is_finished() {
+                eprintln!("  Output {} stopped, detaching", s.name);
+            }
+            !s.handle.is_finished()
+        });
+        for sink in &self.sinks {
+            match sink.tx.try_send(frame.clone()) {
+                Err(TrySendError::Full(frame)) if sink.options.drop == DropPolicy::DropOldest => {
+                    let _ = sink.rx.try_recv();
+                    let _ = sink.tx.try_send(frame);
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+impl Drop for SinkSet {
+    fn drop(&mut self) {
+        stop_all(self.detach_all());
+    }
+}
+
+/// Ask the sinks that can to follow a change in frame size. Returns the size
+/// the first one settled on, normally the main loopback device; None if no
+/// sink can follow. Each sink finishes the frame it is writing first, so the
+/// set is unlocked meanwhile and frames, `STATUS` and the other sinks carry on.
+pub fn resize_sinks(sinks: &Mutex<SinkSet>, width: u32, height: u32) -> Option<(u32, u32)> {
+    let resizable = sinks.lock().unwrap_or_else(|e| e.into_inner()).resizable_sinks();
+    let mut settled = None;
+    for (name, sink) in resizable {
+        let mut sink = sink.lock().unwrap_or_else(|e| e.into_inner());
+        match sink.resize(width, height) {
+            Ok(size) => settled = settled.or(size),
+            Err(e) => eprintln!("  Warning: {}: {}", name, e),
+        }
+    }
+    settled
+}
+
+impl RunningSink {
+    /// Signal the sink's thread to exit and wait for it, normally within
+    /// ~100ms of its recv timeout. A thread still stuck writing to a consumer
+    /// after STOP_TIMEOUT is interrupted if the sink can be, and otherwise
+    /// left behind.
+    pub fn stop(self) {
+        self.stop.store(true, Ordering::SeqCst);
+        drop(self.tx);
+        if !wait_finished(&self.handle, STOP_TIMEOUT) {
+            if let Some(interrupt) = self.interrupter {
+                interrupt();
+            }
+            if !wait_finished(&self.handle, STOP_TIMEOUT) {
+                eprintln!("  Output {} is not responding, leaving it behind", self.name);
+                return;
+            }
+        }
+        if self.handle.join().is_err() {
+            eprintln!("  Output {} panicked", self.name);
+        }
+    }
+}
+
+/// Stop detached sinks, signalling them all before waiting on any
+pub fn stop_all(sinks: Vec<RunningSink>) {
+    for sink in &sinks {
+        sink.stop.store(true, Ordering::SeqCst);
+    }
+    for sink in sinks {
+        sink.stop();
+    }
+}
+
+fn wait_finished(handle: &thread::JoinHandle<()>, timeout: Duration) -> bool {
+    let start = std::time::Instant::now();
+    while !handle.is_finished() {
+        if start.elapsed() >= timeout {
+            return false;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Rgb;
+
+    /// Reports each frame it starts writing on `entered`, then blocks until
+    /// `gate` lets it through, like a consumer that stopped reading
+    struct GateSink {
+        entered: Sender<u8>,
+        gate: Receiver<()>,
+    }
+
+    impl OutputSink for GateSink {
+        fn name(&self) -> String {
+            "gate".into()
+        }
+
+        fn write_frame(&mut self, frame: &RenderedFrame) -> anyhow::Result<()> {
+            let _ = self.entered.send(frame.rgb[0]);
+            let _ = self.gate.recv();
+            Ok(())
+        }
+
+        fn set_frame_rate(&mut self, fps: FrameRate) -> anyhow::Result<Option<FrameRate>> {
+            Ok(Some(fps))
+        }
+    }
+
+    fn frame(value: u8) -> Arc<RenderedFrame> {
+        Arc::new(RenderedFrame {
+            rgb: vec![value],
+            width: 1,
+            height: 1,
+            grid: CellGrid::blank(1, 1, Rgb { r: 0, g: 0, b: 0 }),
+        })
+    }
+
+    #[test]
+    fn test_resize_skips_blocked_sinks() {
+        let (entered_tx, entered) = crossbeam_channel::unbounded();
+        let (open_gate, gate) = crossbeam_channel::unbounded();
+        let set = Mutex::new(SinkSet::new(Arc::new(AtomicBool::new(false)), FrameRate::from_int(30)));
+        let options = SinkOptions {
+            queue: 1,
+            drop: DropPolicy::DropOldest,
+            critical: false,
+        };
+        set.lock()
+            .unwrap()
+            .attach(Box::new(GateSink { entered: entered_tx, gate }), options)
+            .unwrap();
+        set.lock().unwrap().send(frame(1));
+        assert_eq!(entered.recv_timeout(Duration::from_secs(5)), Ok(1));
+
+        // The sink is stuck in write_frame: a resize neither waits for it nor
+        // keeps the set locked
+        assert_eq!(resize_sinks(&set, 8, 8), None);
+        assert!(set.try_lock().is_ok());
+
+        drop(open_gate);
+    }
+
+    #[test]
+    fn test_parse_sink_spec() {
+        assert_eq!(SinkSpec::parse("/dev/video20"), Ok(SinkSpec::V4l2("/dev/video20".into())));
+        assert_eq!(
+            SinkSpec::parse("pipe:ffplay -"),
+            Ok(SinkSpec::Pipe("ffplay -".into()))
+        );
+        assert_eq!(SinkSpec::parse("out.rgb"), Ok(SinkSpec::File("out.rgb".into())));
+        assert_eq!(SinkSpec::parse("demo.CAST"), Ok(SinkSpec::Cast("demo.CAST".into())));
+        assert_eq!(SinkSpec::parse("-"), Ok(SinkSpec::Stdout { y4m: false }));
+        assert!(SinkSpec::parse("pipe:").is_err());
+        assert!(SinkSpec::parse("").is_err());
+    }
+
+    #[test]
+    fn test_slow_sink_drops_without_blocking() {
+        let (entered_tx, entered) = crossbeam_channel::unbounded();
+        let (open_gate, gate) = crossbeam_channel::unbounded();
+        let mut set = SinkSet::new(Arc::new(AtomicBool::new(false)), FrameRate::from_int(30));
+        let options = SinkOptions {
+            queue: 1,
+            drop: DropPolicy::DropOldest,
+            critical: false,
+        };
+        let sink = || GateSink {
+            entered: entered_tx.clone(),
+            gate: gate.clone(),
+        };
+        set.attach(Box::new(sink()), options).unwrap();
+        assert!(set.attach(Box::new(sink()), options).is_err());
+
+        // Hold the sink in its first write, then overfill its queue. Sending
+        // returns each time rather than waiting for the sink.
+        set.send(frame(0));
+        assert_eq!(entered.recv_timeout(Duration::from_secs(5)), Ok(0));
+        for i in 1..20u8 {
+            set.send(frame(i));
+        }
+        // The rate was declared before the first frame was written
+        assert_eq!(set.device_frame_rate(), Some(FrameRate::from_int(30)));
+
+        // Only the newest frame survived DropOldest
+        open_gate.send(()).unwrap();
+        assert_eq!(entered.recv_timeout(Duration::from_secs(5)), Ok(19));
+        drop(open_gate);
+        set.detach("gate").unwrap().stop();
+        assert!(set.sinks.is_empty());
+        assert!(entered.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_stop_interrupts_stalled_pipe() {
+        let mut set = SinkSet::new(Arc::new(AtomicBool::new(false)), FrameRate::from_int(30));
+        let spec = SinkSpec::Pipe("sleep 60".into());
+        let sink = spec.open(256, 256, V4l2Options::default()).unwrap();
+        let name = set.attach(sink, spec.default_options()).unwrap();
+        // Several times what the pipe buffers, so the write blocks
+        set.send(Arc::new(RenderedFrame {
+            rgb: vec![0; 256 * 256 * 3],
+            width: 256,
+            height: 256,
+            grid: CellGrid::blank(1, 1, Rgb { r: 0, g: 0, b: 0 }),
+        }));
+        thread::sleep(Duration::from_millis(100));
+
+        // The command is killed rather than waited on for a minute
+        let start = std::time::Instant::now();
+        set.detach(&name).unwrap().stop();
+        assert!(start.elapsed() < Duration::from_secs(10));
+    }
+
+    #[test]
+    fn test_file_sink_keeps_its_size() {
+        let path = std::env::temp_dir().join(format!("virtual-ascii-sink-{}.rgb", std::process::id()));
+        let mut sink = FileSink::create(path.clone(), 2, 1).unwrap();
+        assert_eq!(sink.resize(4, 2).unwrap(), None);
+        sink.write_frame(&RenderedFrame {
+            rgb: vec![7; 4 * 2 * 3],
+            width: 4,
+            height: 2,
+            grid: CellGrid::blank(1, 1, Rgb { r: 0, g: 0, b: 0 }),
+        })
+        .unwrap();
+        drop(sink);
+        // Rescaled to the 2x1 the file was opened for
+        assert_eq!(std::fs::read(&path).unwrap(), vec![7; 2 * 3]);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_y4m_recording() {
+        let path = std::env::temp_dir().join(format!("virtual-ascii-rec-{}.y4m", std::process::id()));
+        let spec = SinkSpec::File(path.clone());
+        let mut sink = spec.open(4, 2, V4l2Options::default()).unwrap();
+        sink.set_frame_rate(FrameRate::new(30000, 1001).unwrap()).unwrap();
+        for _ in 0..2 {
+            sink.write_frame(&RenderedFrame {
+                rgb: vec![255; 4 * 2 * 3],
+                width: 4,
+                height: 2,
+                grid: CellGrid::blank(1, 1, Rgb { r: 0, g: 0, b: 0 }),
+            })
+            .unwrap();
+        }
+        drop(sink);
+
+        let data = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let header = "YUV4MPEG2 W4 H2 F30000:1001 Ip A1:1 C420jpeg XCOLORRANGE=LIMITED\n";
+        assert!(data.starts_with(header.as_bytes()));
+        // Two frames of 4x2 I420: 8 luma + 2 + 2 chroma bytes each
+        let frame = [b"FRAME\n".as_slice(), &[235; 8], &[128; 4]].concat();
+        assert_eq!(&data[header.len()..], [frame.clone(), frame].concat());
+    }
+}
@@ -0,0 +1,291 @@
+use std::fmt::Write as _;
+use std::path::Path;
+
+use crate::config::Rgb;
+use crate::glyph_cache::FontMetrics;
+use crate::rain::CellRender;
+use crate::renderer::CellGrid;
+use crate::sink::RenderedFrame;
+
+/// What `SNAPSHOT` writes the current frame as. PNG is the rendered picture;
+/// the others are the character grid as text.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SnapshotFormat {
+    /// The characters alone
+    Txt,
+    /// Characters with 24-bit ANSI colours, for `cat` in a terminal
+    Ansi,
+    Html,
+    Svg,
+    Png,
+}
+
+impl SnapshotFormat {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "txt" | "text" => Some(Self::Txt),
+            "ansi" | "ans" => Some(Self::Ansi),
+            "html" | "htm" => Some(Self::Html),
+            "svg" => Some(Self::Svg),
+            "png" => Some(Self::Png),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Txt => "txt",
+            Self::Ansi => "ansi",
+            Self::Html => "html",
+            Self::Svg => "svg",
+            Self::Png => "png",
+        }
+    }
+
+    /// The format a file extension stands for
+    pub fn from_path(path: &Path) -> Option<Self> {
+        path.extension().and_then(|e| e.to_str()).and_then(Self::from_name)
+    }
+}
+
+/// Write a frame to `path`, laying text out with the renderer's cell and
+/// font sizes. Returns the number of bytes written.
+pub fn write(format: SnapshotFormat, frame: &RenderedFrame, metrics: FontMetrics, path: &Path) -> anyhow::Result<u64> {
+    let data = encode(format, frame, metrics)?;
+    std::fs::write(path, &data)
+        .map_err(|e| anyhow::anyhow!("Cannot write '{}': {}", path.display(), e))?;
+    Ok(data.len() as u64)
+}
+
+pub fn encode(format: SnapshotFormat, frame: &RenderedFrame, metrics: FontMetrics) -> anyhow::Result<Vec<u8>> {
+    let grid = &frame.grid;
+    let text = match format {
+        SnapshotFormat::Txt => encode_text(grid),
+        SnapshotFormat::Ansi => encode_ansi(grid),
+        SnapshotFormat::Html => encode_html(grid, metrics),
+        SnapshotFormat::Svg => encode_svg(grid, metrics),
+        SnapshotFormat::Png => return encode_png(frame),
+    };
+    Ok(text.into_bytes())
+}
+
+/// Runs of neighbouring cells drawn in the same colour along one row, as
+/// (first column, shade, cells). Blank cells make runs with no shade.
+fn runs(row: &[CellRender]) -> Vec<(usize, Option<Rgb>, &[CellRender])> {
+    let mut runs = Vec::new();
+    let mut start = 0;
+    for col in 1..=row.len() {
+        let shade = row[start].shade();
+        if col == row.len() || !same_shade(row[col].shade(), shade) {
+            runs.push((start, shade, &row[start..col]));
+            start = col;
+        }
+    }
+    runs
+}
+
+fn same_shade(a: Option<Rgb>, b: Option<Rgb>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => (a.r, a.g, a.b) == (b.r, b.g, b.b),
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+fn rows(grid: &CellGrid) -> impl Iterator<Item = &[CellRender]> {
+    grid.cells.chunks(grid.columns.max(1) as usize)
+}
+
+fn encode_text(grid: &CellGrid) -> String {
+    let mut out = String::new();
+    for row in rows(grid) {
+        let line: String = row
+            .iter()
+            .map(|cell| if cell.shade().is_some() { cell.ch } else { ' ' })
+            .collect();
+        out.push_str(line.trim_end());
+        out.push('\n');
+    }
+    out
+}
+
+fn encode_ansi(grid: &CellGrid) -> String {
+    let mut out = String::new();
+    let bg = grid.bg;
+    for row in rows(grid) {
+        let _ = write!(out, "\x1b[48;2;{};{};{}m", bg.r, bg.g, bg.b);
+        for (_, shade, cells) in runs(row) {
+            if let Some(c) = shade {
+                let _ = write!(out, "\x1b[38;2;{};{};{}m", c.r, c.g, c.b);
+                out.extend(cells.iter().map(|cell| cell.ch));
+            } else {
+                out.extend(cells.iter().map(|_| ' '));
+            }
+        }
+        out.push_str("\x1b[0m\n");
+    }
+    out
+}
+
+fn push_escaped(ch: char, out: &mut String) {
+    match ch {
+        '&' => out.push_str("&amp;"),
+        '<' => out.push_str("&lt;"),
+        '>' => out.push_str("&gt;"),
+        '"' => out.push_str("&quot;"),
+        c => out.push(c),
+    }
+}
+
+const FONT_FAMILY: &str = "'Source Code Pro', monospace";
+
+fn encode_html(grid: &CellGrid, metrics: FontMetrics) -> String {
+    let mut out = String::new();
+    let _ = write!(
+        out,
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>virtual-ascii</title>\n</head>\n\
+         <body style=\"margin: 0; background: #{bg}\">\n\
+         <pre style=\"margin: 0; background: #{bg}; font: {size:.1}px/{line}px {family}\">",
+        bg = grid.bg.to_hex(),
+        size = metrics.font_size,
+        line = metrics.cell_height,
+        family = FONT_FAMILY,
+    );
+    for (i, row) in rows(grid).enumerate() {
+        if i > 0 {
+            out.push('\n');
+        }
+        for (_, shade, cells) in runs(row) {
+            if let Some(c) = shade {
+                let _ = write!(out, "<span style=\"color: #{}\">", c.to_hex());
+                cells.iter().for_each(|cell| push_escaped(cell.ch, &mut out));
+                out.push_str("</span>");
+            } else {
+                out.extend(cells.iter().map(|_| ' '));
+            }
+        }
+    }
+    out.push_str("</pre>\n</body>\n</html>\n");
+    out
+}
+
+/// One `<text>` per row, with each run of a colour placed at its column so
+/// the grid lines up whatever the viewer's font advance
+fn encode_svg(grid: &CellGrid, metrics: FontMetrics) -> String {
+    let (cell_w, cell_h) = (metrics.cell_width, metrics.cell_height);
+    let width = grid.columns as usize * cell_w;
+    let height = grid.rows as usize * cell_h;
+    let mut out = String::new();
+    let _ = write!(
+        out,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\">\n\
+         <rect width=\"100%\" height=\"100%\" fill=\"#{bg}\"/>\n\
+         <g font-family=\"{family}\" font-size=\"{size:.1}\" xml:space=\"preserve\">\n",
+        w = width,
+        h = height,
+        bg = grid.bg.to_hex(),
+        family = FONT_FAMILY,
+        size = metrics.font_size,
+    );
+    for (i, row) in rows(grid).enumerate() {
+        let runs: Vec<_> = runs(row)
+            .into_iter()
+            .filter_map(|(col, shade, cells)| shade.map(|c| (col, c, cells)))
+            .collect();
+        if runs.is_empty() {
+            continue;
+        }
+        let _ = write!(out, "<text y=\"{:.1}\">", (i * cell_h) as f32 + metrics.ascent);
+        for (col, color, cells) in runs {
+            let _ = write!(
+                out,
+                "<tspan x=\"{}\" textLength=\"{}\" fill=\"#{}\">",
+                col * cell_w,
+                cells.len() * cell_w,
+                color.to_hex()
+            );
+            cells.iter().for_each(|cell| push_escaped(cell.ch, &mut out));
+            out.push_str("</tspan>");
+        }
+        out.push_str("</text>\n");
+    }
+    out.push_str("</g>\n</svg>\n");
+    out
+}
+
+#[cfg(feature = "images")]
+fn encode_png(frame: &RenderedFrame) -> anyhow::Result<Vec<u8>> {
+    use image::codecs::png::PngEncoder;
+    use image::{ExtendedColorType, ImageEncoder};
+
+    let mut data = Vec::new();
+    PngEncoder::new(&mut data).write_image(&frame.rgb, frame.width, frame.height, ExtendedColorType::Rgb8)?;
+    Ok(data)
+}
+
+#[cfg(not(feature = "images"))]
+fn encode_png(_frame: &RenderedFrame) -> anyhow::Result<Vec<u8>> {
+    Err(anyhow::anyhow!(
+        "PNG snapshots are not available in this build (rebuild with --features images)"
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid() -> CellGrid {
+        let green = Rgb { r: 0, g: 255, b: 0 };
+        let mut grid = CellGrid::blank(3, 2, Rgb { r: 0, g: 15, b: 0 });
+        grid.cells[0] = CellRender { ch: '<', color: green, intensity: 1.0 };
+        grid.cells[1] = CellRender { ch: '#', color: green, intensity: 1.0 };
+        grid.cells[4] = CellRender { ch: '.', color: green, intensity: 0.5 };
+        grid
+    }
+
+    fn metrics() -> FontMetrics {
+        FontMetrics {
+            cell_width: 10,
+            cell_height: 20,
+            font_size: 16.0,
+            ascent: 15.0,
+        }
+    }
+
+    #[test]
+    fn test_snapshot_format_names() {
+        assert_eq!(SnapshotFormat::from_name("HTML"), Some(SnapshotFormat::Html));
+        assert_eq!(SnapshotFormat::from_path(Path::new("me.svg")), Some(SnapshotFormat::Svg));
+        assert_eq!(SnapshotFormat::from_path(Path::new("me.ans")), Some(SnapshotFormat::Ansi));
+        assert_eq!(SnapshotFormat::from_path(Path::new("me.jpg")), None);
+        assert_eq!(SnapshotFormat::from_path(Path::new("me")), None);
+    }
+
+    #[test]
+    fn test_text_snapshots() {
+        let grid = grid();
+        assert_eq!(encode_text(&grid), "<#\n .\n");
+        assert_eq!(
+            encode_ansi(&grid),
+            "\x1b[48;2;0;15;0m\x1b[38;2;0;255;0m<# \x1b[0m\n\
+             \x1b[48;2;0;15;0m \x1b[38;2;0;127;0m. \x1b[0m\n"
+        );
+
+        let html = encode_html(&grid, metrics());
+        assert!(html.contains("font: 16.0px/20px"));
+        assert!(html.contains(
+            "<span style=\"color: #00ff00\">&lt;#</span> \n <span style=\"color: #007f00\">.</span> </pre>"
+        ));
+    }
+
+    #[test]
+    fn test_svg_snapshot() {
+        let svg = encode_svg(&grid(), metrics());
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"30\" height=\"40\""));
+        assert!(svg.contains("<rect width=\"100%\" height=\"100%\" fill=\"#000f00\"/>"));
+        assert!(svg.contains(
+            "<text y=\"15.0\"><tspan x=\"0\" textLength=\"20\" fill=\"#00ff00\">&lt;#</tspan></text>\n\
+             <text y=\"35.0\"><tspan x=\"10\" textLength=\"10\" fill=\"#007f00\">.</tspan></text>\n"
+        ));
+    }
+}
@@ -0,0 +1,387 @@
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{self, IsTerminal, Seek, SeekFrom, Stdout, Write};
+use std::os::fd::{AsFd, FromRawFd, OwnedFd};
+use std::os::unix::io::AsRawFd;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use crossbeam_channel::Sender;
+
+use crate::config::Rgb;
+use crate::control::{RenderAction, RenderCommand};
+use crate::renderer::CellGrid;
+use crate::sink::{OutputSink, RenderedFrame};
+
+nix::ioctl_read_bad!(tiocgwinsz, libc::TIOCGWINSZ, libc::winsize);
+
+/// xterm's default 16-colour palette, which most terminals stay close to
+const BASIC_COLORS: [(u8, u8, u8); 16] = [
+    (0, 0, 0),
+    (205, 0, 0),
+    (0, 205, 0),
+    (205, 205, 0),
+    (0, 0, 238),
+    (205, 0, 205),
+    (0, 205, 205),
+    (229, 229, 229),
+    (127, 127, 127),
+    (255, 0, 0),
+    (0, 255, 0),
+    (255, 255, 0),
+    (92, 92, 255),
+    (255, 0, 255),
+    (0, 255, 255),
+    (255, 255, 255),
+];
+
+/// Levels of each channel in the 6x6x6 cube of the 256-colour palette
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+/// Pixel width of a cell in the frames still rasterized for other outputs,
+/// kept small so a terminal preview doesn't pay for a full-size render
+pub const CELL_WIDTH: u32 = 8;
+
+/// How many colours the terminal's escape sequences can pick from
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColorDepth {
+    /// 24-bit `38;2;r;g;b` colours
+    TrueColor,
+    /// The xterm 256-colour palette
+    Ansi256,
+    /// The 8 basic colours and their bright variants
+    Ansi16,
+}
+
+impl ColorDepth {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "truecolor" | "24bit" => Some(Self::TrueColor),
+            "256" => Some(Self::Ansi256),
+            "16" => Some(Self::Ansi16),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::TrueColor => "truecolor",
+            Self::Ansi256 => "256",
+            Self::Ansi16 => "16",
+        }
+    }
+
+    /// What the terminal advertises through $COLORTERM and $TERM
+    pub fn detect() -> Self {
+        let colorterm = std::env::var("COLORTERM").unwrap_or_default();
+        let term = std::env::var("TERM").unwrap_or_default();
+        if colorterm == "truecolor" || colorterm == "24bit" {
+            Self::TrueColor
+        } else if term.contains("256color") {
+            Self::Ansi256
+        } else {
+            Self::Ansi16
+        }
+    }
+
+    fn color(self, c: Rgb) -> AnsiColor {
+        match self {
+            Self::TrueColor => AnsiColor::Rgb(c.r, c.g, c.b),
+            Self::Ansi256 => AnsiColor::Indexed(ansi256(c)),
+            Self::Ansi16 => AnsiColor::Basic(ansi16(c)),
+        }
+    }
+}
+
+/// A colour as one of the SGR forms
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum AnsiColor {
+    Rgb(u8, u8, u8),
+    Indexed(u8),
+    Basic(u8),
+}
+
+impl AnsiColor {
+    /// Append the escape sequence selecting this colour for the text, or
+    /// for the background
+    fn push_sgr(self, out: &mut String, background: bool) {
+        let base = if background { 40 } else { 30 };
+        let _ = match self {
+            AnsiColor::Rgb(r, g, b) => write!(out, "\x1b[{};2;{};{};{}m", base + 8, r, g, b),
+            AnsiColor::Indexed(n) => write!(out, "\x1b[{};5;{}m", base + 8, n),
+            AnsiColor::Basic(n) if n < 8 => write!(out, "\x1b[{}m", base + n as u32),
+            AnsiColor::Basic(n) => write!(out, "\x1b[{}m", base + 60 + (n - 8) as u32),
+        };
+    }
+}
+
+fn distance(c: Rgb, (r, g, b): (u8, u8, u8)) -> u32 {
+    let d = |a: u8, b: u8| (a as i32 - b as i32).pow(2) as u32;
+    d(c.r, r) + d(c.g, g) + d(c.b, b)
+}
+
+/// Nearest entry of the 256-colour palette, from the colour cube or the
+/// gray ramp
+fn ansi256(c: Rgb) -> u8 {
+    let level = |v: u8| match v {
+        0..=47 => 0,
+        48..=114 => 1,
+        _ => (v - 35) / 40,
+    };
+    let (r, g, b) = (level(c.r), level(c.g), level(c.b));
+    let cube = (
+        CUBE_LEVELS[r as usize],
+        CUBE_LEVELS[g as usize],
+        CUBE_LEVELS[b as usize],
+    );
+
+    // The ramp runs 8, 18, ..., 238
+    let avg = (c.r as u32 + c.g as u32 + c.b as u32) / 3;
+    let step = (avg.saturating_sub(3) / 10).min(23) as u8;
+    let gray = 8 + 10 * step;
+
+    if distance(c, (gray, gray, gray)) < distance(c, cube) {
+        232 + step
+    } else {
+        16 + 36 * r + 6 * g + b
+    }
+}
+
+/// Nearest of the 16 basic colours
+fn ansi16(c: Rgb) -> u8 {
+    (0..16u8)
+        .min_by_key(|&i| distance(c, BASIC_COLORS[i as usize]))
+        .unwrap_or(0)
+}
+
+/// Character cells of the terminal on stdout. Falls back to $COLUMNS and
+/// $LINES, then 80x24, when stdout isn't a terminal.
+pub fn terminal_size() -> (u32, u32) {
+    let mut size = libc::winsize {
+        ws_row: 0,
+        ws_col: 0,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+    let fd = io::stdout().as_raw_fd();
+    if unsafe { tiocgwinsz(fd, &mut size) }.is_ok() && size.ws_col > 0 && size.ws_row > 0 {
+        return (size.ws_col as u32, size.ws_row as u32);
+    }
+    let env = |name: &str| std::env::var(name).ok().and_then(|v| v.parse().ok()).filter(|&n| n > 0);
+    (env("COLUMNS").unwrap_or(80), env("LINES").unwrap_or(24))
+}
+
+/// Append the escape sequences that draw `grid` from the top-left corner,
+/// clipped to `columns`x`rows` cells. Lines are cleared past the grid's
+/// right edge and the screen below its last row, so a grid smaller than the
+/// terminal leaves no leftovers behind. Without a `depth`, the characters
+/// are drawn in the terminal's own colours.
+pub fn encode_grid(grid: &CellGrid, depth: Option<ColorDepth>, (columns, rows): (u32, u32), out: &mut String) {
+    out.push_str("\x1b[H\x1b[0m");
+    if let Some(depth) = depth {
+        depth.color(grid.bg).push_sgr(out, true);
+    }
+
+    let width = grid.columns as usize;
+    let shown_rows = grid.rows.min(rows) as usize;
+    let shown_cols = grid.columns.min(columns) as usize;
+    let mut current = None;
+    for row in 0..shown_rows {
+        if row > 0 {
+            out.push_str("\r\n");
+        }
+        for cell in &grid.cells[row * width..row * width + shown_cols] {
+            let Some(shade) = cell.shade() else {
+                out.push(' ');
+                continue;
+            };
+            let Some(depth) = depth else {
+                out.push(cell.ch);
+                continue;
+            };
+            let color = depth.color(shade);
+            if current != Some(color) {
+                color.push_sgr(out, false);
+                current = Some(color);
+            }
+            out.push(cell.ch);
+        }
+        if shown_cols < columns as usize {
+            out.push_str("\x1b[K");
+        }
+    }
+    out.push_str("\x1b[J");
+}
+
+/// Set while the grid is up on the alternate screen
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// The real stderr, kept while log lines go to `log` instead
+struct HeldStderr {
+    saved: OwnedFd,
+    log: File,
+}
+
+static HELD_STDERR: Mutex<Option<HeldStderr>> = Mutex::new(None);
+
+/// Send stderr to an anonymous in-memory file while the grid is drawn, when
+/// both go to a terminal, so log lines don't land on top of the picture.
+/// `restore_terminal` prints what was held back.
+fn hold_stderr() -> io::Result<()> {
+    if !io::stdout().is_terminal() || !io::stderr().is_terminal() {
+        return Ok(());
+    }
+    let fd = unsafe { libc::memfd_create(c"virtual-ascii-stderr".as_ptr(), libc::MFD_CLOEXEC) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let log = File::from(unsafe { OwnedFd::from_raw_fd(fd) });
+    let saved = io::stderr().as_fd().try_clone_to_owned()?;
+    if unsafe { libc::dup2(log.as_raw_fd(), libc::STDERR_FILENO) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    *HELD_STDERR.lock().unwrap_or_else(|e| e.into_inner()) = Some(HeldStderr { saved, log });
+    Ok(())
+}
+
+/// Leave the alternate screen, show the cursor and put stderr back, printing
+/// the log lines written meanwhile. Besides `Drop`, this runs from the
+/// Ctrl+C handler and on shutdown, as a sink thread that is never joined
+/// doesn't drop its sink. Does nothing after the first call, or without a
+/// `TerminalSink`.
+pub fn restore_terminal() {
+    // Checked before locking stdout, which another output may hold while
+    // blocked on the terminal. Frames check the flag under the lock, so
+    // taking it lets a frame being drawn finish and none is drawn after.
+    if !ACTIVE.swap(false, Ordering::SeqCst) {
+        return;
+    }
+    let mut stdout = io::stdout().lock();
+    let _ = stdout.write_all(b"\x1b[0m\x1b[?25h\x1b[?1049l");
+    let _ = stdout.flush();
+
+    let held = HELD_STDERR.lock().unwrap_or_else(|e| e.into_inner()).take();
+    if let Some(mut held) = held {
+        unsafe { libc::dup2(held.saved.as_raw_fd(), libc::STDERR_FILENO) };
+        let _ = held.log.seek(SeekFrom::Start(0));
+        let _ = io::copy(&mut held.log, &mut io::stderr());
+    }
+}
+
+/// Draws the character grid in the terminal on stdout, redrawing in place
+/// on the alternate screen. When the window changes size, asks the render
+/// thread for a grid that fits it.
+pub struct TerminalSink {
+    stdout: Stdout,
+    depth: ColorDepth,
+    /// Grid size last asked of the render thread
+    size: (u32, u32),
+    render_cmd_tx: Sender<RenderCommand>,
+    text: String,
+}
+
+impl TerminalSink {
+    pub fn new(depth: ColorDepth, render_cmd_tx: Sender<RenderCommand>) -> io::Result<Self> {
+        hold_stderr()?;
+        let mut stdout = io::stdout();
+        // Alternate screen, cursor hidden
+        stdout.write_all(b"\x1b[?1049h\x1b[?25l")?;
+        stdout.flush()?;
+        ACTIVE.store(true, Ordering::SeqCst);
+        Ok(TerminalSink {
+            stdout,
+            depth,
+            size: terminal_size(),
+            render_cmd_tx,
+            text: String::new(),
+        })
+    }
+}
+
+impl OutputSink for TerminalSink {
+    fn name(&self) -> String {
+        "terminal".into()
+    }
+
+    fn write_frame(&mut self, frame: &RenderedFrame) -> anyhow::Result<()> {
+        let size = terminal_size();
+        if size != self.size {
+            // Nobody waits for the reply; frames at the new size are the answer
+            let (response_tx, _) = crossbeam_channel::bounded(1);
+            let fit = RenderCommand {
+                action: RenderAction::FitGrid {
+                    columns: size.0,
+                    rows: size.1,
+                },
+                response_tx,
+            };
+            if self.render_cmd_tx.try_send(fit).is_ok() {
+                self.size = size;
+            }
+        }
+
+        self.text.clear();
+        encode_grid(&frame.grid, Some(self.depth), size, &mut self.text);
+        let mut out = self.stdout.lock();
+        // The terminal was handed back on shutdown
+        if !ACTIVE.load(Ordering::SeqCst) {
+            return Ok(());
+        }
+        out.write_all(self.text.as_bytes())?;
+        out.flush()?;
+        Ok(())
+    }
+}
+
+impl Drop for TerminalSink {
+    fn drop(&mut self) {
+        restore_terminal();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rain::CellRender;
+
+    #[test]
+    fn test_palette_mapping() {
+        let rgb = |r, g, b| Rgb { r, g, b };
+        assert_eq!(ansi256(rgb(0, 0, 0)), 16);
+        assert_eq!(ansi256(rgb(255, 0, 0)), 196);
+        assert_eq!(ansi256(rgb(0, 255, 0)), 46);
+        // Grays land on the ramp rather than the cube's coarser steps
+        assert_eq!(ansi256(rgb(128, 128, 128)), 244);
+        assert_eq!(ansi16(rgb(0, 0, 0)), 0);
+        assert_eq!(ansi16(rgb(0, 255, 0)), 10);
+        assert_eq!(ansi16(rgb(0, 150, 0)), 2);
+    }
+
+    #[test]
+    fn test_encode_grid() {
+        let bg = Rgb { r: 0, g: 15, b: 0 };
+        let green = Rgb { r: 0, g: 255, b: 0 };
+        let mut grid = CellGrid::blank(3, 2, bg);
+        grid.cells[0] = CellRender { ch: '#', color: green, intensity: 1.0 };
+        grid.cells[1] = CellRender { ch: '#', color: green, intensity: 1.0 };
+        grid.cells[3] = CellRender { ch: '.', color: green, intensity: 0.5 };
+
+        let mut out = String::new();
+        encode_grid(&grid, Some(ColorDepth::TrueColor), (80, 24), &mut out);
+        assert_eq!(
+            out,
+            "\x1b[H\x1b[0m\x1b[48;2;0;15;0m\x1b[38;2;0;255;0m## \x1b[K\r\n\
+             \x1b[38;2;0;127;0m.  \x1b[K\x1b[J"
+        );
+
+        // A terminal smaller than the grid cuts it off
+        out.clear();
+        encode_grid(&grid, Some(ColorDepth::Ansi16), (2, 1), &mut out);
+        assert_eq!(out, "\x1b[H\x1b[0m\x1b[40m\x1b[92m##\x1b[J");
+
+        // Without colours, only the characters
+        out.clear();
+        encode_grid(&grid, None, (3, 2), &mut out);
+        assert_eq!(out, "\x1b[H\x1b[0m## \r\n.  \x1b[J");
+    }
+}
@@ -0,0 +1,369 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::capture::{EndOfStream, FrameSource};
+use crate::framerate::FrameRate;
+use crate::pixfmt::{self, Chroma};
+
+const SIGNATURE: &str = "YUV4MPEG2";
+/// Upper bound on stream/frame header lines, guards against reading a non-Y4M file whole
+const MAX_HEADER_LEN: usize = 1024;
+
+/// Parsed YUV4MPEG2 stream header
+#[derive(Debug, Clone, PartialEq)]
+pub struct Y4mHeader {
+    pub width: u32,
+    pub height: u32,
+    pub fps_num: u32,
+    pub fps_den: u32,
+    pub chroma: Chroma,
+    pub full_range: bool,
+}
+
+impl Y4mHeader {
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let mut tokens = line.split_ascii_whitespace();
+        if tokens.next() != Some(SIGNATURE) {
+            return Err("not a YUV4MPEG2 stream (bad signature)".into());
+        }
+
+        let mut width = 0;
+        let mut height = 0;
+        let mut fps = (25, 1);
+        let mut chroma = Chroma::Yuv420;
+        let mut full_range = false;
+
+        for token in tokens {
+            if !token.is_char_boundary(1) {
+                continue;
+            }
+            let (tag, value) = token.split_at(1);
+            match tag {
+                "W" => {
+                    width = value
+                        .parse()
+                        .map_err(|_| format!("invalid width '{}'", value))?
+                }
+                "H" => {
+                    height = value
+                        .parse()
+                        .map_err(|_| format!("invalid height '{}'", value))?
+                }
+                "F" => {
+                    fps = value
+                        .split_once(':')
+                        .and_then(|(n, d)| Some((n.parse().ok()?, d.parse().ok()?)))
+                        .filter(|&(n, d)| n > 0 && d > 0)
+                        .ok_or_else(|| format!("invalid frame rate '{}'", value))?;
+                }
+                "C" => {
+                    chroma =
+                        match value {
+                            "420" | "420jpeg" | "420paldv" | "420mpeg2" => Chroma::Yuv420,
+                            "422" => Chroma::Yuv422,
+                            "444" => Chroma::Yuv444,
+                            "mono" => Chroma::Mono,
+                            _ => return Err(format!(
+                                "unsupported colour space 'C{}' (8-bit 420, 422, 444 or mono only)",
+                                value
+                            )),
+                        };
+                }
+                "X" if value.eq_ignore_ascii_case("COLORRANGE=FULL") => full_range = true,
+                // Interlacing, pixel aspect and other tags don't affect decoding
+                _ => {}
+            }
+        }
+
+        if width == 0 || height == 0 {
+            return Err("missing or zero W/H in stream header".into());
+        }
+
+        Ok(Y4mHeader {
+            width,
+            height,
+            fps_num: fps.0,
+            fps_den: fps.1,
+            chroma,
+            full_range,
+        })
+    }
+
+    /// Bytes of pixel data per frame (excluding the FRAME header line)
+    pub fn frame_size(&self) -> usize {
+        self.chroma.frame_size(self.width, self.height)
+    }
+
+    pub fn frame_duration(&self) -> Duration {
+        Duration::from_secs_f64(self.fps_den as f64 / self.fps_num as f64)
+    }
+}
+
+/// The stream header line, without the trailing newline
+impl fmt::Display for Y4mHeader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let chroma = match self.chroma {
+            Chroma::Yuv420 => "420jpeg",
+            Chroma::Yuv422 => "422",
+            Chroma::Yuv444 => "444",
+            Chroma::Mono => "mono",
+        };
+        let range = if self.full_range { "FULL" } else { "LIMITED" };
+        write!(
+            f,
+            "{} W{} H{} F{}:{} Ip A1:1 C{} XCOLORRANGE={}",
+            SIGNATURE, self.width, self.height, self.fps_num, self.fps_den, chroma, range
+        )
+    }
+}
+
+/// Read one `\n`-terminated header line, bounded to MAX_HEADER_LEN.
+/// Returns None on clean EOF before any bytes.
+fn read_header_line<R: BufRead>(reader: &mut R) -> std::io::Result<Option<String>> {
+    let mut line = Vec::new();
+    let n = reader
+        .by_ref()
+        .take(MAX_HEADER_LEN as u64)
+        .read_until(b'\n', &mut line)?;
+    if n == 0 {
+        return Ok(None);
+    }
+    if line.last() != Some(&b'\n') {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "Y4M header line too long or truncated",
+        ));
+    }
+    line.pop();
+    String::from_utf8(line).map(Some).map_err(|_| {
+        std::io::Error::new(std::io::ErrorKind::InvalidData, "Y4M header is not ASCII")
+    })
+}
+
+/// Sequential reader over the frames of a Y4M stream
+pub struct Y4mReader<R: BufRead> {
+    reader: R,
+    header: Y4mHeader,
+}
+
+impl<R: BufRead> Y4mReader<R> {
+    pub fn new(mut reader: R) -> anyhow::Result<Self> {
+        let line =
+            read_header_line(&mut reader)?.ok_or_else(|| anyhow::anyhow!("empty Y4M stream"))?;
+        let header = Y4mHeader::parse(&line).map_err(|e| anyhow::anyhow!(e))?;
+        Ok(Y4mReader { reader, header })
+    }
+
+    pub fn header(&self) -> &Y4mHeader {
+        &self.header
+    }
+
+    /// Read the next frame's raw planar data into `buf`.
+    /// Returns Ok(false) at end of stream; a partial trailing frame is treated as EOF.
+    pub fn read_frame(&mut self, buf: &mut Vec<u8>) -> anyhow::Result<bool> {
+        let line = match read_header_line(&mut self.reader)? {
+            Some(l) => l,
+            None => return Ok(false),
+        };
+        if !line.starts_with("FRAME") {
+            return Err(anyhow::anyhow!(
+                "corrupt Y4M stream: expected FRAME, got '{:.16}'",
+                line
+            ));
+        }
+
+        buf.resize(self.header.frame_size(), 0);
+        match self.reader.read_exact(buf) {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// Y4M file source. Plays frames at the file's declared frame rate, dropping
+/// frames when the capture thread runs slower than the file.
+pub struct Y4mSource {
+    path: PathBuf,
+    looping: bool,
+    /// Off to hand out every frame as fast as it's asked for
+    paced: bool,
+    reader: Y4mReader<BufReader<File>>,
+    raw: Vec<u8>,
+    rgb: Vec<u8>,
+    started: Instant,
+    frames_read: u64,
+}
+
+impl Y4mSource {
+    pub fn open(path: &Path, looping: bool) -> anyhow::Result<Self> {
+        let reader = Self::open_reader(path)?;
+        Ok(Y4mSource {
+            path: path.to_path_buf(),
+            looping,
+            paced: true,
+            reader,
+            raw: Vec::new(),
+            rgb: Vec::new(),
+            started: Instant::now(),
+            frames_read: 0,
+        })
+    }
+
+    /// Read every frame in order without waiting for the file's clock, e.g.
+    /// to convert it faster than real time
+    pub fn unpaced(mut self) -> Self {
+        self.paced = false;
+        self
+    }
+
+    /// The frame rate the header declares
+    pub fn frame_rate(&self) -> Option<FrameRate> {
+        let header = self.reader.header();
+        FrameRate::new(header.fps_num, header.fps_den)
+    }
+
+    fn open_reader(path: &Path) -> anyhow::Result<Y4mReader<BufReader<File>>> {
+        let file = File::open(path)
+            .map_err(|e| anyhow::anyhow!("Cannot open '{}': {}", path.display(), e))?;
+        Y4mReader::new(BufReader::new(file))
+            .map_err(|e| anyhow::anyhow!("Cannot read '{}': {}", path.display(), e))
+    }
+
+    /// Read the next raw frame, rewinding to the start when looping
+    fn next_raw(&mut self) -> anyhow::Result<()> {
+        if self.reader.read_frame(&mut self.raw)? {
+            self.frames_read += 1;
+            return Ok(());
+        }
+        if !self.looping || self.frames_read == 0 {
+            return Err(EndOfStream.into());
+        }
+        self.reader = Self::open_reader(&self.path)?;
+        self.started = Instant::now();
+        self.frames_read = 0;
+        if !self.reader.read_frame(&mut self.raw)? {
+            return Err(EndOfStream.into());
+        }
+        self.frames_read = 1;
+        Ok(())
+    }
+}
+
+impl FrameSource for Y4mSource {
+    fn capture_frame(&mut self) -> anyhow::Result<Vec<u8>> {
+        let frame_duration = self.reader.header().frame_duration();
+
+        if self.paced {
+            // Wait until the next frame is due on the file's clock
+            let due_at = frame_duration.mul_f64(self.frames_read as f64);
+            let elapsed = self.started.elapsed();
+            if due_at > elapsed {
+                thread::sleep(due_at - elapsed);
+            }
+
+            // Skip ahead to the frame that should be showing now (decode only that one).
+            // The due index is recomputed each step since a loop rewind resets the clock.
+            loop {
+                self.next_raw()?;
+                let due_index =
+                    (self.started.elapsed().as_secs_f64() / frame_duration.as_secs_f64()) as u64;
+                if self.frames_read > due_index {
+                    break;
+                }
+            }
+        } else {
+            self.next_raw()?;
+        }
+
+        let header = self.reader.header();
+        pixfmt::planar_yuv_to_rgb(
+            &self.raw,
+            header.width,
+            header.height,
+            header.chroma,
+            header.full_range,
+            &mut self.rgb,
+        );
+        Ok(self.rgb.clone())
+    }
+
+    fn resolution(&self) -> (u32, u32) {
+        let header = self.reader.header();
+        (header.width, header.height)
+    }
+
+    fn stop_stream(&mut self) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_header() {
+        let h = Y4mHeader::parse("YUV4MPEG2 W640 H480 F30000:1001 Ip A1:1 C444 XCOLORRANGE=FULL")
+            .unwrap();
+        assert_eq!((h.width, h.height), (640, 480));
+        assert_eq!((h.fps_num, h.fps_den), (30000, 1001));
+        assert_eq!(h.chroma, Chroma::Yuv444);
+        assert!(h.full_range);
+
+        // Defaults: 4:2:0, limited range
+        let h = Y4mHeader::parse("YUV4MPEG2 W2 H2 F25:1").unwrap();
+        assert_eq!(h.chroma, Chroma::Yuv420);
+        assert!(!h.full_range);
+        assert_eq!(h.frame_size(), 6);
+
+        assert!(Y4mHeader::parse("YUV4MPEG2 W2 H2 C420p10").is_err());
+        assert!(Y4mHeader::parse("P6 640 480").is_err());
+    }
+
+    #[test]
+    fn test_header_round_trip() {
+        let h = Y4mHeader {
+            width: 1280,
+            height: 720,
+            fps_num: 30000,
+            fps_den: 1001,
+            chroma: Chroma::Yuv420,
+            full_range: false,
+        };
+        assert_eq!(
+            h.to_string(),
+            "YUV4MPEG2 W1280 H720 F30000:1001 Ip A1:1 C420jpeg XCOLORRANGE=LIMITED"
+        );
+        assert_eq!(Y4mHeader::parse(&h.to_string()), Ok(h));
+    }
+
+    #[test]
+    fn test_read_frames_until_eof() {
+        // Two 2x2 4:2:0 frames (4 luma + 1 U + 1 V), then a truncated third
+        let mut stream = b"YUV4MPEG2 W2 H2 F25:1 C420jpeg\n".to_vec();
+        stream.extend_from_slice(b"FRAME\n");
+        stream.extend_from_slice(&[16, 16, 16, 16, 128, 128]);
+        stream.extend_from_slice(b"FRAME Ixyz\n");
+        stream.extend_from_slice(&[235, 235, 235, 235, 128, 128]);
+        stream.extend_from_slice(b"FRAME\n");
+        stream.extend_from_slice(&[1, 2]);
+
+        let mut reader = Y4mReader::new(&stream[..]).unwrap();
+        let mut buf = Vec::new();
+
+        assert!(reader.read_frame(&mut buf).unwrap());
+        let mut rgb = Vec::new();
+        let h = reader.header().clone();
+        pixfmt::planar_yuv_to_rgb(&buf, h.width, h.height, h.chroma, h.full_range, &mut rgb);
+        assert_eq!(rgb, vec![0; 12]);
+
+        assert!(reader.read_frame(&mut buf).unwrap());
+        pixfmt::planar_yuv_to_rgb(&buf, h.width, h.height, h.chroma, h.full_range, &mut rgb);
+        assert_eq!(rgb, vec![255; 12]);
+
+        assert!(!reader.read_frame(&mut buf).unwrap());
+    }
+}